@@ -45,7 +45,7 @@ const DIMS: window::Extent2D = window::Extent2D {
     width: 1024,
     height: 768,
 };
-const DEFAULT_SIZE: &'static [u32] = &[1024, 1024];
+const DEFAULT_SIZE: &[u32] = &[1024, 1024];
 fn main() {
     println!("Hello, world!");
     let event_loop = winit::event_loop::EventLoop::new();
@@ -38,12 +38,12 @@ mod test {
     #[test]
     fn manage() {
         let mut manager = AssetManager::default();
-        assert_eq!(manager.contains("zero"), false);
+        assert!(!manager.contains("zero"));
         assert_eq!(manager.get_or_create("zero", 0), &0);
         assert_eq!(manager.get_or_create("zero", 1), &0);
         assert_eq!(manager.get("zero"), Some(&0));
         assert_eq!(manager.get("one"), None);
-        assert_eq!(manager.contains("zero"), true);
+        assert!(manager.contains("zero"));
         assert_eq!(manager.get_or_create("one", 1), &1);
         assert_eq!(manager.overwrite("zero", 1), &1);
         assert_eq!(manager.overwrite("zero", 0), &0);
@@ -0,0 +1,531 @@
+use super::commands::PlayerCommand;
+use super::prelude::{
+    FollowPath, GraphLayer, Model, Node, RuntimeModelId, Terrain, Texture, Transform,
+};
+use super::terrain::{replace_tiles, Tile, TileEdits, TileType};
+use egui::CtxRef;
+use legion::world::EntityStore;
+use legion::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+/// Reason a skiier stops skiing for a while
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Need {
+    Eat,
+    WarmUp,
+    Restroom,
+    /// Beginners want to learn how to ski
+    Lesson,
+    /// Guests without their own gear rent skis
+    Rent,
+}
+impl Need {
+    pub const ALL: [Need; 5] = [
+        Need::Eat,
+        Need::WarmUp,
+        Need::Restroom,
+        Need::Lesson,
+        Need::Rent,
+    ];
+}
+impl std::fmt::Display for Need {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Eat => write!(f, "Eat"),
+            Self::WarmUp => write!(f, "WarmUp"),
+            Self::Restroom => write!(f, "Restroom"),
+            Self::Lesson => write!(f, "Lesson"),
+            Self::Rent => write!(f, "Rent"),
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuildingKind {
+    /// Food and somewhere warm to sit
+    Lodge,
+    /// Pricier food than the lodge
+    Restaurant,
+    /// Rents out skis
+    Rental,
+    Restroom,
+    /// Guests arrive at parking lots and head back to them when they leave
+    Parking,
+    /// Gives lessons while it has instructors
+    SkiSchool,
+}
+impl BuildingKind {
+    pub const ALL: [BuildingKind; 6] = [
+        BuildingKind::Lodge,
+        BuildingKind::Restaurant,
+        BuildingKind::Rental,
+        BuildingKind::Restroom,
+        BuildingKind::Parking,
+        BuildingKind::SkiSchool,
+    ];
+    pub fn serves(&self, need: Need) -> bool {
+        match self {
+            Self::Lodge => need == Need::Eat || need == Need::WarmUp,
+            Self::Restaurant => need == Need::Eat,
+            Self::Rental => need == Need::Rent,
+            Self::Restroom => need == Need::Restroom,
+            Self::Parking => false,
+            Self::SkiSchool => need == Need::Lesson,
+        }
+    }
+    /// Width along x and depth along y of the footprint in tiles
+    pub fn size(&self) -> (i64, i64) {
+        match self {
+            Self::Lodge => (3, 2),
+            Self::Restaurant => (2, 2),
+            Self::Rental => (2, 1),
+            Self::Restroom => (1, 1),
+            Self::Parking => (4, 3),
+            Self::SkiSchool => (2, 2),
+        }
+    }
+    /// Number of guests that can be inside at once, or groups that can park each day
+    pub fn capacity(&self) -> u32 {
+        match self {
+            Self::Lodge => 20,
+            Self::Restaurant => 12,
+            Self::Rental => 6,
+            Self::Restroom => 4,
+            Self::Parking => 40,
+            Self::SkiSchool => 8,
+        }
+    }
+    /// Paid by each guest who visits, or by each group that parks
+    pub fn price(&self) -> f32 {
+        match self {
+            Self::Lodge => 10.0,
+            Self::Restaurant => 25.0,
+            Self::Rental => 30.0,
+            Self::Restroom => 0.0,
+            Self::Parking => 10.0,
+            Self::SkiSchool => 40.0,
+        }
+    }
+    /// Daily cost of running the building
+    pub fn upkeep(&self) -> f32 {
+        match self {
+            Self::Lodge => 200.0,
+            Self::Restaurant => 300.0,
+            Self::Rental => 150.0,
+            Self::Restroom => 50.0,
+            Self::Parking => 50.0,
+            Self::SkiSchool => 100.0,
+        }
+    }
+    fn color(&self) -> Vector4<u8> {
+        match self {
+            Self::Lodge => Vector4::new(140, 90, 40, 255),
+            Self::Restaurant => Vector4::new(200, 60, 40, 255),
+            Self::Rental => Vector4::new(60, 120, 200, 255),
+            Self::Restroom => Vector4::new(200, 200, 200, 255),
+            Self::Parking => Vector4::new(80, 80, 80, 255),
+            Self::SkiSchool => Vector4::new(220, 180, 0, 255),
+        }
+    }
+}
+impl std::fmt::Display for BuildingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lodge => write!(f, "lodge"),
+            Self::Restaurant => write!(f, "restaurant"),
+            Self::Rental => write!(f, "rental shop"),
+            Self::Restroom => write!(f, "restroom"),
+            Self::Parking => write!(f, "parking lot"),
+            Self::SkiSchool => write!(f, "ski school"),
+        }
+    }
+}
+/// Somewhere skiiers can go to take care of their needs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Building {
+    pub kind: BuildingKind,
+    /// Tile skiiers walk to in order to use the building
+    pub entrance: Node,
+}
+impl Building {
+    /// Tiles covered by the building. The entrance is in front of the middle of the side facing
+    /// -y.
+    pub fn footprint(&self) -> Vec<Vector2<i64>> {
+        footprint(self.kind, self.entrance.node)
+    }
+    /// Whether another guest can go inside given the number of guests inside each building,
+    /// keyed by entrance
+    pub fn has_room(&self, occupancy: &HashMap<Node, u32>) -> bool {
+        occupancy.get(&self.entrance).copied().unwrap_or(0) < self.kind.capacity()
+    }
+}
+fn footprint(kind: BuildingKind, entrance: Vector2<i64>) -> Vec<Vector2<i64>> {
+    let (width, depth) = kind.size();
+    let start = Vector2::new(entrance.x - width / 2, entrance.y + 1);
+    (0..width)
+        .flat_map(|x| (0..depth).map(move |y| start + Vector2::new(x, y)))
+        .collect()
+}
+/// Guests who have visited a building and what they paid
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BuildingStatistics {
+    pub visits: u32,
+    pub revenue: f32,
+}
+/// Reason a building can not be placed
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlacementError {
+    NoTerrain,
+    /// Entrance or footprint is outside of the terrain
+    OffTerrain,
+    /// Tile is taken by another building, a lift or a run
+    Occupied(Vector2<i64>),
+}
+impl std::fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoTerrain => write!(f, "there is no terrain to build on"),
+            Self::OffTerrain => write!(f, "building is off the terrain"),
+            Self::Occupied(tile) => write!(f, "tile ({}, {}) is already taken", tile.x, tile.y),
+        }
+    }
+}
+impl std::error::Error for PlacementError {}
+/// Id of the model used for buildings of a kind
+pub fn building_model_id(kind: BuildingKind) -> String {
+    format!("building_{:?}", kind).to_lowercase()
+}
+/// Block colored by the kind of building
+pub fn building_model(kind: BuildingKind) -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(kind.color(), Vector2::new(8, 8));
+    model
+}
+/// Block covering the footprint of a building with its entrance at `entrance`
+fn building_transform(kind: BuildingKind, entrance: Vector2<i64>, terrain: &Terrain) -> Transform {
+    let (width, depth) = kind.size();
+    let height = terrain.get_transform(&entrance).map(|t| t.y).unwrap_or(0.0);
+    let mut transform = Transform::default();
+    transform.set_scale(Vector3::new(width as f32 / 2.0, 0.5, depth as f32 / 2.0));
+    transform.set_translation(Vector3::new(
+        (entrance.x - width / 2) as f32 + (width - 1) as f32 / 2.0,
+        height + 0.5,
+        (entrance.y + 1) as f32 + (depth - 1) as f32 / 2.0,
+    ));
+    transform
+}
+/// Adds a building to the world without changing the terrain under it
+pub fn push_building(world: &mut World, kind: BuildingKind, entrance: Vector2<i64>) -> Entity {
+    let transform = match <&Terrain>::query().iter(world).next() {
+        Some(terrain) => building_transform(kind, entrance, terrain),
+        None => Transform::default(),
+    };
+    world.push((
+        Building {
+            kind,
+            entrance: Node { node: entrance },
+        },
+        BuildingStatistics::default(),
+        transform,
+        RuntimeModelId::new(building_model_id(kind)),
+    ))
+}
+/// Builds a building with its entrance at `entrance`. The footprint is flattened to the height
+/// of the entrance and blocked so skiiers walk around it. Returns the building along with the
+/// tiles it replaced.
+pub fn place_building(
+    world: &mut World,
+    kind: BuildingKind,
+    entrance: Vector2<i64>,
+) -> Result<(Entity, TileEdits), PlacementError> {
+    let footprint = footprint(kind, entrance);
+    let height = {
+        let terrain = <&Terrain>::query()
+            .iter(world)
+            .next()
+            .ok_or(PlacementError::NoTerrain)?;
+        let mut tiles = footprint.iter().chain(std::iter::once(&entrance));
+        let open = |t: &Vector2<i64>| {
+            terrain
+                .get_tile(t)
+                .map(|t| t.tile_type != TileType::Building)
+        };
+        match tiles.find(|t| open(t) != Some(true)) {
+            Some(tile) if terrain.get_tile(tile).is_none() => {
+                return Err(PlacementError::OffTerrain)
+            }
+            Some(tile) => return Err(PlacementError::Occupied(*tile)),
+            None => (),
+        }
+        terrain.get_transform(&entrance).unwrap().y
+    };
+    let entrances: Vec<Vector2<i64>> = <&Building>::query()
+        .iter(world)
+        .map(|b| b.entrance.node)
+        .collect();
+    let taken = |tile: &Vector2<i64>| {
+        let node = Node { node: *tile };
+        entrances.contains(tile)
+            || <&GraphLayer>::query().iter(world).any(|layer| match layer {
+                GraphLayer::Lift(lift) => lift.start == node || lift.end == node,
+                GraphLayer::Trail(trail) => trail.contains(&node),
+                GraphLayer::Grid { .. } => false,
+            })
+    };
+    if let Some(tile) = footprint.iter().find(|t| taken(t)) {
+        return Err(PlacementError::Occupied(*tile));
+    }
+    if entrances.contains(&entrance) {
+        return Err(PlacementError::Occupied(entrance));
+    }
+    let flattened: TileEdits = footprint
+        .iter()
+        .map(|t| {
+            (
+                *t,
+                Tile {
+                    height,
+                    tile_type: TileType::Building,
+                    snow_depth: 0.0,
+                },
+            )
+        })
+        .collect();
+    let replaced = replace_tiles(world, &flattened);
+    Ok((push_building(world, kind, entrance), replaced))
+}
+/// Number of guests inside each building, keyed by the entrance. Guests are inside while they
+/// wait at the entrance.
+pub fn occupancy<W: EntityStore>(world: &W) -> HashMap<Node, u32> {
+    let entrances: Vec<Node> = <&Building>::query()
+        .iter(world)
+        .map(|b| b.entrance.clone())
+        .collect();
+    let mut occupancy = HashMap::new();
+    for path in <&FollowPath>::query().iter(world) {
+        if let Some(tile) = path.waiting_at() {
+            if entrances.contains(tile) {
+                *occupancy.entry(tile.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    occupancy
+}
+/// Charges one guest for each visit to the building entered at the visit's tile. Returns the
+/// total paid.
+pub fn charge_visits<W: EntityStore>(world: &mut W, visits: &[Node]) -> f32 {
+    let mut total = 0.0;
+    for (building, statistics) in <(&Building, &mut BuildingStatistics)>::query().iter_mut(world) {
+        let count = visits.iter().filter(|v| **v == building.entrance).count() as u32;
+        statistics.visits += count;
+        statistics.revenue += count as f32 * building.kind.price();
+        total += count as f32 * building.kind.price();
+    }
+    total
+}
+/// Building being placed
+struct BuildingDraft {
+    kind: BuildingKind,
+    entrance: Vector2<i64>,
+    /// Entity previewing the building
+    marker: Entity,
+}
+/// Tool for placing buildings
+#[derive(Default)]
+pub struct PlaceBuilding {
+    draft: Option<BuildingDraft>,
+}
+impl PlaceBuilding {
+    /// Draws the buildings window listing buildings and their usage. Returns a command to build
+    /// once placement is done
+    pub fn draw_gui(&mut self, world: &mut World, context: &mut CtxRef) -> Option<PlayerCommand> {
+        let has_terrain = <&Terrain>::query().iter(world).next().is_some();
+        let inside = occupancy(world);
+        let buildings: Vec<String> = <(&Building, &BuildingStatistics)>::query()
+            .iter(world)
+            .map(|(building, statistics)| {
+                format!(
+                    "{} at {}: {}/{} inside, {} visits, ${:.0} revenue",
+                    building.kind,
+                    building.entrance,
+                    inside.get(&building.entrance).copied().unwrap_or(0),
+                    building.kind.capacity(),
+                    statistics.visits,
+                    statistics.revenue
+                )
+            })
+            .collect();
+        let mut start = None;
+        let mut finish = false;
+        let mut cancel = false;
+        let mut direction: Vector2<i64> = Vector2::new(0, 0);
+        let draft = &self.draft;
+        egui::Window::new("buildings").show(context, |ui| {
+            for building in buildings.iter() {
+                ui.label(building);
+            }
+            if !has_terrain {
+                ui.label("Load a scenario to place buildings");
+                return;
+            }
+            match draft {
+                None => {
+                    for kind in BuildingKind::ALL.iter() {
+                        if ui.button(format!("place {}", kind)).clicked {
+                            start = Some(*kind);
+                        }
+                    }
+                }
+                Some(draft) => {
+                    let (width, depth) = draft.kind.size();
+                    ui.label(format!(
+                        "Placing {} ({}x{}), entrance at {}",
+                        draft.kind, width, depth, draft.entrance
+                    ));
+                    ui.label(format!(
+                        "holds {}, charges ${:.0}, costs ${:.0} a day",
+                        draft.kind.capacity(),
+                        draft.kind.price(),
+                        draft.kind.upkeep()
+                    ));
+                    if ui.button("x+").clicked {
+                        direction.x += 1;
+                    }
+                    if ui.button("x-").clicked {
+                        direction.x -= 1;
+                    }
+                    if ui.button("y+").clicked {
+                        direction.y += 1;
+                    }
+                    if ui.button("y-").clicked {
+                        direction.y -= 1;
+                    }
+                    if ui.button("build").clicked {
+                        finish = true;
+                    }
+                    if ui.button("cancel").clicked {
+                        cancel = true;
+                    }
+                }
+            }
+        });
+        if let Some(kind) = start {
+            let marker = world.push((
+                Transform::default(),
+                RuntimeModelId::new(building_model_id(kind)),
+            ));
+            let mut draft = BuildingDraft {
+                kind,
+                entrance: Vector2::new(0, 0),
+                marker,
+            };
+            Self::move_to(&mut draft, world, Vector2::new(0, 0));
+            self.draft = Some(draft);
+        }
+        if let Some(draft) = self.draft.as_mut() {
+            if direction != Vector2::new(0, 0) {
+                let entrance = draft.entrance + direction;
+                Self::move_to(draft, world, entrance);
+            }
+        }
+        if cancel {
+            if let Some(draft) = self.draft.take() {
+                world.remove(draft.marker);
+            }
+        }
+        if finish {
+            if let Some(draft) = self.draft.take() {
+                world.remove(draft.marker);
+                return Some(PlayerCommand::BuildBuilding {
+                    kind: draft.kind,
+                    entrance: (draft.entrance.x, draft.entrance.y),
+                });
+            }
+        }
+        None
+    }
+    /// Moves the entrance to `entrance` if it is on the terrain
+    fn move_to(draft: &mut BuildingDraft, world: &mut World, entrance: Vector2<i64>) {
+        let transform = match <&Terrain>::query().iter(world).next() {
+            Some(terrain) if terrain.get_tile(&entrance).is_some() => {
+                building_transform(draft.kind, entrance, terrain)
+            }
+            _ => return,
+        };
+        draft.entrance = entrance;
+        if let Some(mut entry) = world.entry(draft.marker) {
+            if let Ok(marker) = entry.get_component_mut::<Transform>() {
+                *marker = transform;
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{push_terrain, GraphWeight};
+    #[test]
+    fn place_flattens_and_blocks() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5),
+            &mut world,
+        );
+        let entrance = Vector2::new(3, 2);
+        let (_, replaced) = place_building(&mut world, BuildingKind::Lodge, entrance).unwrap();
+        assert_eq!(replaced.len(), 6);
+        {
+            let terrain = <&Terrain>::query().iter(&world).next().unwrap();
+            let height = terrain.get_tile(&entrance).unwrap().height;
+            for tile in footprint(BuildingKind::Lodge, entrance).iter() {
+                let tile = terrain.get_tile(tile).unwrap();
+                assert_eq!(tile.tile_type, TileType::Building);
+                assert_eq!(tile.height, height);
+            }
+            //skiiers can not walk into the lodge
+            assert_eq!(
+                terrain.get_weight(entrance, Vector2::new(3, 3)),
+                GraphWeight::Infinity
+            );
+            assert!(terrain.get_weight(entrance, Vector2::new(3, 1)).is_finite());
+        }
+        //buildings can not overlap or hang off the terrain
+        assert_eq!(
+            place_building(&mut world, BuildingKind::Restroom, Vector2::new(2, 2)),
+            Err(PlacementError::Occupied(Vector2::new(2, 3)))
+        );
+        assert_eq!(
+            place_building(&mut world, BuildingKind::Parking, Vector2::new(7, 0)),
+            Err(PlacementError::OffTerrain)
+        );
+        //restoring the tiles reopens the footprint
+        replace_tiles(&mut world, &replaced);
+        let terrain = <&Terrain>::query().iter(&world).next().unwrap();
+        assert!(terrain.get_weight(entrance, Vector2::new(3, 3)).is_finite());
+    }
+    #[test]
+    fn capacity() {
+        let lodge = Building {
+            kind: BuildingKind::Lodge,
+            entrance: Node {
+                node: Vector2::new(0, 0),
+            },
+        };
+        let mut occupancy = HashMap::new();
+        assert!(lodge.has_room(&occupancy));
+        occupancy.insert(lodge.entrance.clone(), BuildingKind::Lodge.capacity());
+        assert!(!lodge.has_room(&occupancy));
+        let mut world = World::default();
+        let entity = push_building(&mut world, BuildingKind::Restaurant, Vector2::new(1, 1));
+        let entrance = Node {
+            node: Vector2::new(1, 1),
+        };
+        let paid = charge_visits(&mut world, &[entrance.clone(), entrance, lodge.entrance]);
+        assert_eq!(paid, 2.0 * BuildingKind::Restaurant.price());
+        let entry = world.entry_ref(entity).unwrap();
+        assert_eq!(
+            entry.get_component::<BuildingStatistics>().unwrap().visits,
+            2
+        );
+    }
+}
@@ -34,14 +34,9 @@ impl DeltaCamera {
     /// Increases by value proportional to delta radius
     pub fn update_radius(&mut self, delta_radius: f32) {
         self.new_next();
-        let mut next = self.next.as_mut().unwrap();
+        let next = self.next.as_mut().unwrap();
         next.radius += delta_radius * next.radius;
-        if next.radius > Camera::FAR_CLIP {
-            next.radius = Camera::FAR_CLIP;
-        }
-        if next.radius < 0.1 {
-            next.radius = 0.1;
-        }
+        next.radius = next.radius.clamp(0.1, Camera::FAR_CLIP);
     }
     pub fn get_radius(&self) -> f32 {
         self.previous.radius
@@ -0,0 +1,53 @@
+use legion::*;
+/// Simulated time of the current scenario
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Clock {
+    tick: u64,
+}
+impl Clock {
+    pub const TICKS_PER_DAY: u64 = 3600;
+    pub const TICKS_PER_HOUR: u64 = Self::TICKS_PER_DAY / 24;
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+    /// Current day, starting at day 1
+    pub fn day(&self) -> u32 {
+        (self.tick / Self::TICKS_PER_DAY) as u32 + 1
+    }
+    pub fn hour(&self) -> u32 {
+        ((self.tick % Self::TICKS_PER_DAY) / Self::TICKS_PER_HOUR) as u32
+    }
+    /// Advances time by one tick. Returns true if a new day started
+    pub fn advance(&mut self) -> bool {
+        self.tick += 1;
+        self.tick.is_multiple_of(Self::TICKS_PER_DAY)
+    }
+}
+/// Advances the scenario clock. Returns true if a new day started, false if it did not or if no
+/// scenario is loaded
+pub fn advance_clock(world: &mut World) -> bool {
+    if let Some(clock) = <&mut Clock>::query().iter_mut(world).next() {
+        clock.advance()
+    } else {
+        false
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn days() {
+        let mut clock = Clock::default();
+        assert_eq!(clock.day(), 1);
+        assert_eq!(clock.hour(), 0);
+        for _ in 0..Clock::TICKS_PER_HOUR {
+            assert!(!clock.advance());
+        }
+        assert_eq!(clock.hour(), 1);
+        for _ in Clock::TICKS_PER_HOUR..Clock::TICKS_PER_DAY - 1 {
+            assert!(!clock.advance());
+        }
+        assert!(clock.advance());
+        assert_eq!(clock.day(), 2);
+    }
+}
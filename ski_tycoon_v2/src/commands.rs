@@ -0,0 +1,597 @@
+use super::{
+    building::{place_building, BuildingKind},
+    congestion::set_routing,
+    graph::tiles_connected,
+    lift::push_lift,
+    prelude::{GraphLayer, Terrain},
+    rng::SeededRng,
+    snowmaking::place_snow_gun,
+    staff::{fire_staff, hire_staff, Role, Shift},
+    terrain::{replace_tiles, Difficulty, TerrainLibrary, Tile},
+    trail::push_trail,
+};
+use egui::CtxRef;
+use legion::*;
+use log::error;
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+/// Action taken by the player that changes the simulation
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum PlayerCommand {
+    /// Replaces the world with a scenario, reseeding the simulation with `seed`
+    LoadScenario {
+        name: String,
+        seed: u64,
+    },
+    BuildLift {
+        start: (i64, i64),
+        end: (i64, i64),
+    },
+    /// Marks a connected set of tiles as a named run
+    BuildTrail {
+        name: String,
+        difficulty: Difficulty,
+        tiles: Vec<(i64, i64)>,
+    },
+    /// Places a building that skiiers enter at `entrance`, flattening the tiles under it
+    BuildBuilding {
+        kind: BuildingKind,
+        entrance: (i64, i64),
+    },
+    /// Places a snow gun on or next to a run
+    PlaceSnowGun {
+        tile: (i64, i64),
+    },
+    /// Hires staff for `role` working `shift`. Lift operators work at the lift with its base at
+    /// `post` and instructors at the ski school entered at `post`
+    HireStaff {
+        role: Role,
+        shift: Shift,
+        post: Option<(i64, i64)>,
+    },
+    /// Fires a staff member with `role` working `shift` at `post`
+    FireStaff {
+        role: Role,
+        shift: Shift,
+        post: Option<(i64, i64)>,
+    },
+    /// Sets whether skiiers route around crowded tiles
+    SetCongestionRouting {
+        enabled: bool,
+    },
+    /// Reverts the last command, handled by `CommandHistory`
+    Undo,
+    /// Re-applies the last undone command, handled by `CommandHistory`
+    Redo,
+}
+/// Changes needed to revert an applied command
+#[derive(Clone, Debug, PartialEq)]
+pub enum Revert {
+    /// Removes the entities created by the command
+    RemoveEntities(Vec<Entity>),
+    /// Removes a building and puts back the tiles it was built on
+    RemoveBuilding {
+        entity: Entity,
+        tiles: Vec<(Vector2<i64>, Tile)>,
+    },
+    /// Hires a fired staff member back. The post is kept by tile so it is found again even if
+    /// the lift was rebuilt since.
+    Rehire {
+        role: Role,
+        shift: Shift,
+        post: Option<(i64, i64)>,
+    },
+    /// Fires a hired staff member. Kept by post like `Rehire` since undoing a firing hires a new
+    /// entity.
+    Fire {
+        role: Role,
+        shift: Shift,
+        post: Option<(i64, i64)>,
+    },
+}
+impl Revert {
+    fn revert(&self, world: &mut World) {
+        match self {
+            Self::RemoveEntities(entities) => {
+                for entity in entities.iter() {
+                    world.remove(*entity);
+                }
+            }
+            Self::RemoveBuilding { entity, tiles } => {
+                world.remove(*entity);
+                replace_tiles(world, tiles);
+            }
+            Self::Rehire { role, shift, post } => {
+                if let Err(e) = hire_staff(world, *role, *shift, *post) {
+                    error!("can not hire {} back: {}", role, e);
+                }
+            }
+            Self::Fire { role, shift, post } => {
+                if let Err(e) = fire_staff(world, *role, *shift, *post) {
+                    error!("can not fire {} again: {}", role, e);
+                }
+            }
+        }
+    }
+}
+impl PlayerCommand {
+    /// Applies the command to the world. Returns how to undo the command, or `None` if it can
+    /// not be undone.
+    fn apply(
+        &self,
+        world: &mut World,
+        library: &TerrainLibrary,
+        rng: &mut SeededRng,
+    ) -> Option<Revert> {
+        match self {
+            Self::LoadScenario { name, seed } => {
+                if let Some(scenario) = library.get(name) {
+                    *rng = SeededRng::new(*seed);
+                    scenario.build_scenario(world, rng);
+                } else {
+                    error!("scenario {} does not exist", name);
+                }
+                None
+            }
+            Self::BuildLift { start, end } => {
+                let (start, end) = (Vector2::new(start.0, start.1), Vector2::new(end.0, end.1));
+                let on_terrain = match <&Terrain>::query().iter(world).next() {
+                    Some(terrain) => {
+                        terrain.get_tile(&start).is_some() && terrain.get_tile(&end).is_some()
+                    }
+                    None => {
+                        error!("can not build lift without terrain");
+                        return None;
+                    }
+                };
+                if on_terrain {
+                    let entities = push_lift(world, start, end);
+                    Some(Revert::RemoveEntities(entities.to_vec()))
+                } else {
+                    error!("lift from {:?} to {:?} is off the terrain", start, end);
+                    None
+                }
+            }
+            Self::BuildTrail {
+                name,
+                difficulty,
+                tiles,
+            } => {
+                let tiles: Vec<Vector2<i64>> =
+                    tiles.iter().map(|(x, y)| Vector2::new(*x, *y)).collect();
+                let valid_tiles = match <&Terrain>::query().iter(world).next() {
+                    Some(terrain) => {
+                        let dimensions = terrain.dimensions();
+                        tiles.iter().all(|t| {
+                            t.x >= 0
+                                && t.y >= 0
+                                && t.x < dimensions.x as i64
+                                && t.y < dimensions.y as i64
+                        })
+                    }
+                    None => {
+                        error!("can not build trail without terrain");
+                        return None;
+                    }
+                };
+                let name_taken = <&GraphLayer>::query().iter(world).any(|layer| match layer {
+                    GraphLayer::Trail(t) => &t.name == name,
+                    _ => false,
+                });
+                if !valid_tiles || !tiles_connected(&tiles) {
+                    error!("trail {} is not a connected set of tiles", name);
+                    None
+                } else if name_taken {
+                    error!("trail {} already exists", name);
+                    None
+                } else {
+                    let entity = push_trail(world, name.clone(), *difficulty, &tiles);
+                    Some(Revert::RemoveEntities(vec![entity]))
+                }
+            }
+            Self::BuildBuilding { kind, entrance } => {
+                match place_building(world, *kind, Vector2::new(entrance.0, entrance.1)) {
+                    Ok((entity, tiles)) => Some(Revert::RemoveBuilding { entity, tiles }),
+                    Err(e) => {
+                        error!("can not build {} at {:?}: {}", kind, entrance, e);
+                        None
+                    }
+                }
+            }
+            Self::PlaceSnowGun { tile } => {
+                match place_snow_gun(world, Vector2::new(tile.0, tile.1)) {
+                    Ok(entity) => Some(Revert::RemoveEntities(vec![entity])),
+                    Err(e) => {
+                        error!("can not place snow gun at {:?}: {}", tile, e);
+                        None
+                    }
+                }
+            }
+            Self::HireStaff { role, shift, post } => {
+                match hire_staff(world, *role, *shift, *post) {
+                    Ok(_) => Some(Revert::Fire {
+                        role: *role,
+                        shift: *shift,
+                        post: *post,
+                    }),
+                    Err(e) => {
+                        error!("can not hire {}: {}", role, e);
+                        None
+                    }
+                }
+            }
+            Self::FireStaff { role, shift, post } => {
+                match fire_staff(world, *role, *shift, *post) {
+                    Ok(_) => Some(Revert::Rehire {
+                        role: *role,
+                        shift: *shift,
+                        post: *post,
+                    }),
+                    Err(e) => {
+                        error!("can not fire {}: {}", role, e);
+                        None
+                    }
+                }
+            }
+            Self::SetCongestionRouting { enabled } => {
+                if !set_routing(world, *enabled) {
+                    error!("can not change routing without terrain");
+                }
+                None
+            }
+            Self::Undo | Self::Redo => None,
+        }
+    }
+}
+/// Command that has been applied along with how to revert it
+struct AppliedCommand {
+    command: PlayerCommand,
+    revert: Revert,
+}
+/// Undo and redo stacks of player commands. Loading a scenario clears the history.
+#[derive(Default)]
+pub struct CommandHistory {
+    undo: Vec<AppliedCommand>,
+    redo: Vec<PlayerCommand>,
+}
+impl CommandHistory {
+    /// Maximum number of commands that can be undone
+    pub const MAX_UNDO: usize = 100;
+    /// Applies a command, recording it so it can be undone
+    pub fn execute(
+        &mut self,
+        command: &PlayerCommand,
+        world: &mut World,
+        library: &TerrainLibrary,
+        rng: &mut SeededRng,
+    ) {
+        match command {
+            PlayerCommand::Undo => {
+                if let Some(applied) = self.undo.pop() {
+                    applied.revert.revert(world);
+                    self.redo.push(applied.command);
+                }
+            }
+            PlayerCommand::Redo => {
+                if let Some(command) = self.redo.pop() {
+                    if let Some(revert) = command.apply(world, library, rng) {
+                        self.undo.push(AppliedCommand { command, revert });
+                    }
+                }
+            }
+            _ => match command.apply(world, library, rng) {
+                //commands that fail leave the history alone
+                Some(revert) => {
+                    self.redo.clear();
+                    self.undo.push(AppliedCommand {
+                        command: command.clone(),
+                        revert,
+                    });
+                    if self.undo.len() > Self::MAX_UNDO {
+                        self.undo.remove(0);
+                    }
+                }
+                None => {
+                    if matches!(command, PlayerCommand::LoadScenario { .. }) {
+                        self.undo.clear();
+                        self.redo.clear();
+                    }
+                }
+            },
+        }
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    pub fn draw_gui(&self, context: &mut CtxRef) -> Option<PlayerCommand> {
+        let mut command = None;
+        egui::Window::new("history").show(context, |ui| {
+            if self.can_undo() && ui.button("Undo (Ctrl+Z)").clicked {
+                command = Some(PlayerCommand::Undo);
+            }
+            if self.can_redo() && ui.button("Redo (Ctrl+Y)").clicked {
+                command = Some(PlayerCommand::Redo);
+            }
+        });
+        command
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::super::{
+        prelude::{Building, GraphLayer},
+        staff::Staff,
+        terrain::parse_scenario,
+    };
+    use super::*;
+    const SCENARIO: &str = r#"Scenario(
+        name: "Test",
+        terrain: Cone(dimensions: (8, 8), center: (4.0, 4.0), center_height: 4.0, slope: -1.0),
+        starting_cash: 1000.0,
+    )"#;
+    fn lift_count(world: &World) -> usize {
+        <&GraphLayer>::query()
+            .iter(world)
+            .filter(|l| matches!(l, GraphLayer::Lift(_)))
+            .count()
+    }
+    fn setup() -> (World, TerrainLibrary, SeededRng, CommandHistory) {
+        let library = TerrainLibrary::from_scenarios(vec![parse_scenario(
+            "test.ron",
+            SCENARIO,
+            &|_: &str| None,
+        )
+        .unwrap()]);
+        let mut world = World::default();
+        let mut rng = SeededRng::new(0);
+        let mut history = CommandHistory::default();
+        history.execute(
+            &PlayerCommand::LoadScenario {
+                name: "Test".to_string(),
+                seed: 0,
+            },
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        (world, library, rng, history)
+    }
+    #[test]
+    fn undo_redo_lift() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let entities = world.len();
+        let build = PlayerCommand::BuildLift {
+            start: (0, 0),
+            end: (4, 4),
+        };
+        history.execute(&build, &mut world, &library, &mut rng);
+        assert_eq!(lift_count(&world), 1);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        assert_eq!(lift_count(&world), 0);
+        assert_eq!(world.len(), entities);
+        assert!(history.can_redo());
+        history.execute(&PlayerCommand::Redo, &mut world, &library, &mut rng);
+        assert_eq!(lift_count(&world), 1);
+        assert!(!history.can_redo());
+        //undoing with an empty stack does nothing
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        assert_eq!(lift_count(&world), 0);
+    }
+    #[test]
+    fn lift_off_terrain() {
+        let (mut world, library, mut rng, mut history) = setup();
+        for (start, end) in [((0, 0), (8, 4)), ((-1, 0), (4, 4))].iter() {
+            history.execute(
+                &PlayerCommand::BuildLift {
+                    start: *start,
+                    end: *end,
+                },
+                &mut world,
+                &library,
+                &mut rng,
+            );
+        }
+        assert_eq!(lift_count(&world), 0);
+        assert!(!history.can_undo());
+    }
+    #[test]
+    fn failed_command_keeps_redo() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let build = PlayerCommand::BuildLift {
+            start: (0, 0),
+            end: (4, 4),
+        };
+        history.execute(&build, &mut world, &library, &mut rng);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        //there is nobody to fire
+        history.execute(
+            &PlayerCommand::FireStaff {
+                role: Role::Patroller,
+                shift: Shift::Day,
+                post: None,
+            },
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        assert!(history.can_redo());
+        history.execute(&PlayerCommand::Redo, &mut world, &library, &mut rng);
+        assert_eq!(lift_count(&world), 1);
+    }
+    #[test]
+    fn rehire_at_rebuilt_lift() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let post = Some((0, 0));
+        let commands = [
+            PlayerCommand::BuildLift {
+                start: (0, 0),
+                end: (4, 4),
+            },
+            PlayerCommand::HireStaff {
+                role: Role::LiftOperator,
+                shift: Shift::Day,
+                post,
+            },
+            PlayerCommand::FireStaff {
+                role: Role::LiftOperator,
+                shift: Shift::Day,
+                post,
+            },
+        ];
+        for command in commands.iter() {
+            history.execute(command, &mut world, &library, &mut rng);
+        }
+        //take everything back and build it again, the lift is a new entity
+        for _ in 0..3 {
+            history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        }
+        for _ in 0..3 {
+            history.execute(&PlayerCommand::Redo, &mut world, &library, &mut rng);
+        }
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        let lift = <(Entity, &GraphLayer)>::query()
+            .iter(&world)
+            .find(|(_, layer)| matches!(layer, GraphLayer::Lift(_)))
+            .map(|(entity, _)| *entity);
+        let posts: Vec<Option<Entity>> = <&Staff>::query().iter(&world).map(|s| s.post).collect();
+        assert_eq!(posts, vec![lift]);
+    }
+    #[test]
+    fn new_command_clears_redo() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let build = PlayerCommand::BuildLift {
+            start: (0, 0),
+            end: (4, 4),
+        };
+        history.execute(&build, &mut world, &library, &mut rng);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        history.execute(&build, &mut world, &library, &mut rng);
+        assert!(!history.can_redo());
+        history.execute(
+            &PlayerCommand::LoadScenario {
+                name: "Test".to_string(),
+                seed: 0,
+            },
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        assert!(!history.can_undo());
+    }
+    #[test]
+    fn undo_building() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let tiles = |world: &World| {
+            let terrain = <&Terrain>::query().iter(world).next().unwrap();
+            (0..8)
+                .flat_map(|x| (0..8).map(move |y| Vector2::new(x, y)))
+                .map(|t| terrain.get_tile(&t).unwrap().clone())
+                .collect::<Vec<_>>()
+        };
+        let before = tiles(&world);
+        let build = PlayerCommand::BuildBuilding {
+            kind: BuildingKind::Lodge,
+            entrance: (4, 1),
+        };
+        history.execute(&build, &mut world, &library, &mut rng);
+        assert_ne!(tiles(&world), before);
+        //the lodge is in the way
+        history.execute(
+            &PlayerCommand::BuildBuilding {
+                kind: BuildingKind::Restroom,
+                entrance: (4, 2),
+            },
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        assert_eq!(<&Building>::query().iter(&world).count(), 1);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        assert_eq!(tiles(&world), before);
+        assert_eq!(<&Building>::query().iter(&world).count(), 0);
+    }
+    #[test]
+    fn undo_fire_staff() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let staff_count = |world: &World| <&Staff>::query().iter(world).count();
+        let hire = PlayerCommand::HireStaff {
+            role: Role::Patroller,
+            shift: Shift::Night,
+            post: None,
+        };
+        let fire = PlayerCommand::FireStaff {
+            role: Role::Patroller,
+            shift: Shift::Night,
+            post: None,
+        };
+        history.execute(&hire, &mut world, &library, &mut rng);
+        assert_eq!(staff_count(&world), 1);
+        history.execute(&fire, &mut world, &library, &mut rng);
+        assert_eq!(staff_count(&world), 0);
+        //nobody is left to fire
+        history.execute(&fire, &mut world, &library, &mut rng);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        assert_eq!(staff_count(&world), 1);
+        //operators need a lift
+        history.execute(
+            &PlayerCommand::HireStaff {
+                role: Role::LiftOperator,
+                shift: Shift::Day,
+                post: Some((0, 0)),
+            },
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        assert_eq!(staff_count(&world), 1);
+    }
+    #[test]
+    fn build_trail() {
+        let (mut world, library, mut rng, mut history) = setup();
+        let trail = |name: &str, tiles: Vec<(i64, i64)>| PlayerCommand::BuildTrail {
+            name: name.to_string(),
+            difficulty: Difficulty::Blue,
+            tiles,
+        };
+        let trail_count = |world: &World| {
+            <&GraphLayer>::query()
+                .iter(world)
+                .filter(|l| matches!(l, GraphLayer::Trail(_)))
+                .count()
+        };
+        history.execute(
+            &trail("Run 1", vec![(0, 0), (0, 1), (1, 1)]),
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        assert_eq!(trail_count(&world), 1);
+        //names must be unique
+        history.execute(
+            &trail("Run 1", vec![(2, 2), (2, 3)]),
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        //tiles must be connected and on the terrain
+        history.execute(
+            &trail("Run 2", vec![(0, 0), (2, 2)]),
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        history.execute(
+            &trail("Run 3", vec![(7, 7), (8, 7)]),
+            &mut world,
+            &library,
+            &mut rng,
+        );
+        assert_eq!(trail_count(&world), 1);
+        history.execute(&PlayerCommand::Undo, &mut world, &library, &mut rng);
+        assert_eq!(trail_count(&world), 0);
+    }
+}
@@ -0,0 +1,202 @@
+use super::commands::PlayerCommand;
+use super::prelude::{GraphLayer, Grid, Node, Terrain, Transform};
+use super::skiier::Skill;
+use egui::CtxRef;
+use legion::*;
+use nalgebra::Vector2;
+/// Number of skiiers on each tile, updated every tick, along with how long each tile has been
+/// crowded.
+pub struct Congestion {
+    occupancy: Grid<u16>,
+    /// Ticks each tile spent crowded
+    crowded_ticks: Grid<u32>,
+    /// Whether skiiers route around crowded tiles
+    pub routing: bool,
+}
+impl Congestion {
+    /// Number of skiiers on a tile before it counts as crowded
+    pub const CROWDED: u16 = 3;
+    /// Weight added to entering a tile for each skiier already on it
+    pub const WEIGHT_PER_SKIIER: i32 = 5;
+    /// Number of hotspots listed in the gui
+    const SHOWN_HOTSPOTS: usize = 5;
+    pub fn new(dimensions: Vector2<usize>) -> Self {
+        Self {
+            occupancy: Grid::from_vec(vec![0; dimensions.x * dimensions.y], dimensions),
+            crowded_ticks: Grid::from_vec(vec![0; dimensions.x * dimensions.y], dimensions),
+            routing: false,
+        }
+    }
+    fn in_bounds(&self, tile: Vector2<i64>) -> bool {
+        tile.x >= 0
+            && tile.y >= 0
+            && tile.x < self.occupancy.width() as i64
+            && tile.y < self.occupancy.height() as i64
+    }
+    /// Number of skiiers on a tile
+    pub fn occupancy(&self, tile: Vector2<i64>) -> u16 {
+        if self.in_bounds(tile) {
+            self.occupancy[Vector2::new(tile.x as usize, tile.y as usize)]
+        } else {
+            0
+        }
+    }
+    /// Extra weight of entering a tile because of the skiiers on it
+    pub fn weight(&self, tile: Vector2<i64>) -> i32 {
+        self.occupancy(tile) as i32 * Self::WEIGHT_PER_SKIIER
+    }
+    /// Tiles that have been crowded the longest along with the number of ticks they were
+    /// crowded, most crowded first
+    pub fn hotspots(&self, count: usize) -> Vec<(Vector2<i64>, u32)> {
+        let mut hotspots = vec![];
+        for x in 0..self.crowded_ticks.width() {
+            for y in 0..self.crowded_ticks.height() {
+                let ticks = self.crowded_ticks[Vector2::new(x, y)];
+                if ticks > 0 {
+                    hotspots.push((Vector2::new(x as i64, y as i64), ticks));
+                }
+            }
+        }
+        hotspots.sort_by_key(|(_, ticks)| std::cmp::Reverse(*ticks));
+        hotspots.truncate(count);
+        hotspots
+    }
+    /// Recounts the skiiers on each tile from their positions
+    fn count(&mut self, positions: &[Vector2<i64>]) {
+        for x in 0..self.occupancy.width() {
+            for y in 0..self.occupancy.height() {
+                self.occupancy[Vector2::new(x, y)] = 0;
+            }
+        }
+        for tile in positions.iter() {
+            if self.in_bounds(*tile) {
+                let index = Vector2::new(tile.x as usize, tile.y as usize);
+                self.occupancy[index] = self.occupancy[index].saturating_add(1);
+            }
+        }
+        for x in 0..self.occupancy.width() {
+            for y in 0..self.occupancy.height() {
+                let index = Vector2::new(x, y);
+                if self.occupancy[index] >= Self::CROWDED {
+                    self.crowded_ticks[index] += 1;
+                }
+            }
+        }
+    }
+}
+/// Updates the number of skiiers on each tile, creating the congestion grid for a new scenario
+pub fn update_congestion(world: &mut World) {
+    let positions: Vec<Vector2<i64>> = <(&Transform, &Skill)>::query()
+        .iter(world)
+        .map(|(transform, _)| {
+            let t = transform.get_translation();
+            Vector2::new(t.x.round() as i64, t.z.round() as i64)
+        })
+        .collect();
+    if let Some(congestion) = <&mut Congestion>::query().iter_mut(world).next() {
+        congestion.count(&positions);
+        return;
+    }
+    let dimensions = match <&Terrain>::query().iter(world).next() {
+        Some(terrain) => terrain.dimensions(),
+        None => return,
+    };
+    let mut congestion = Congestion::new(dimensions);
+    congestion.count(&positions);
+    world.push((congestion,));
+}
+/// Sets whether skiiers route around crowds. Returns false if there is no scenario loaded.
+pub fn set_routing(world: &mut World, enabled: bool) -> bool {
+    update_congestion(world);
+    match <&mut Congestion>::query().iter_mut(world).next() {
+        Some(congestion) => {
+            congestion.routing = enabled;
+            true
+        }
+        None => false,
+    }
+}
+/// Draws the congestion window listing hotspots. Returns a command if the player toggles
+/// routing around crowds
+pub fn draw_gui(world: &World, context: &mut CtxRef) -> Option<PlayerCommand> {
+    let congestion = <&Congestion>::query().iter(world).next()?;
+    let trails: Vec<_> = <&GraphLayer>::query()
+        .iter(world)
+        .filter_map(|layer| match layer {
+            GraphLayer::Trail(t) => Some(t),
+            _ => None,
+        })
+        .collect();
+    let hotspots: Vec<String> = congestion
+        .hotspots(Congestion::SHOWN_HOTSPOTS)
+        .iter()
+        .map(|(tile, ticks)| {
+            let node = Node { node: *tile };
+            match trails.iter().find(|t| t.contains(&node)) {
+                Some(trail) => format!(
+                    "({}, {}) on {}: crowded for {} ticks, {} skiiers now",
+                    tile.x,
+                    tile.y,
+                    trail.name,
+                    ticks,
+                    congestion.occupancy(*tile)
+                ),
+                None => format!(
+                    "({}, {}): crowded for {} ticks, {} skiiers now",
+                    tile.x,
+                    tile.y,
+                    ticks,
+                    congestion.occupancy(*tile)
+                ),
+            }
+        })
+        .collect();
+    let mut command = None;
+    egui::Window::new("congestion").show(context, |ui| {
+        if hotspots.is_empty() {
+            ui.label("No crowded tiles yet");
+        }
+        for hotspot in hotspots.iter() {
+            ui.label(hotspot);
+        }
+        let toggle = if congestion.routing {
+            "stop routing around crowds"
+        } else {
+            "route around crowds"
+        };
+        if ui.button(toggle).clicked {
+            command = Some(PlayerCommand::SetCongestionRouting {
+                enabled: !congestion.routing,
+            });
+        }
+    });
+    command
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn hotspots() {
+        let mut congestion = Congestion::new(Vector2::new(4, 4));
+        let crowd = vec![Vector2::new(1, 1); 3];
+        congestion.count(&crowd);
+        let mut bigger = crowd.clone();
+        bigger.extend(vec![Vector2::new(2, 3); 4]);
+        //skiiers off the map are ignored
+        bigger.push(Vector2::new(9, 9));
+        congestion.count(&bigger);
+        assert_eq!(congestion.occupancy(Vector2::new(2, 3)), 4);
+        assert_eq!(congestion.occupancy(Vector2::new(0, 0)), 0);
+        assert_eq!(
+            congestion.weight(Vector2::new(1, 1)),
+            3 * Congestion::WEIGHT_PER_SKIIER
+        );
+        assert_eq!(
+            congestion.hotspots(5),
+            vec![(Vector2::new(1, 1), 2), (Vector2::new(2, 3), 1)]
+        );
+        congestion.count(&[]);
+        assert_eq!(congestion.occupancy(Vector2::new(1, 1)), 0);
+        assert_eq!(congestion.hotspots(1), vec![(Vector2::new(1, 1), 2)]);
+    }
+}
@@ -1,19 +1,20 @@
 use js_sys::Map as JsMap;
 use log::info;
 use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MouseButton {
     LeftClick,
     MiddleClick,
     RightClick,
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MouseClick {
     position: Vector2<f32>,
     button_pressed: MouseButton,
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Event {
     MouseMove {
         delta_x: f32,
@@ -43,6 +44,10 @@ pub enum Event {
         y: f32,
     },
     MouseClick(MouseClick),
+    /// Ctrl+Z
+    Undo,
+    /// Ctrl+Y or Ctrl+Shift+Z
+    Redo,
 }
 impl Event {
     pub fn from_map(map: JsMap) -> Option<Self> {
@@ -94,6 +99,17 @@ impl Event {
     }
     fn from_keypress(map: JsMap) -> Option<Self> {
         let key: String = map.get(&JsValue::from_str("key")).as_string().unwrap();
+        let ctrl = map
+            .get(&JsValue::from_str("ctrl"))
+            .as_bool()
+            .unwrap_or(false);
+        if ctrl {
+            return match key.as_str() {
+                "z" => Some(Self::Undo),
+                "y" | "Z" => Some(Self::Redo),
+                _ => None,
+            };
+        }
         match key.as_str() {
             "w" => Some(Self::CameraMove {
                 direction: Vector3::new(0.0, 0.0, -1.0),
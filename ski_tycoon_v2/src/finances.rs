@@ -0,0 +1,39 @@
+use super::prelude::{Building, GraphLayer, GraphLayerList};
+use super::staff::daily_wages;
+use legion::*;
+/// Money owned by the resort
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finances {
+    pub cash: f32,
+}
+impl Finances {
+    /// Price of a day ticket paid by every arriving guest
+    pub const TICKET_PRICE: f32 = 50.0;
+    /// Daily cost of running a lift
+    pub const LIFT_UPKEEP: f32 = 500.0;
+    pub fn new(cash: f32) -> Self {
+        Self { cash }
+    }
+}
+/// Adds `amount` to the resort's cash. Does nothing if no scenario is loaded
+pub fn add_cash(world: &mut World, amount: f32) {
+    if let Some(finances) = <&mut Finances>::query().iter_mut(world).next() {
+        finances.cash += amount;
+    }
+}
+/// Pays the daily running cost of every lift and building along with staff wages
+pub fn pay_upkeep(world: &mut World) {
+    let lift_count = {
+        let layers: Vec<&GraphLayer> = <&GraphLayer>::query().iter(world).collect();
+        GraphLayerList::new(layers).find_lifts().len()
+    };
+    let buildings: f32 = <&Building>::query()
+        .iter(world)
+        .map(|b| b.kind.upkeep())
+        .sum();
+    let wages = daily_wages(world);
+    add_cash(
+        world,
+        -(lift_count as f32) * Finances::LIFT_UPKEEP - buildings - wages,
+    );
+}
@@ -1,9 +1,41 @@
+use super::building::Building;
+use super::congestion::Congestion;
 use super::prelude::{Grid, Terrain};
+use super::terrain::Difficulty;
 use log::info;
 use nalgebra::{Vector2, Vector3};
 use priority_queue::PriorityQueue;
 use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
+mod flow_field;
+mod hpa;
+mod navigation;
+mod spline;
+pub use flow_field::{update_flow_fields, FlowField, FlowFields};
+pub use hpa::{update_path_hierarchies, PathHierarchies, PathHierarchy};
+pub use navigation::{
+    update_navigation_graph, DirtyRegion, GraphChange, NavigationGraph, PathCache,
+};
+/// Reason a path could not be found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathError {
+    /// The destination can not be reached from the source
+    Unreachable,
+    /// The source or destination is not part of the graph
+    OutOfBounds,
+    /// The search expanded more nodes than its budget allows
+    BudgetExceeded,
+}
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unreachable => write!(f, "destination is not reachable"),
+            Self::OutOfBounds => write!(f, "node is not on the graph"),
+            Self::BudgetExceeded => write!(f, "search budget exceeded"),
+        }
+    }
+}
+impl std::error::Error for PathError {}
 #[derive(Clone, Hash, Debug, PartialEq, Eq)]
 pub enum GraphWeight {
     Some(i32),
@@ -67,6 +99,58 @@ pub struct GridNode {
     pub x_minus: GraphWeight,
     pub z_plus: GraphWeight,
     pub z_minus: GraphWeight,
+    pub x_plus_z_plus: GraphWeight,
+    pub x_plus_z_minus: GraphWeight,
+    pub x_minus_z_plus: GraphWeight,
+    pub x_minus_z_minus: GraphWeight,
+}
+impl GridNode {
+    /// Offsets to the neighbors of a node, in the same order as `weights`
+    pub const DIRECTIONS: [(i64, i64); 8] = [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ];
+    /// Builds node from the weight of the edge in each of `DIRECTIONS`
+    pub fn from_weights<F: Fn(Vector2<i64>) -> GraphWeight>(weight: F) -> Self {
+        let w = |(x, z): (i64, i64)| weight(Vector2::new(x, z));
+        Self {
+            x_plus: w(Self::DIRECTIONS[0]),
+            x_minus: w(Self::DIRECTIONS[1]),
+            z_plus: w(Self::DIRECTIONS[2]),
+            z_minus: w(Self::DIRECTIONS[3]),
+            x_plus_z_plus: w(Self::DIRECTIONS[4]),
+            x_plus_z_minus: w(Self::DIRECTIONS[5]),
+            x_minus_z_plus: w(Self::DIRECTIONS[6]),
+            x_minus_z_minus: w(Self::DIRECTIONS[7]),
+        }
+    }
+    pub fn weights(&self) -> [&GraphWeight; 8] {
+        [
+            &self.x_plus,
+            &self.x_minus,
+            &self.z_plus,
+            &self.z_minus,
+            &self.x_plus_z_plus,
+            &self.x_plus_z_minus,
+            &self.x_minus_z_plus,
+            &self.x_minus_z_minus,
+        ]
+    }
+    /// Gets weight of the edge to the neighbor at `delta`, infinite if it is not a neighbor
+    pub fn get(&self, delta: Vector2<i64>) -> GraphWeight {
+        Self::DIRECTIONS
+            .iter()
+            .zip(self.weights().iter())
+            .find(|((x, z), _)| delta == Vector2::new(*x, *z))
+            .map(|(_, weight)| (*weight).clone())
+            .unwrap_or(GraphWeight::Infinity)
+    }
 }
 #[derive(Clone, Debug)]
 pub struct LiftLayer {
@@ -91,11 +175,98 @@ impl LiftLayer {
         }
     }
 }
+/// Named run groomed over a connected set of tiles. Moving between two tiles of the run is
+/// cheaper than moving over the bare grid.
+#[derive(Clone, Debug)]
+pub struct TrailLayer {
+    pub name: String,
+    pub difficulty: Difficulty,
+    edges: HashMap<Node, Vec<(Node, GraphWeight)>>,
+}
+impl TrailLayer {
+    /// Weights of grid edges are divided by this on a trail
+    pub const WEIGHT_DIVISOR: i32 = 2;
+    pub fn new(
+        name: String,
+        difficulty: Difficulty,
+        tiles: &[Vector2<i64>],
+        terrain: &Terrain,
+    ) -> Self {
+        let tile_set: HashSet<Vector2<i64>> = tiles.iter().copied().collect();
+        let mut edges = HashMap::new();
+        for tile in tile_set.iter() {
+            let children = GridNode::DIRECTIONS
+                .iter()
+                .map(|(x, z)| tile + Vector2::new(*x, *z))
+                .filter(|child| tile_set.contains(child))
+                .filter_map(|child| match terrain.get_weight(*tile, child) {
+                    GraphWeight::Some(w) => Some((
+                        Node { node: child },
+                        GraphWeight::Some(w / Self::WEIGHT_DIVISOR),
+                    )),
+                    GraphWeight::Infinity => None,
+                })
+                .collect();
+            edges.insert(Node { node: *tile }, children);
+        }
+        Self {
+            name,
+            difficulty,
+            edges,
+        }
+    }
+    pub fn contains(&self, node: &Node) -> bool {
+        self.edges.contains_key(node)
+    }
+    pub fn tiles(&self) -> impl Iterator<Item = &Node> {
+        self.edges.keys()
+    }
+    pub fn get_children(&self, source: &Node) -> Vec<(Node, GraphWeight)> {
+        self.edges.get(source).cloned().unwrap_or_default()
+    }
+    pub fn get(&self, source: Node, destination: Node) -> GraphWeight {
+        self.edges
+            .get(&source)
+            .and_then(|children| children.iter().find(|(child, _)| child == &destination))
+            .map(|(_, weight)| weight.clone())
+            .unwrap_or(GraphWeight::Infinity)
+    }
+}
+/// Checks that every tile can be reached from every other tile by moving in the four cardinal
+/// directions
+pub fn tiles_connected(tiles: &[Vector2<i64>]) -> bool {
+    let tile_set: HashSet<Vector2<i64>> = tiles.iter().copied().collect();
+    let first = match tiles.first() {
+        Some(first) => *first,
+        None => return false,
+    };
+    let mut visited = HashSet::new();
+    let mut stack = vec![first];
+    while let Some(tile) = stack.pop() {
+        if visited.insert(tile) {
+            for direction in [
+                Vector2::new(1, 0),
+                Vector2::new(-1, 0),
+                Vector2::new(0, 1),
+                Vector2::new(0, -1),
+            ]
+            .iter()
+            {
+                let child = tile + direction;
+                if tile_set.contains(&child) && !visited.contains(&child) {
+                    stack.push(child);
+                }
+            }
+        }
+    }
+    visited.len() == tile_set.len()
+}
 //layer of graph system
 #[derive(Clone, Debug)]
 pub enum GraphLayer {
     Grid { grid: Grid<GridNode> },
     Lift(LiftLayer),
+    Trail(TrailLayer),
 }
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct Node {
@@ -149,45 +320,42 @@ impl std::ops::Add for NodeFloat {
     }
 }
 impl GraphLayer {
+    /// Whether the node is part of the layer
+    pub fn contains(&self, node: &Node) -> bool {
+        match self {
+            Self::Grid { grid } => {
+                node.node.x >= 0
+                    && node.node.y >= 0
+                    && node.node.x < grid.width() as i64
+                    && node.node.y < grid.height() as i64
+            }
+            Self::Lift(l) => node == &l.start || node == &l.end,
+            Self::Trail(t) => t.contains(node),
+        }
+    }
     pub fn get_children(&self, source: &Node) -> Vec<(Node, GraphWeight)> {
         match self {
             Self::Grid { grid } => {
                 if let Some(node) = grid.get(source.node) {
-                    vec![
-                        (
-                            Node {
-                                node: source.node + Vector2::new(1, 0),
-                            },
-                            node.x_plus.clone(),
-                        ),
-                        (
-                            Node {
-                                node: source.node + Vector2::new(-1, 0),
-                            },
-                            node.x_minus.clone(),
-                        ),
-                        (
-                            Node {
-                                node: source.node + Vector2::new(0, 1),
-                            },
-                            node.z_plus.clone(),
-                        ),
-                        (
-                            Node {
-                                node: source.node + Vector2::new(0, -1),
-                            },
-                            node.z_minus.clone(),
-                        ),
-                    ]
-                    .iter()
-                    .filter(|(_, weight)| weight.is_finite())
-                    .cloned()
-                    .collect()
+                    GridNode::DIRECTIONS
+                        .iter()
+                        .zip(node.weights().iter())
+                        .filter(|(_, weight)| weight.is_finite())
+                        .map(|((x, z), weight)| {
+                            (
+                                Node {
+                                    node: source.node + Vector2::new(*x, *z),
+                                },
+                                (*weight).clone(),
+                            )
+                        })
+                        .collect()
                 } else {
                     vec![]
                 }
             }
             Self::Lift(l) => l.get_children(source),
+            Self::Trail(t) => t.get_children(source),
         }
     }
     pub fn get(&self, source: Node, destination: Node) -> GraphWeight {
@@ -195,72 +363,292 @@ impl GraphLayer {
         match self {
             Self::Grid { grid } => {
                 if let Some(node) = grid.get(source.node) {
-                    let x_plus = Vector2::new(1, 0);
-                    let x_minus = Vector2::new(-1, 0);
-                    let z_plus = Vector2::new(0, 1);
-                    let z_minus = Vector2::new(0, -1);
-                    let delta = destination - source;
-                    if delta.node == x_plus {
-                        node.x_plus.clone()
-                    } else if delta.node == x_minus {
-                        node.x_minus.clone()
-                    } else if delta.node == z_plus {
-                        node.z_plus.clone()
-                    } else if delta.node == z_minus {
-                        node.z_minus.clone()
-                    } else {
-                        GraphWeight::Infinity
-                    }
+                    node.get((destination - source).node)
                 } else {
                     GraphWeight::Infinity
                 }
             }
             Self::Lift(l) => l.get(source, destination),
+            Self::Trail(t) => t.get(source, destination),
         }
     }
 }
 pub struct GraphLayerList<'a> {
     layers: Vec<&'a GraphLayer>,
+    hierarchy: Option<&'a PathHierarchy>,
+    flow_fields: Vec<&'a FlowField>,
+    path_cache: Option<(&'a PathCache, Difficulty)>,
+    search_budget: usize,
+    congestion: Option<&'a Congestion>,
+    buildings: Vec<&'a Building>,
 }
 impl<'a> GraphLayerList<'a> {
+    /// Default number of nodes a search may expand before giving up
+    pub const DEFAULT_SEARCH_BUDGET: usize = 20_000;
     pub fn new(layers: Vec<&'a GraphLayer>) -> Self {
-        Self { layers }
+        Self {
+            layers,
+            hierarchy: None,
+            flow_fields: vec![],
+            path_cache: None,
+            search_budget: Self::DEFAULT_SEARCH_BUDGET,
+            congestion: None,
+            buildings: vec![],
+        }
+    }
+    /// Uses `hierarchy` to find paths over the terrain. It must be built from the same layers.
+    pub fn with_hierarchy(self, hierarchy: Option<&'a PathHierarchy>) -> Self {
+        Self { hierarchy, ..self }
+    }
+    pub fn hierarchy(&self) -> Option<&'a PathHierarchy> {
+        self.hierarchy
+    }
+    /// Uses `flow_fields` to find paths to their targets. They must be built from the same
+    /// layers.
+    pub fn with_flow_fields(self, flow_fields: Vec<&'a FlowField>) -> Self {
+        Self {
+            flow_fields,
+            ..self
+        }
+    }
+    /// Sets the number of nodes searches over the list may expand before giving up
+    pub fn with_search_budget(self, search_budget: usize) -> Self {
+        Self {
+            search_budget,
+            ..self
+        }
+    }
+    pub fn search_budget(&self) -> usize {
+        self.search_budget
+    }
+    /// Adds the weight of the skiiers on a tile to every edge entering it so searches route
+    /// around crowds. Flow fields, path hierarchies and cached paths do not know about crowds
+    /// so they should not be used along with congestion.
+    pub fn with_congestion(self, congestion: Option<&'a Congestion>) -> Self {
+        Self { congestion, ..self }
+    }
+    pub fn congestion(&self) -> Option<&'a Congestion> {
+        self.congestion
+    }
+    /// Weight of entering a tile because of crowds
+    fn crowd_weight(&self, node: &Node) -> GraphWeight {
+        match self.congestion {
+            Some(congestion) => GraphWeight::Some(congestion.weight(node.node)),
+            None => GraphWeight::Some(0),
+        }
+    }
+    /// Caches paths found for skiiers of `skill` in `cache`
+    pub fn with_path_cache(self, cache: Option<&'a PathCache>, skill: Difficulty) -> Self {
+        Self {
+            path_cache: cache.map(|c| (c, skill)),
+            ..self
+        }
+    }
+    /// Gets the path from `source` to `destination` from the path cache, calling `find` and
+    /// caching its result on a miss. Searches that ran out of budget are not cached.
+    pub fn cached_path<F: FnOnce() -> Result<Path, PathError>>(
+        &self,
+        source: &Node,
+        destination: &Node,
+        find: F,
+    ) -> Result<Path, PathError> {
+        match self.path_cache {
+            Some((cache, skill)) => match cache.get(source, destination, skill) {
+                Some(path) => path,
+                None => {
+                    let path = find();
+                    if path != Err(PathError::BudgetExceeded) {
+                        cache.insert(source, destination, skill, path.clone());
+                    }
+                    path
+                }
+            },
+            None => find(),
+        }
+    }
+    /// Gets the flow field leading to `target` if there is one
+    pub fn flow_field(&self, target: &Node) -> Option<&'a FlowField> {
+        self.flow_fields
+            .iter()
+            .find(|field| field.target() == &target.node)
+            .copied()
     }
     pub fn find_lifts(&self) -> Vec<&'a LiftLayer> {
         self.layers
             .iter()
-            .map(|layer| match layer {
-                GraphLayer::Grid { .. } => None,
+            .filter_map(|layer| match layer {
                 GraphLayer::Lift(l) => Some(l),
+                _ => None,
             })
-            .filter(|l| l.is_some())
-            .map(|l| l.unwrap())
             .collect()
     }
-}
-impl<'a> Graph for GraphLayerList<'a> {
-    fn get_children(&self, node: &Node) -> Vec<(Node, GraphWeight)> {
-        let mut out = vec![];
+    /// Buildings skiiers can walk to. Buildings are not part of the graph so they are only
+    /// found when added with `with_buildings`
+    pub fn with_buildings(self, buildings: Vec<&'a Building>) -> Self {
+        Self { buildings, ..self }
+    }
+    pub fn find_buildings(&self) -> Vec<&'a Building> {
+        self.buildings.clone()
+    }
+    pub fn find_trails(&self) -> Vec<&'a TrailLayer> {
+        self.layers
+            .iter()
+            .filter_map(|layer| match layer {
+                GraphLayer::Trail(t) => Some(t),
+                _ => None,
+            })
+            .collect()
+    }
+    /// Gets children of a node from every layer. If several layers connect the same nodes only
+    /// the cheapest edge is kept.
+    fn merged_children(&self, node: &Node) -> Vec<(Node, GraphWeight)> {
+        let mut out: Vec<(Node, GraphWeight)> = vec![];
         for layer in self.layers.iter() {
-            out.append(&mut layer.get_children(node));
+            for (child, weight) in layer.get_children(node) {
+                if let Some(existing) = out.iter_mut().find(|(n, _)| n == &child) {
+                    if weight < existing.1 {
+                        existing.1 = weight;
+                    }
+                } else {
+                    out.push((child, weight));
+                }
+            }
+        }
+        if self.congestion.is_some() {
+            for (child, weight) in out.iter_mut() {
+                *weight = weight.clone() + self.crowd_weight(child);
+            }
         }
         out
     }
+    /// Walks the straight line of tiles from `start` to `end`. Returns the steps along the line
+    /// or none if a step along the line is not connected. The line is shorter than the grid steps
+    /// it is drawn through so their weights are scaled by the length of the line over the length
+    /// of the steps.
+    fn straight_line(&self, start: &Node, end: &Node) -> Option<Vec<(Node, GraphWeight)>> {
+        let tiles = line_tiles(start.node, end.node);
+        let diagonals = tiles
+            .windows(2)
+            .filter(|w| w[0].x != w[1].x && w[0].y != w[1].y)
+            .count();
+        let steps_length =
+            (tiles.len() - 1 - diagonals) as f32 + diagonals as f32 * std::f32::consts::SQRT_2;
+        let delta = end.node - start.node;
+        let length = ((delta.x * delta.x + delta.y * delta.y) as f32).sqrt();
+        let scale = if steps_length > 0.0 {
+            length / steps_length
+        } else {
+            1.0
+        };
+        let mut steps = vec![];
+        let mut previous = start.clone();
+        //weights are rounded from the running total so the line costs the same however it is
+        //split into steps
+        let mut total = 0;
+        let mut scaled = 0;
+        for tile in tiles.iter().skip(1) {
+            let next = Node { node: *tile };
+            let weight = match self
+                .layers
+                .iter()
+                .map(|layer| layer.get(previous.clone(), next.clone()))
+                .min()
+            {
+                Some(GraphWeight::Some(w)) => w,
+                _ => return None,
+            };
+            total += weight;
+            let step = (total as f32 * scale).round() as i32 - scaled;
+            scaled += step;
+            steps.push((
+                next.clone(),
+                GraphWeight::Some(step) + self.crowd_weight(&next),
+            ));
+            previous = next;
+        }
+        Some(steps)
+    }
+}
+impl<'a> Graph for GraphLayerList<'a> {
+    fn get_children(&self, node: &Node) -> Vec<(Node, GraphWeight)> {
+        self.merged_children(node)
+    }
+    fn contains(&self, node: &Node) -> bool {
+        self.layers.iter().any(|layer| layer.contains(node))
+    }
+    fn line_of_sight(&self, start: &Node, end: &Node) -> Option<Vec<(Node, GraphWeight)>> {
+        self.straight_line(start, end)
+    }
 }
 impl<'a> Graph for &GraphLayerList<'a> {
     fn get_children(&self, node: &Node) -> Vec<(Node, GraphWeight)> {
-        let mut out = vec![];
-        for layer in self.layers.iter() {
-            out.append(&mut layer.get_children(node));
-        }
-        out
+        self.merged_children(node)
+    }
+    fn contains(&self, node: &Node) -> bool {
+        self.layers.iter().any(|layer| layer.contains(node))
+    }
+    fn line_of_sight(&self, start: &Node, end: &Node) -> Option<Vec<(Node, GraphWeight)>> {
+        self.straight_line(start, end)
     }
 }
 pub trait Graph {
     /// Gets children of a given node
     fn get_children(&self, node: &Node) -> Vec<(Node, GraphWeight)>;
+    /// Whether the node is part of the graph. By default every node is.
+    fn contains(&self, _node: &Node) -> bool {
+        true
+    }
+    /// Gets the steps of a straight line between two nodes, not including `start`. Returns none
+    /// if the nodes can not be connected by a straight line. By default graphs have no straight
+    /// lines.
+    fn line_of_sight(&self, _start: &Node, _end: &Node) -> Option<Vec<(Node, GraphWeight)>> {
+        None
+    }
 }
-#[derive(Clone, Debug, PartialEq)]
+/// Octile distance using the cheapest possible straight and diagonal steps, which are steps
+/// along a trail with no change in height. Never overestimates the cost of skiing between two
+/// tiles.
+pub fn octile_heuristic(start: &Node, end: &Node) -> GraphWeight {
+    let straight = Terrain::STRAIGHT_WEIGHT / TrailLayer::WEIGHT_DIVISOR;
+    let diagonal = Terrain::DIAGONAL_WEIGHT / TrailLayer::WEIGHT_DIVISOR;
+    let dx = (end.node.x - start.node.x).abs() as i32;
+    let dy = (end.node.y - start.node.y).abs() as i32;
+    GraphWeight::Some(straight * (dx.max(dy) - dx.min(dy)) + diagonal * dx.min(dy))
+}
+/// Straight line distance using the cheapest possible straight step. Never overestimates the
+/// cost of skiing between two tiles when paths can take straight lines in any direction.
+pub fn euclidean_heuristic(start: &Node, end: &Node) -> GraphWeight {
+    let straight = Terrain::STRAIGHT_WEIGHT / TrailLayer::WEIGHT_DIVISOR;
+    let delta = end.node - start.node;
+    let length = ((delta.x * delta.x + delta.y * delta.y) as f32).sqrt();
+    GraphWeight::Some((straight as f32 * length).floor() as i32)
+}
+/// Tiles on the line from `start` to `end` (inclusive) using
+/// [Bresenham's algorithm](https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm).
+/// Consecutive tiles are neighbors in the 8-connected grid.
+pub fn line_tiles(start: Vector2<i64>, end: Vector2<i64>) -> Vec<Vector2<i64>> {
+    let dx = (end.x - start.x).abs();
+    let dy = -(end.y - start.y).abs();
+    let step_x = if start.x < end.x { 1 } else { -1 };
+    let step_y = if start.y < end.y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut current = start;
+    let mut tiles = vec![current];
+    while current != end {
+        let error_2 = 2 * error;
+        if error_2 >= dy {
+            error += dy;
+            current.x += step_x;
+        }
+        if error_2 <= dx {
+            error += dx;
+            current.y += step_y;
+        }
+        tiles.push(current);
+    }
+    tiles
+}
+#[derive(Clone, Debug, PartialEq, Default)]
 pub struct Path {
     pub path: Vec<(Node, GraphWeight)>,
 }
@@ -292,17 +680,15 @@ impl Path {
         }
     }
 }
-impl Default for Path {
-    fn default() -> Self {
-        Path { path: vec![] }
-    }
-}
 /// Implements Dijkstra's algorythm on a generic graph.
 /// used [wikipedia](https://en.wikipedia.org/wiki/Dijkstra%27s_algorithm) as a refrence
 /// # Preconditions:
 /// Graph Weights are greater than zero. If any of the graph weights are less then zero then
 /// the alorythm panics
-pub fn dijkstra<G: Graph>(source: &Node, destination: &Node, graph: &G) -> Path {
+pub fn dijkstra<G: Graph>(source: &Node, destination: &Node, graph: &G) -> Result<Path, PathError> {
+    if !graph.contains(source) || !graph.contains(destination) {
+        return Err(PathError::OutOfBounds);
+    }
     //queue used to priortize searching
     let mut queue = PriorityQueue::new();
     //annotates previous node in shortest path tree. If item is not preseant then previous is marked as infinite.
@@ -333,6 +719,9 @@ pub fn dijkstra<G: Graph>(source: &Node, destination: &Node, graph: &G) -> Path
             }
         }
     }
+    if destination != source && !previous.contains_key(destination) {
+        return Err(PathError::Unreachable);
+    }
     let mut path: Vec<(Node, GraphWeight)> = vec![];
     let mut current = (destination.clone(), GraphWeight::Some(0));
     path.push(current.clone());
@@ -341,21 +730,32 @@ pub fn dijkstra<G: Graph>(source: &Node, destination: &Node, graph: &G) -> Path
             path.push((node.clone(), weight.clone().clone()));
             current = (node.clone(), weight.clone().clone());
         } else {
-            return Path {
+            return Ok(Path {
                 path: path.iter().rev().cloned().collect(),
-            };
+            });
         }
     }
 }
+/// Estimated weight from a node to the destination, called with (node, destination, graph)
+pub type Heuristic<G> = Box<dyn Fn(&Node, &Node, &G) -> GraphWeight>;
 /// Uses a* to get the shortest distance between a source and destination node
 /// heuristic is a function that thaes in (source,destination,graph) and returns an estimated
-/// weight
+/// weight. Gives up once more than `budget` nodes have been expanded.
 pub fn a_star<G: Graph>(
     source: &Node,
     destination: &Node,
     graph: &G,
-    heuristic: Box<dyn Fn(&Node, &Node, &G) -> GraphWeight>,
-) -> Path {
+    heuristic: Heuristic<G>,
+    budget: usize,
+) -> Result<Path, PathError> {
+    if !graph.contains(source) || !graph.contains(destination) {
+        return Err(PathError::OutOfBounds);
+    }
+    if source == destination {
+        return Ok(Path {
+            path: vec![(source.clone(), GraphWeight::Some(0))],
+        });
+    }
     let null_node = Node {
         node: Vector2::new(0, 0),
     };
@@ -383,7 +783,12 @@ pub fn a_star<G: Graph>(
             Reverse(h),
         );
     }
+    let mut expanded = 0;
     while !open.is_empty() {
+        expanded += 1;
+        if expanded > budget {
+            return Err(PathError::BudgetExceeded);
+        }
         let (parent, _) = open.pop().unwrap();
         closed.insert(parent.node.clone(), parent.clone());
         for (child, child_distance) in graph.get_children(&parent.node).iter() {
@@ -413,9 +818,9 @@ pub fn a_star<G: Graph>(
                         let next = closed[current].clone();
                         if &next.parent == source {
                             path.push((next.parent, GraphWeight::Some(0)));
-                            return Path {
+                            return Ok(Path {
                                 path: (path.iter()).rev().cloned().collect(),
-                            };
+                            });
                         } else {
                             path.push((next.parent, next.g));
                         }
@@ -438,20 +843,160 @@ pub fn a_star<G: Graph>(
             }
         }
     }
-
-    panic!("node not reachable");
+    Err(PathError::Unreachable)
+}
+/// Any-angle variant of `a_star` based on
+/// [Theta*](https://en.wikipedia.org/wiki/Theta*). When a node can be reached by a straight line
+/// from its grandparent the line is used instead of the path through the parent, so paths are
+/// not restricted to the eight grid directions. Straight lines are expanded back into the tiles
+/// they pass through so the returned path has the same layout as `a_star`. Falls back to plain
+/// a* on graphs without line of sight. Gives up once more than `budget` nodes have been
+/// expanded.
+pub fn theta_star<G: Graph>(
+    source: &Node,
+    destination: &Node,
+    graph: &G,
+    heuristic: Heuristic<G>,
+    budget: usize,
+) -> Result<Path, PathError> {
+    if !graph.contains(source) || !graph.contains(destination) {
+        return Err(PathError::OutOfBounds);
+    }
+    let mut open: PriorityQueue<Node, Reverse<GraphWeight>> = PriorityQueue::new();
+    let mut closed: HashSet<Node> = HashSet::new();
+    let mut g: HashMap<Node, GraphWeight> = HashMap::new();
+    //parent of each node along with the steps taken from the parent to the node
+    let mut parent: HashMap<Node, (Node, Vec<(Node, GraphWeight)>)> = HashMap::new();
+    g.insert(source.clone(), GraphWeight::Some(0));
+    open.push(
+        source.clone(),
+        Reverse(heuristic(source, destination, graph)),
+    );
+    let mut expanded = 0;
+    while let Some((node, _)) = open.pop() {
+        expanded += 1;
+        if expanded > budget {
+            return Err(PathError::BudgetExceeded);
+        }
+        if &node == destination {
+            let mut segments = vec![];
+            let mut current = node;
+            while let Some((previous, steps)) = parent.get(&current) {
+                segments.push(steps.clone());
+                current = previous.clone();
+            }
+            let mut path = vec![(source.clone(), GraphWeight::Some(0))];
+            for steps in segments.iter().rev() {
+                path.extend(steps.iter().cloned());
+            }
+            return Ok(Path { path });
+        }
+        closed.insert(node.clone());
+        let node_g = g[&node].clone();
+        let grandparent = parent.get(&node).map(|(p, _)| p.clone());
+        for (child, child_distance) in graph.get_children(&node).iter() {
+            if closed.contains(child) {
+                continue;
+            }
+            //path through the current node
+            let mut best = (
+                node_g.clone() + child_distance.clone(),
+                node.clone(),
+                vec![(child.clone(), child_distance.clone())],
+            );
+            //straight line from the grandparent
+            if let Some(grandparent) = grandparent.as_ref() {
+                if let Some(steps) = graph.line_of_sight(grandparent, child) {
+                    let line_g = g[grandparent].clone()
+                        + steps.iter().map(|(_, weight)| weight.clone()).sum();
+                    if line_g <= best.0 {
+                        best = (line_g, grandparent.clone(), steps);
+                    }
+                }
+            }
+            let (child_g, child_parent, steps) = best;
+            //weights are rounded so a bent path can cost as much as the straight line. Ties go to
+            //the parent reached for less, which draws the longer line.
+            let improved = match (g.get(child), parent.get(child)) {
+                (Some(known), Some((known_parent, _))) if &child_g == known => {
+                    g[&child_parent] < g[known_parent]
+                }
+                (Some(known), _) => &child_g < known,
+                (None, _) => true,
+            };
+            if improved && child_g.is_finite() {
+                g.insert(child.clone(), child_g.clone());
+                parent.insert(child.clone(), (child_parent, steps));
+                let f = child_g + heuristic(child, destination, graph);
+                open.push(child.clone(), Reverse(f));
+            }
+        }
+    }
+    Err(PathError::Unreachable)
+}
+/// Pulls a path tight by replacing stretches of it with straight lines wherever the line costs no
+/// more than the stretch it replaces. Used on paths from flow fields and the path hierarchy,
+/// which only move in the eight grid directions. Lines never cross a lift ride.
+pub fn smooth_path<G: Graph>(path: Path, graph: &G) -> Path {
+    let nodes = path.path;
+    if nodes.len() < 3 {
+        return Path { path: nodes };
+    }
+    let mut smoothed = vec![nodes[0].clone()];
+    let mut anchor = 0;
+    while anchor + 1 < nodes.len() {
+        //furthest node a line from the anchor reaches for no more than the path to it
+        let mut best: Option<(usize, Vec<(Node, GraphWeight)>)> = None;
+        let mut along = GraphWeight::Some(0);
+        for end in anchor + 1..nodes.len() {
+            let step = nodes[end].0.node - nodes[end - 1].0.node;
+            if step.x.abs() > 1 || step.y.abs() > 1 {
+                break;
+            }
+            along = along + nodes[end].1.clone();
+            match graph.line_of_sight(&nodes[anchor].0, &nodes[end].0) {
+                Some(steps) => {
+                    let cost: GraphWeight = steps.iter().map(|(_, w)| w.clone()).sum();
+                    if cost <= along {
+                        best = Some((end, steps));
+                    }
+                }
+                None => break,
+            }
+        }
+        match best {
+            Some((end, steps)) => {
+                smoothed.extend(steps);
+                anchor = end;
+            }
+            None => {
+                smoothed.push(nodes[anchor + 1].clone());
+                anchor += 1;
+            }
+        }
+    }
+    Path { path: smoothed }
 }
 /// Path used to follow
 #[derive(Clone, Debug, PartialEq)]
 pub struct FollowPath {
     pub nodes: Vec<Vector3<f32>>,
+    /// Arc lengths of the smoothed segments between nodes
+    lengths: Vec<[f32; spline::SAMPLES + 1]>,
+    /// Tiles the path passes through
+    tiles: Vec<Node>,
     start: Option<Node>,
     endpoint: Option<Node>,
+    /// Node the follower is on plus the fraction of the distance to the next node travelled
     t: f64,
+    /// Ticks to wait at each node before moving on
+    waits: Vec<u32>,
+    /// Ticks waited so far at the node the follower is on
+    waited: u32,
 }
 impl FollowPath {
     pub fn new(path: Path, terrain: &Terrain) -> Self {
-        let start = if let Some((node, _)) = path.path.get(0) {
+        let start = if let Some((node, _)) = path.path.first() {
             Some(node.clone())
         } else {
             None
@@ -461,19 +1006,105 @@ impl FollowPath {
         } else {
             None
         };
+        let nodes: Vec<Vector3<f32>> = path
+            .path
+            .iter()
+            .map(|n| terrain.get_transform(&n.0.node).unwrap())
+            .collect();
         Self {
             t: 0.0,
             start,
             endpoint,
-            nodes: path
-                .path
-                .iter()
-                .map(|n| terrain.get_transform(&n.0.node).unwrap())
-                .collect(),
+            waits: vec![0; nodes.len()],
+            waited: 0,
+            tiles: path.path.iter().map(|(node, _)| node.clone()).collect(),
+            lengths: spline::arc_lengths(&nodes),
+            nodes,
+        }
+    }
+    /// Ends of the segment the follower is on, none past the last node
+    pub fn segment(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let t0 = self.t.floor() as usize;
+        if t0 + 1 < self.nodes.len() {
+            Some((self.nodes[t0], self.nodes[t0 + 1]))
+        } else {
+            None
+        }
+    }
+    /// Waits `ticks` ticks at the last node before the path ends
+    pub fn with_wait(mut self, ticks: u32) -> Self {
+        if let Some(wait) = self.waits.last_mut() {
+            *wait = ticks;
+        }
+        self
+    }
+    /// Spends a tick waiting if the follower is on a node it has not finished waiting at
+    pub fn wait(&mut self) -> bool {
+        let t0 = self.t.floor();
+        match self.waits.get(t0 as usize) {
+            Some(wait) if self.t == t0 && self.waited < *wait => {
+                self.waited += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Tile the follower is waiting at, none while moving
+    pub fn waiting_at(&self) -> Option<&Node> {
+        let t0 = self.t.floor();
+        match self.waits.get(t0 as usize) {
+            Some(wait) if self.t == t0 && self.waited < *wait => self.tiles.get(t0 as usize),
+            _ => None,
+        }
+    }
+    /// Moves `distance` along the smoothed path, carrying on into the following segments. Stops
+    /// at nodes the follower has to wait at. Past the last node distance is counted in nodes.
+    pub fn incr(&mut self, mut distance: f32) {
+        while distance > 0.0 {
+            let t0 = self.t.floor();
+            match self.lengths.get(t0 as usize) {
+                Some(lengths) => {
+                    let length = lengths[spline::SAMPLES];
+                    let remaining = (1.0 - (self.t - t0)) as f32 * length;
+                    if remaining <= distance {
+                        distance -= remaining;
+                        self.t = t0 + 1.0;
+                        self.waited = 0;
+                        if self.waits[t0 as usize + 1] > 0 {
+                            distance = 0.0;
+                        }
+                    } else {
+                        self.t += (distance / length) as f64;
+                        distance = 0.0;
+                    }
+                }
+                None => {
+                    self.t += distance as f64;
+                    distance = 0.0;
+                }
+            }
         }
     }
-    pub fn incr(&mut self, incr: f64) {
-        self.t += incr
+    /// Spline parameter on the current segment, none past the last node
+    fn parameter(&self) -> Option<(usize, f32)> {
+        let t0 = self.t.floor() as usize;
+        self.lengths
+            .get(t0)
+            .map(|lengths| (t0, spline::parameter(lengths, (self.t - t0 as f64) as f32)))
+    }
+    /// Direction of travel along the smoothed path, none past the last node or when the
+    /// follower is not moving anywhere
+    pub fn heading(&self) -> Option<Vector3<f32>> {
+        const STEP: f32 = 0.01;
+        let (segment, u) = self.parameter()?;
+        let before = spline::point(&self.nodes, segment, (u - STEP).max(0.0));
+        let after = spline::point(&self.nodes, segment, (u + STEP).min(1.0));
+        let delta = after - before;
+        if delta.norm() > f32::EPSILON {
+            Some(delta.normalize())
+        } else {
+            None
+        }
     }
     pub fn start(&self) -> Option<&Node> {
         self.start.as_ref()
@@ -484,10 +1115,17 @@ impl FollowPath {
         for i in other.nodes.iter() {
             nodes.push(*i);
         }
-
+        let mut tiles = self.tiles.clone();
+        tiles.extend(other.tiles.iter().cloned());
+        let mut waits = self.waits.clone();
+        waits.extend(other.waits.iter());
         Self {
             t,
+            waits,
+            waited: self.waited,
+            lengths: spline::arc_lengths(&nodes),
             nodes,
+            tiles,
             start: self.start.clone(),
             endpoint: if other.endpoint.is_some() {
                 other.endpoint.clone()
@@ -507,22 +1145,23 @@ impl FollowPath {
     pub fn endpoint(&self) -> Option<&Node> {
         self.endpoint.as_ref()
     }
-    pub fn get(&self) -> Vector3<f32> {
-        let t0: usize = self.t.floor() as usize;
-        if t0 >= self.nodes.len() {
-            self.nodes[self.nodes.len() - 1]
-        } else if t0 + 1 < self.nodes.len() {
-            let x0 = self.nodes[t0];
-            let x1 = self.nodes[t0 + 1];
-            let t1 = t0 as f64 + 1.0;
-            ((x1 - x0) / (t1 as f32 - t0 as f32)) * (self.t as f32 - t0 as f32) + (x0)
-        } else {
-            self.nodes[t0]
+    pub fn tiles(&self) -> &[Node] {
+        &self.tiles
+    }
+    /// Point on the smoothed path the follower is at, none when the path is empty
+    pub fn get(&self) -> Option<Vector3<f32>> {
+        match self.parameter() {
+            Some((segment, u)) => Some(spline::point(&self.nodes, segment, u)),
+            None => self.nodes.get(self.reached()).copied(),
         }
     }
     pub fn at_end(&self) -> bool {
         self.t > self.nodes.len() as f64
     }
+    /// Index of the last node the follower has reached
+    pub fn reached(&self) -> usize {
+        (self.t.floor() as usize).min(self.nodes.len().saturating_sub(1))
+    }
 }
 pub mod graph_debug {
     use super::GraphLayer;
@@ -543,6 +1182,14 @@ pub mod graph_debug {
                     GraphLayer::Lift(lift) => {
                         ui.label(format!("Lift start: {} end: {}", lift.start, lift.end));
                     }
+                    GraphLayer::Trail(trail) => {
+                        ui.label(format!(
+                            "Trail {} ({}), tiles: {}",
+                            trail.name,
+                            trail.difficulty,
+                            trail.tiles().count()
+                        ));
+                    }
                 }
             }
         });
@@ -561,4 +1208,332 @@ mod test {
         assert!(zero < inf);
         assert!(zero <= inf);
     }
+    fn flat_terrain(size: usize) -> Terrain {
+        Terrain::new_cone(Vector2::new(size, size), Vector2::new(0.0, 0.0), 0.0, 0.0)
+    }
+    fn path_cost(path: &Path) -> GraphWeight {
+        path.path.iter().map(|(_, weight)| weight.clone()).sum()
+    }
+    #[test]
+    fn eight_neighbors() {
+        let grid = flat_terrain(3).build_graph();
+        let center = Node {
+            node: Vector2::new(1, 1),
+        };
+        let children = grid.get_children(&center);
+        assert_eq!(children.len(), 8);
+        assert_eq!(
+            grid.get(
+                center.clone(),
+                Node {
+                    node: Vector2::new(2, 2)
+                }
+            ),
+            GraphWeight::Some(Terrain::DIAGONAL_WEIGHT)
+        );
+        assert_eq!(
+            grid.get(
+                center,
+                Node {
+                    node: Vector2::new(1, 0)
+                }
+            ),
+            GraphWeight::Some(Terrain::STRAIGHT_WEIGHT)
+        );
+        //corners only have three neighbors
+        assert_eq!(
+            grid.get_children(&Node {
+                node: Vector2::new(0, 0)
+            })
+            .len(),
+            3
+        );
+    }
+    #[test]
+    fn line() {
+        assert_eq!(
+            line_tiles(Vector2::new(0, 0), Vector2::new(4, 2)),
+            vec![
+                Vector2::new(0, 0),
+                Vector2::new(1, 1),
+                Vector2::new(2, 1),
+                Vector2::new(3, 2),
+                Vector2::new(4, 2)
+            ]
+        );
+        assert_eq!(
+            line_tiles(Vector2::new(2, 2), Vector2::new(2, 2)),
+            vec![Vector2::new(2, 2)]
+        );
+        assert_eq!(line_tiles(Vector2::new(0, 3), Vector2::new(0, 0)).len(), 4);
+    }
+    #[test]
+    fn any_angle_path_is_straight() {
+        let grid = flat_terrain(10).build_graph();
+        let list = GraphLayerList::new(vec![&grid]);
+        let start = Node {
+            node: Vector2::new(0, 0),
+        };
+        let end = Node {
+            node: Vector2::new(8, 3),
+        };
+        let path = theta_star(
+            &start,
+            &end,
+            &&list,
+            Box::new(|_: &Node, _: &Node, _: &&GraphLayerList| GraphWeight::Some(0)),
+            GraphLayerList::DEFAULT_SEARCH_BUDGET,
+        )
+        .unwrap();
+        let tiles: Vec<Vector2<i64>> = path.path.iter().map(|(n, _)| n.node).collect();
+        assert_eq!(tiles, line_tiles(start.node, end.node));
+        //cheaper than the shortest path over the grid since the line is shorter
+        let shortest = dijkstra(&start, &end, &&list).unwrap();
+        assert_eq!(
+            path_cost(&shortest),
+            GraphWeight::Some(3 * Terrain::DIAGONAL_WEIGHT + 5 * Terrain::STRAIGHT_WEIGHT)
+        );
+        let length = (73.0f32).sqrt() * Terrain::STRAIGHT_WEIGHT as f32;
+        assert!(path_cost(&path) < path_cost(&shortest));
+        assert!(path_cost(&path) >= GraphWeight::Some(length.floor() as i32));
+        //pulling a flow field path over the grid tight gives the same line
+        let field = flow_field::FlowField::new(&list, Vector2::new(10, 10), end.node);
+        let grid = field.path(&list, &start).unwrap();
+        assert_eq!(path_cost(&grid), path_cost(&shortest));
+        let grid_cost = path_cost(&grid);
+        let pulled = smooth_path(grid, &&list);
+        let pulled_tiles: Vec<Vector2<i64>> = pulled.path.iter().map(|(n, _)| n.node).collect();
+        assert_eq!(pulled_tiles, tiles);
+        assert!(path_cost(&pulled) < grid_cost);
+    }
+    /// Two separate lines of nodes: (0,0)-(1,0) and (2,0)-(3,0)
+    struct Islands {}
+    impl Graph for Islands {
+        fn get_children(&self, node: &Node) -> Vec<(Node, GraphWeight)> {
+            let x = node.node.x;
+            [x - 1, x + 1]
+                .iter()
+                .filter(|child| (0..4).contains(*child) && (x < 2) == (**child < 2))
+                .map(|child| {
+                    (
+                        Node {
+                            node: Vector2::new(*child, 0),
+                        },
+                        GraphWeight::Some(1),
+                    )
+                })
+                .collect()
+        }
+        fn contains(&self, node: &Node) -> bool {
+            (0..4).contains(&node.node.x) && node.node.y == 0
+        }
+    }
+    #[test]
+    fn disconnected_graph() {
+        let node = |x: i64| Node {
+            node: Vector2::new(x, 0),
+        };
+        let heuristic = || Box::new(|_: &Node, _: &Node, _: &Islands| GraphWeight::Some(0));
+        assert_eq!(
+            dijkstra(&node(0), &node(3), &Islands {}),
+            Err(PathError::Unreachable)
+        );
+        assert_eq!(
+            a_star(&node(0), &node(3), &Islands {}, heuristic(), 100),
+            Err(PathError::Unreachable)
+        );
+        assert_eq!(
+            theta_star(&node(0), &node(3), &Islands {}, heuristic(), 100),
+            Err(PathError::Unreachable)
+        );
+        assert_eq!(
+            a_star(&node(0), &node(7), &Islands {}, heuristic(), 100),
+            Err(PathError::OutOfBounds)
+        );
+        assert_eq!(
+            dijkstra(&node(-1), &node(1), &Islands {}),
+            Err(PathError::OutOfBounds)
+        );
+        assert_eq!(
+            a_star(&node(0), &node(1), &Islands {}, heuristic(), 100)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            theta_star(&node(3), &node(2), &Islands {}, heuristic(), 100)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+    #[test]
+    fn search_budget() {
+        let grid = flat_terrain(30).build_graph();
+        let list = GraphLayerList::new(vec![&grid]).with_search_budget(5);
+        let start = Node {
+            node: Vector2::new(0, 0),
+        };
+        let end = Node {
+            node: Vector2::new(29, 17),
+        };
+        assert_eq!(
+            theta_star(
+                &start,
+                &end,
+                &&list,
+                Box::new(|_: &Node, _: &Node, _: &&GraphLayerList| GraphWeight::Some(0)),
+                list.search_budget(),
+            ),
+            Err(PathError::BudgetExceeded)
+        );
+        assert!(theta_star(
+            &start,
+            &end,
+            &&list,
+            Box::new(|s: &Node, e: &Node, _: &&GraphLayerList| octile_heuristic(s, e)),
+            GraphLayerList::DEFAULT_SEARCH_BUDGET,
+        )
+        .is_ok());
+        //nodes off the grid
+        assert_eq!(
+            dijkstra(
+                &start,
+                &Node {
+                    node: Vector2::new(0, 30)
+                },
+                &&list
+            ),
+            Err(PathError::OutOfBounds)
+        );
+    }
+    #[test]
+    fn connected_tiles() {
+        let line = vec![Vector2::new(0, 0), Vector2::new(1, 0), Vector2::new(1, 1)];
+        assert!(tiles_connected(&line));
+        let gap = vec![Vector2::new(0, 0), Vector2::new(2, 0)];
+        assert!(!tiles_connected(&gap));
+        let diagonal = vec![Vector2::new(0, 0), Vector2::new(1, 1)];
+        assert!(!tiles_connected(&diagonal));
+        assert!(!tiles_connected(&[]));
+    }
+    #[test]
+    fn trail_reduces_weight() {
+        let terrain = Terrain::new_cone(Vector2::new(6, 6), Vector2::new(0.0, 0.0), 0.0, -1.0);
+        let tiles = vec![Vector2::new(0, 0), Vector2::new(1, 0), Vector2::new(2, 0)];
+        let trail = GraphLayer::Trail(TrailLayer::new(
+            "Run 1".to_string(),
+            Difficulty::Green,
+            &tiles,
+            &terrain,
+        ));
+        let grid = terrain.build_graph();
+        let start = Node {
+            node: Vector2::new(1, 0),
+        };
+        let end = Node {
+            node: Vector2::new(2, 0),
+        };
+        let grid_weight = grid.get(start.clone(), end.clone());
+        let list = GraphLayerList::new(vec![&grid, &trail]);
+        let merged = list
+            .get_children(&start)
+            .iter()
+            .find(|(n, _)| n == &end)
+            .unwrap()
+            .1
+            .clone();
+        assert!(merged < grid_weight);
+        assert_eq!(merged, trail.get(start, end));
+        //edges leaving the trail use the grid
+        assert_eq!(
+            trail
+                .get_children(&Node {
+                    node: Vector2::new(2, 0)
+                })
+                .len(),
+            1
+        );
+    }
+    #[test]
+    fn follow_smoothed_path() {
+        let terrain = flat_terrain(5);
+        //a turn then a diagonal
+        let tiles = [(0, 0), (1, 0), (2, 0), (2, 1), (3, 2), (4, 3)];
+        let path = Path::new(
+            tiles
+                .iter()
+                .map(|(x, y)| {
+                    (
+                        Node {
+                            node: Vector2::new(*x, *y),
+                        },
+                        GraphWeight::Some(10),
+                    )
+                })
+                .collect(),
+        );
+        let mut follow = FollowPath::new(path, &terrain);
+        let mut travelled = 0.0;
+        let mut last = follow.get().unwrap();
+        while follow.segment().is_some() {
+            follow.incr(0.05);
+            let p = follow.get().unwrap();
+            travelled += (p - last).norm();
+            last = p;
+            //stays close to the line through the tiles it passes
+            assert!(
+                spline::distance_to(&follow.nodes, p) < 0.25,
+                "{} off the path",
+                p
+            );
+            if let Some(heading) = follow.heading() {
+                assert!((heading.norm() - 1.0).abs() < 1e-4);
+            }
+        }
+        assert_eq!(last, Vector3::new(4.0, 0.0, 3.0));
+        //moves by distance along a curve close in length to the tiles
+        let straight = 3.0 + 2.0 * 2.0f32.sqrt();
+        assert!((travelled - straight).abs() < 0.2);
+    }
+    #[test]
+    fn follow_path_waits() {
+        let terrain = flat_terrain(5);
+        let path = |tiles: &[i64]| {
+            Path::new(
+                tiles
+                    .iter()
+                    .map(|x| {
+                        (
+                            Node {
+                                node: Vector2::new(*x, 0),
+                            },
+                            GraphWeight::Some(10),
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        let mut follow = FollowPath::new(path(&[0, 1]), &terrain)
+            .with_wait(3)
+            .append(&FollowPath::new(path(&[1, 2]), &terrain));
+        //stops at the node it waits at even when moving far enough to pass it
+        assert_eq!(follow.waiting_at(), None);
+        follow.incr(1.5);
+        assert_eq!(follow.get(), Some(Vector3::new(1.0, 0.0, 0.0)));
+        for _ in 0..3 {
+            assert_eq!(
+                follow.waiting_at().map(|n| n.node),
+                Some(Vector2::new(1, 0))
+            );
+            assert!(follow.wait());
+        }
+        assert!(!follow.wait());
+        assert_eq!(follow.waiting_at(), None);
+        follow.incr(1.5);
+        assert_eq!(follow.get(), Some(Vector3::new(2.0, 0.0, 0.0)));
+        assert!(!follow.wait());
+        //there is nowhere to be on an empty path
+        assert_eq!(FollowPath::new(Path::default(), &terrain).get(), None);
+    }
 }
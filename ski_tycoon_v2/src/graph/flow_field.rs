@@ -0,0 +1,194 @@
+use super::hpa::{search_bounds, skill_layers, tile_weight, Bounds};
+use super::{
+    GraphLayer, GraphLayerList, GraphWeight, Grid, NavigationGraph, Node, Path, PathError,
+};
+use crate::terrain::Difficulty;
+use legion::*;
+use nalgebra::Vector2;
+/// Shortest way to a target from every tile of the map. Built with one reverse Dijkstra from the
+/// target so any number of skiiers heading to the same place can look up their path in time
+/// proportional to its length.
+pub struct FlowField {
+    target: Vector2<i64>,
+    /// Step to take from each tile towards the target. None on the target and on tiles that can
+    /// not reach it.
+    directions: Grid<Option<(i8, i8)>>,
+}
+impl FlowField {
+    pub fn new(layers: &GraphLayerList, dimensions: Vector2<usize>, target: Vector2<i64>) -> Self {
+        let bounds = Bounds {
+            min: Vector2::new(0, 0),
+            max: Vector2::new(dimensions.x as i64, dimensions.y as i64),
+        };
+        let found = if bounds.contains(target) {
+            search_bounds(layers, target, bounds, true)
+        } else {
+            Default::default()
+        };
+        let mut directions = Vec::with_capacity(dimensions.x * dimensions.y);
+        for x in 0..dimensions.x as i64 {
+            for y in 0..dimensions.y as i64 {
+                let tile = Vector2::new(x, y);
+                directions.push(found.get(&tile).and_then(|(_, next)| {
+                    if *next == tile {
+                        None
+                    } else {
+                        Some(((next.x - x) as i8, (next.y - y) as i8))
+                    }
+                }));
+            }
+        }
+        Self {
+            target,
+            directions: Grid::from_vec(directions, dimensions),
+        }
+    }
+    pub fn target(&self) -> &Vector2<i64> {
+        &self.target
+    }
+    /// Follows the field from `start` to the target
+    pub fn path(&self, layers: &GraphLayerList, start: &Node) -> Result<Path, PathError> {
+        let bounds = Bounds {
+            min: Vector2::new(0, 0),
+            max: Vector2::new(
+                self.directions.width() as i64,
+                self.directions.height() as i64,
+            ),
+        };
+        if !bounds.contains(start.node) {
+            return Err(PathError::OutOfBounds);
+        }
+        let mut current = start.node;
+        let mut path = vec![(start.clone(), GraphWeight::Some(0))];
+        while current != self.target {
+            let (dx, dy) = self.directions[Vector2::new(current.x as usize, current.y as usize)]
+                .ok_or(PathError::Unreachable)?;
+            let next = current + Vector2::new(dx as i64, dy as i64);
+            let weight = tile_weight(layers, current, next).ok_or(PathError::Unreachable)?;
+            path.push((Node { node: next }, GraphWeight::Some(weight)));
+            current = next;
+        }
+        Ok(Path::new(path))
+    }
+}
+/// Flow fields toward the base of every lift for each skill. Rebuilt when lifts or trails
+/// change, a new scenario starts with no fields.
+pub struct FlowFields {
+    fields: Vec<(Difficulty, FlowField)>,
+    /// Version of the navigation graph the fields were built from
+    version: u64,
+}
+impl FlowFields {
+    pub fn for_skill(&self, max_difficulty: Difficulty) -> Vec<&FlowField> {
+        self.fields
+            .iter()
+            .filter(|(d, _)| *d == max_difficulty)
+            .map(|(_, f)| f)
+            .collect()
+    }
+}
+fn lift_bases(layers: &[&GraphLayer]) -> Vec<Vector2<i64>> {
+    let mut bases: Vec<Vector2<i64>> = layers
+        .iter()
+        .filter_map(|layer| match layer {
+            GraphLayer::Lift(lift) => Some(lift.start.node),
+            _ => None,
+        })
+        .collect();
+    bases.sort_by_key(|b| (b.x, b.y));
+    bases.dedup();
+    bases
+}
+/// Keeps a flow field toward every lift base for each skill. Fields of lifts that were removed
+/// are dropped, fields of new lifts are built and when a trail changes the fields of the skills
+/// that can ski it are rebuilt.
+pub fn update_flow_fields(world: &mut World) {
+    let (version, dimensions) = match <&NavigationGraph>::query().iter(world).next() {
+        Some(graph) => match graph.dimensions() {
+            Some(dimensions) => (graph.version(), dimensions),
+            None => return,
+        },
+        None => return,
+    };
+    let existing = match <&mut FlowFields>::query().iter_mut(world).next() {
+        Some(fields) => {
+            if fields.version == version {
+                return;
+            }
+            Some((std::mem::take(&mut fields.fields), fields.version))
+        }
+        None => None,
+    };
+    let graph = <&NavigationGraph>::query().iter(world).next().unwrap();
+    let layers = graph.layers();
+    let change = graph.last_change();
+    let bases = lift_bases(&layers);
+    let mut fields = match existing {
+        Some((mut fields, built)) if built + 1 == version && !change.full => {
+            //easiest skill that can ski a region that changed
+            let changed_skill = change.regions.iter().map(|r| r.difficulty).min();
+            fields.retain(|(skill, field)| {
+                bases.contains(&field.target) && changed_skill.map(|d| *skill < d).unwrap_or(true)
+            });
+            fields
+        }
+        _ => vec![],
+    };
+    for skill in Difficulty::ALL.iter() {
+        let skill_graph = skill_layers(&layers, *skill);
+        for base in bases.iter() {
+            let built = fields
+                .iter()
+                .any(|(d, field)| d == skill && &field.target == base);
+            if !built {
+                fields.push((*skill, FlowField::new(&skill_graph, dimensions, *base)));
+            }
+        }
+    }
+    if let Some(existing) = <&mut FlowFields>::query().iter_mut(world).next() {
+        existing.fields = fields;
+        existing.version = version;
+        return;
+    }
+    world.push((FlowFields { fields, version },));
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{dijkstra, Terrain};
+    #[test]
+    fn matches_dijkstra() {
+        let terrain = Terrain::new_cone(Vector2::new(20, 20), Vector2::new(10.0, 10.0), 8.0, -0.4);
+        let grid = terrain.build_graph();
+        let layers = GraphLayerList::new(vec![&grid]);
+        let target = Vector2::new(3, 17);
+        let field = FlowField::new(&layers, terrain.dimensions(), target);
+        for start in [
+            Vector2::new(0, 0),
+            Vector2::new(19, 19),
+            Vector2::new(10, 10),
+        ]
+        .iter()
+        {
+            let path = field.path(&layers, &Node { node: *start }).unwrap();
+            assert_eq!(path.path[0].0.node, *start);
+            assert_eq!(path.endpoint().unwrap().node, target);
+            let shortest =
+                dijkstra(&Node { node: *start }, &Node { node: target }, &layers).unwrap();
+            let cost = |p: &Path| -> GraphWeight { p.path.iter().map(|(_, w)| w.clone()).sum() };
+            assert_eq!(cost(&path), cost(&shortest));
+        }
+        //the target is a path of one tile
+        assert_eq!(
+            field.path(&layers, &Node { node: target }).unwrap().len(),
+            1
+        );
+        //outside of the map
+        for outside in [Vector2::new(25, 0), Vector2::new(0, 20)].iter() {
+            assert_eq!(
+                field.path(&layers, &Node { node: *outside }),
+                Err(PathError::OutOfBounds)
+            );
+        }
+    }
+}
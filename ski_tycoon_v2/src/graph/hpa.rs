@@ -0,0 +1,677 @@
+use super::{
+    octile_heuristic, GraphLayer, GraphLayerList, GraphWeight, GridNode, NavigationGraph, Node,
+    Path, PathError,
+};
+use crate::terrain::Difficulty;
+use legion::*;
+use nalgebra::Vector2;
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+/// Side length of the square clusters the map is divided into
+pub const CLUSTER_SIZE: i64 = 10;
+/// Borders with more than this many connected tiles in a row get a portal at each end of the row
+/// instead of one in the middle
+const LONG_ENTRANCE: usize = 5;
+/// Weight of skiing between two neighboring tiles. Lifts are not used.
+pub(super) fn tile_weight(
+    layers: &GraphLayerList,
+    from: Vector2<i64>,
+    to: Vector2<i64>,
+) -> Option<i32> {
+    let weight = layers
+        .layers
+        .iter()
+        .filter(|layer| !matches!(layer, GraphLayer::Lift(_)))
+        .map(|layer| layer.get(Node { node: from }, Node { node: to }))
+        .min()
+        .unwrap_or(GraphWeight::Infinity);
+    match weight {
+        GraphWeight::Some(w) => Some(w),
+        GraphWeight::Infinity => None,
+    }
+}
+/// Rectangle of tiles from `min` (inclusive) to `max` (exclusive)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Bounds {
+    pub min: Vector2<i64>,
+    pub max: Vector2<i64>,
+}
+impl Bounds {
+    pub fn contains(&self, tile: Vector2<i64>) -> bool {
+        tile.x >= self.min.x && tile.y >= self.min.y && tile.x < self.max.x && tile.y < self.max.y
+    }
+}
+/// Dijkstra restricted to the tiles in `bounds`. Forward searches get the cost from `start` to
+/// each tile along with the previous tile on the path. Reverse searches get the cost from each
+/// tile to `start` along with the next tile on the path.
+pub(super) fn search_bounds(
+    layers: &GraphLayerList,
+    start: Vector2<i64>,
+    bounds: Bounds,
+    reverse: bool,
+) -> HashMap<Vector2<i64>, (i32, Vector2<i64>)> {
+    let mut found: HashMap<Vector2<i64>, (i32, Vector2<i64>)> = HashMap::new();
+    let mut done: HashSet<Vector2<i64>> = HashSet::new();
+    let mut queue = BinaryHeap::new();
+    found.insert(start, (0, start));
+    queue.push(Reverse((0, start.x, start.y)));
+    while let Some(Reverse((cost, x, y))) = queue.pop() {
+        let tile = Vector2::new(x, y);
+        if !done.insert(tile) {
+            continue;
+        }
+        for (dx, dy) in GridNode::DIRECTIONS.iter() {
+            let neighbor = tile + Vector2::new(*dx, *dy);
+            if !bounds.contains(neighbor) || done.contains(&neighbor) {
+                continue;
+            }
+            let weight = if reverse {
+                tile_weight(layers, neighbor, tile)
+            } else {
+                tile_weight(layers, tile, neighbor)
+            };
+            if let Some(weight) = weight {
+                let total = cost + weight;
+                let improved = match found.get(&neighbor) {
+                    Some((known, _)) => total < *known,
+                    None => true,
+                };
+                if improved {
+                    found.insert(neighbor, (total, tile));
+                    queue.push(Reverse((total, neighbor.x, neighbor.y)));
+                }
+            }
+        }
+    }
+    found
+}
+/// Gets the path from the start of a forward search to `end`
+fn forward_path(
+    layers: &GraphLayerList,
+    found: &HashMap<Vector2<i64>, (i32, Vector2<i64>)>,
+    end: Vector2<i64>,
+) -> Option<Vec<(Node, GraphWeight)>> {
+    let mut tiles = vec![end];
+    let mut current = end;
+    loop {
+        let (_, previous) = found.get(&current)?;
+        if *previous == current {
+            break;
+        }
+        current = *previous;
+        tiles.push(current);
+    }
+    tiles.reverse();
+    let mut path = vec![(Node { node: tiles[0] }, GraphWeight::Some(0))];
+    for pair in tiles.windows(2) {
+        path.push((
+            Node { node: pair[1] },
+            GraphWeight::Some(tile_weight(layers, pair[0], pair[1])?),
+        ));
+    }
+    Some(path)
+}
+/// Pair of tiles on either side of a cluster border
+type Portal = (Vector2<i64>, Vector2<i64>);
+/// Portals each portal is connected to along with the cost of getting there
+type Edges = HashMap<Vector2<i64>, Vec<(Vector2<i64>, i32)>>;
+/// Graph of portals between clusters used to find paths on large maps, see
+/// [HPA*](https://webdocs.cs.ualberta.ca/~mmueller/ps/hpastar.pdf). The map is divided into
+/// square clusters. Tiles on either side of a cluster border become portals, portals in the same
+/// cluster are connected by the cost of the shortest path between them inside the cluster. A path
+/// is found by searching the portal graph and then filling in the path inside each cluster.
+pub struct PathHierarchy {
+    dimensions: Vector2<i64>,
+    /// Portals on the border between a cluster and the next cluster along x (axis 0) or y
+    /// (axis 1). Each portal is the pair of tiles on either side of the border.
+    borders: HashMap<(Vector2<i64>, usize), Vec<Portal>>,
+    /// Edges between portals that cross a border
+    inter: Edges,
+    /// Edges between portals of the same cluster, keyed by cluster
+    intra: HashMap<Vector2<i64>, Edges>,
+}
+impl PathHierarchy {
+    pub fn new(layers: &GraphLayerList, dimensions: Vector2<usize>) -> Self {
+        let mut hierarchy = Self {
+            dimensions: Vector2::new(dimensions.x as i64, dimensions.y as i64),
+            borders: HashMap::new(),
+            inter: HashMap::new(),
+            intra: HashMap::new(),
+        };
+        let clusters = hierarchy.clusters();
+        for cluster in clusters.iter() {
+            hierarchy.build_borders(layers, *cluster);
+        }
+        hierarchy.build_inter(layers);
+        for cluster in clusters.iter() {
+            hierarchy.build_intra(layers, *cluster);
+        }
+        hierarchy
+    }
+    /// Rebuilds the parts of the hierarchy around tiles whose weights changed
+    pub fn update(&mut self, layers: &GraphLayerList, tiles: &[Vector2<i64>]) {
+        let mut changed: Vec<Vector2<i64>> = tiles
+            .iter()
+            .filter(|t| self.in_bounds(**t))
+            .map(|t| Self::cluster_of(*t))
+            .collect();
+        changed.sort_by_key(|c| (c.x, c.y));
+        changed.dedup();
+        if changed.is_empty() {
+            return;
+        }
+        let mut affected = vec![];
+        for cluster in changed.iter() {
+            self.build_borders(layers, *cluster);
+            for neighbor in [*cluster - Vector2::new(1, 0), *cluster - Vector2::new(0, 1)].iter() {
+                if self.cluster_exists(*neighbor) {
+                    self.build_borders(layers, *neighbor);
+                }
+            }
+            for offset in [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+                let neighbor = cluster + Vector2::new(offset.0, offset.1);
+                if self.cluster_exists(neighbor) {
+                    affected.push(neighbor);
+                }
+            }
+        }
+        affected.sort_by_key(|c| (c.x, c.y));
+        affected.dedup();
+        self.build_inter(layers);
+        for cluster in affected.iter() {
+            self.build_intra(layers, *cluster);
+        }
+    }
+    fn in_bounds(&self, tile: Vector2<i64>) -> bool {
+        Bounds {
+            min: Vector2::new(0, 0),
+            max: self.dimensions,
+        }
+        .contains(tile)
+    }
+    fn cluster_of(tile: Vector2<i64>) -> Vector2<i64> {
+        Vector2::new(tile.x / CLUSTER_SIZE, tile.y / CLUSTER_SIZE)
+    }
+    fn cluster_count(&self) -> Vector2<i64> {
+        Vector2::new(
+            (self.dimensions.x + CLUSTER_SIZE - 1) / CLUSTER_SIZE,
+            (self.dimensions.y + CLUSTER_SIZE - 1) / CLUSTER_SIZE,
+        )
+    }
+    fn cluster_exists(&self, cluster: Vector2<i64>) -> bool {
+        let count = self.cluster_count();
+        cluster.x >= 0 && cluster.y >= 0 && cluster.x < count.x && cluster.y < count.y
+    }
+    fn clusters(&self) -> Vec<Vector2<i64>> {
+        let count = self.cluster_count();
+        (0..count.x)
+            .flat_map(|x| (0..count.y).map(move |y| Vector2::new(x, y)))
+            .collect()
+    }
+    fn bounds(&self, cluster: Vector2<i64>) -> Bounds {
+        let min = cluster * CLUSTER_SIZE;
+        Bounds {
+            min,
+            max: Vector2::new(
+                (min.x + CLUSTER_SIZE).min(self.dimensions.x),
+                (min.y + CLUSTER_SIZE).min(self.dimensions.y),
+            ),
+        }
+    }
+    /// Finds portals on the borders between `cluster` and the next clusters along x and y
+    fn build_borders(&mut self, layers: &GraphLayerList, cluster: Vector2<i64>) {
+        let bounds = self.bounds(cluster);
+        for axis in 0..2 {
+            let next = if axis == 0 {
+                cluster + Vector2::new(1, 0)
+            } else {
+                cluster + Vector2::new(0, 1)
+            };
+            if !self.cluster_exists(next) {
+                self.borders.remove(&(cluster, axis));
+                continue;
+            }
+            let crossings: Vec<(Vector2<i64>, Vector2<i64>)> = if axis == 0 {
+                (bounds.min.y..bounds.max.y)
+                    .map(|y| {
+                        (
+                            Vector2::new(bounds.max.x - 1, y),
+                            Vector2::new(bounds.max.x, y),
+                        )
+                    })
+                    .collect()
+            } else {
+                (bounds.min.x..bounds.max.x)
+                    .map(|x| {
+                        (
+                            Vector2::new(x, bounds.max.y - 1),
+                            Vector2::new(x, bounds.max.y),
+                        )
+                    })
+                    .collect()
+            };
+            let mut portals = vec![];
+            let mut run: Vec<(Vector2<i64>, Vector2<i64>)> = vec![];
+            let mut close_run = |run: &mut Vec<(Vector2<i64>, Vector2<i64>)>| {
+                if run.len() > LONG_ENTRANCE {
+                    portals.push(run[0]);
+                    portals.push(run[run.len() - 1]);
+                } else if !run.is_empty() {
+                    portals.push(run[run.len() / 2]);
+                }
+                run.clear();
+            };
+            for (a, b) in crossings.iter() {
+                let connected =
+                    tile_weight(layers, *a, *b).is_some() || tile_weight(layers, *b, *a).is_some();
+                if connected {
+                    run.push((*a, *b));
+                } else {
+                    close_run(&mut run);
+                }
+            }
+            close_run(&mut run);
+            self.borders.insert((cluster, axis), portals);
+        }
+    }
+    fn build_inter(&mut self, layers: &GraphLayerList) {
+        let mut keys: Vec<&(Vector2<i64>, usize)> = self.borders.keys().collect();
+        keys.sort_by_key(|(c, axis)| (c.x, c.y, *axis));
+        let mut inter: Edges = HashMap::new();
+        for key in keys.iter() {
+            for (a, b) in self.borders[key].iter() {
+                if let Some(weight) = tile_weight(layers, *a, *b) {
+                    inter.entry(*a).or_default().push((*b, weight));
+                }
+                if let Some(weight) = tile_weight(layers, *b, *a) {
+                    inter.entry(*b).or_default().push((*a, weight));
+                }
+            }
+        }
+        self.inter = inter;
+    }
+    /// Portals that are inside of `cluster`
+    fn portals(&self, cluster: Vector2<i64>) -> Vec<Vector2<i64>> {
+        let mut portals = vec![];
+        for axis in 0..2 {
+            if let Some(border) = self.borders.get(&(cluster, axis)) {
+                portals.extend(border.iter().map(|(a, _)| *a));
+            }
+            let previous = if axis == 0 {
+                cluster - Vector2::new(1, 0)
+            } else {
+                cluster - Vector2::new(0, 1)
+            };
+            if let Some(border) = self.borders.get(&(previous, axis)) {
+                portals.extend(border.iter().map(|(_, b)| *b));
+            }
+        }
+        portals.sort_by_key(|p| (p.x, p.y));
+        portals.dedup();
+        portals
+    }
+    fn build_intra(&mut self, layers: &GraphLayerList, cluster: Vector2<i64>) {
+        let bounds = self.bounds(cluster);
+        let portals = self.portals(cluster);
+        let mut edges = HashMap::new();
+        for portal in portals.iter() {
+            let found = search_bounds(layers, *portal, bounds, false);
+            let reachable: Vec<(Vector2<i64>, i32)> = portals
+                .iter()
+                .filter(|other| *other != portal)
+                .filter_map(|other| found.get(other).map(|(cost, _)| (*other, *cost)))
+                .collect();
+            edges.insert(*portal, reachable);
+        }
+        self.intra.insert(cluster, edges);
+    }
+    /// Finds a path between two tiles by skiing. Paths are close to but not always the shortest.
+    pub fn find_path(
+        &self,
+        layers: &GraphLayerList,
+        start: &Node,
+        goal: &Node,
+    ) -> Result<Path, PathError> {
+        let (start, goal) = (start.node, goal.node);
+        if !self.in_bounds(start) || !self.in_bounds(goal) {
+            return Err(PathError::OutOfBounds);
+        }
+        let start_cluster = Self::cluster_of(start);
+        let goal_cluster = Self::cluster_of(goal);
+        let from_start = search_bounds(layers, start, self.bounds(start_cluster), false);
+        if start_cluster == goal_cluster && from_start.contains_key(&goal) {
+            return forward_path(layers, &from_start, goal)
+                .map(Path::new)
+                .ok_or(PathError::Unreachable);
+        }
+        let to_goal = search_bounds(layers, goal, self.bounds(goal_cluster), true);
+        let waypoints = self
+            .search_portals(start, goal, &from_start, &to_goal)
+            .ok_or(PathError::Unreachable)?;
+        let mut path = vec![(Node { node: start }, GraphWeight::Some(0))];
+        for pair in waypoints.windows(2) {
+            if Self::cluster_of(pair[0]) == Self::cluster_of(pair[1]) {
+                let found = search_bounds(
+                    layers,
+                    pair[0],
+                    self.bounds(Self::cluster_of(pair[0])),
+                    false,
+                );
+                let steps = forward_path(layers, &found, pair[1]).ok_or(PathError::Unreachable)?;
+                path.extend(steps.into_iter().skip(1));
+            } else {
+                let weight = tile_weight(layers, pair[0], pair[1]).ok_or(PathError::Unreachable)?;
+                path.push((Node { node: pair[1] }, GraphWeight::Some(weight)));
+            }
+        }
+        Ok(Path::new(path))
+    }
+    /// A* over the portal graph. Returns the tiles visited from `start` to `goal`
+    fn search_portals(
+        &self,
+        start: Vector2<i64>,
+        goal: Vector2<i64>,
+        from_start: &HashMap<Vector2<i64>, (i32, Vector2<i64>)>,
+        to_goal: &HashMap<Vector2<i64>, (i32, Vector2<i64>)>,
+    ) -> Option<Vec<Vector2<i64>>> {
+        let start_cluster = Self::cluster_of(start);
+        let goal_cluster = Self::cluster_of(goal);
+        let heuristic =
+            |tile: Vector2<i64>| match octile_heuristic(&Node { node: tile }, &Node { node: goal })
+            {
+                GraphWeight::Some(h) => h,
+                GraphWeight::Infinity => 0,
+            };
+        let mut open: PriorityQueue<Vector2<i64>, Reverse<i32>> = PriorityQueue::new();
+        let mut best: HashMap<Vector2<i64>, (i32, Option<Vector2<i64>>)> = HashMap::new();
+        let mut closed: HashSet<Vector2<i64>> = HashSet::new();
+        best.insert(start, (0, None));
+        open.push(start, Reverse(heuristic(start)));
+        while let Some((tile, _)) = open.pop() {
+            if tile == goal {
+                let mut waypoints = vec![goal];
+                let mut current = goal;
+                while let Some((_, Some(previous))) = best.get(&current) {
+                    waypoints.push(*previous);
+                    current = *previous;
+                }
+                waypoints.reverse();
+                return Some(waypoints);
+            }
+            closed.insert(tile);
+            let cost = best[&tile].0;
+            let mut edges: Vec<(Vector2<i64>, i32)> = vec![];
+            if tile == start {
+                edges.extend(
+                    self.portals(start_cluster)
+                        .iter()
+                        .filter_map(|p| from_start.get(p).map(|(c, _)| (*p, *c))),
+                );
+            }
+            if let Some(cluster_edges) = self.intra.get(&Self::cluster_of(tile)) {
+                if let Some(portal_edges) = cluster_edges.get(&tile) {
+                    edges.extend(portal_edges.iter().cloned());
+                }
+            }
+            if let Some(portal_edges) = self.inter.get(&tile) {
+                edges.extend(portal_edges.iter().cloned());
+            }
+            if Self::cluster_of(tile) == goal_cluster {
+                if let Some((c, _)) = to_goal.get(&tile) {
+                    edges.push((goal, *c));
+                }
+            }
+            for (next, weight) in edges.iter() {
+                if closed.contains(next) {
+                    continue;
+                }
+                let total = cost + weight;
+                let improved = match best.get(next) {
+                    Some((known, _)) => total < *known,
+                    None => true,
+                };
+                if improved {
+                    best.insert(*next, (total, Some(tile)));
+                    open.push(*next, Reverse(total + heuristic(*next)));
+                }
+            }
+        }
+        None
+    }
+}
+/// Path hierarchy for each skill. Skiiers only get the benefit of trails at or below their
+/// skill so each skill has its own hierarchy.
+pub struct PathHierarchies {
+    hierarchies: Vec<(Difficulty, PathHierarchy)>,
+    /// Version of the navigation graph the hierarchies were built from
+    version: u64,
+    /// Tiles that changed since the last update, keyed by the easiest skill affected
+    dirty: Vec<(Difficulty, Vector2<i64>)>,
+}
+impl PathHierarchies {
+    pub fn for_skill(&self, max_difficulty: Difficulty) -> Option<&PathHierarchy> {
+        self.hierarchies
+            .iter()
+            .find(|(d, _)| *d == max_difficulty)
+            .map(|(_, h)| h)
+    }
+    /// Marks tiles whose weights changed for skiiers of `difficulty` and above. They are
+    /// rebuilt on the next call to `update_path_hierarchies`.
+    pub fn invalidate(&mut self, difficulty: Difficulty, tiles: &[Vector2<i64>]) {
+        self.dirty.extend(tiles.iter().map(|t| (difficulty, *t)));
+    }
+}
+/// Layers used to build the hierarchy for a skill: the grid and the trails at or below the skill
+pub(super) fn skill_layers<'a>(
+    layers: &[&'a GraphLayer],
+    max_difficulty: Difficulty,
+) -> GraphLayerList<'a> {
+    GraphLayerList::new(
+        layers
+            .iter()
+            .filter(|layer| match layer {
+                GraphLayer::Grid { .. } => true,
+                GraphLayer::Trail(t) => t.difficulty <= max_difficulty,
+                GraphLayer::Lift(_) => false,
+            })
+            .copied()
+            .collect(),
+    )
+}
+/// Builds the path hierarchies from the navigation graph and keeps them up to date as it
+/// changes. Only the clusters around changed tiles are rebuilt.
+pub fn update_path_hierarchies(world: &mut World) {
+    let (version, dimensions) = match <&NavigationGraph>::query().iter(world).next() {
+        Some(graph) => match graph.dimensions() {
+            Some(dimensions) => (graph.version(), dimensions),
+            None => return,
+        },
+        None => return,
+    };
+    let existing = match <&mut PathHierarchies>::query().iter_mut(world).next() {
+        Some(hierarchies) => {
+            if hierarchies.version == version && hierarchies.dirty.is_empty() {
+                return;
+            }
+            Some((
+                std::mem::take(&mut hierarchies.hierarchies),
+                hierarchies.version,
+                std::mem::take(&mut hierarchies.dirty),
+            ))
+        }
+        None => None,
+    };
+    let graph = <&NavigationGraph>::query().iter(world).next().unwrap();
+    let layers = graph.layers();
+    let change = graph.last_change();
+    let hierarchies = match existing {
+        //one change behind, or only tiles were invalidated
+        Some((mut hierarchies, built, mut dirty))
+            if built == version || (built + 1 == version && !change.full) =>
+        {
+            if built != version {
+                for region in change.regions.iter() {
+                    dirty.extend(region.tiles.iter().map(|t| (region.difficulty, *t)));
+                }
+            }
+            for (skill, hierarchy) in hierarchies.iter_mut() {
+                let tiles: Vec<Vector2<i64>> = dirty
+                    .iter()
+                    .filter(|(d, _)| d <= skill)
+                    .map(|(_, t)| *t)
+                    .collect();
+                hierarchy.update(&skill_layers(&layers, *skill), &tiles);
+            }
+            hierarchies
+        }
+        _ => Difficulty::ALL
+            .iter()
+            .map(|d| {
+                (
+                    *d,
+                    PathHierarchy::new(&skill_layers(&layers, *d), dimensions),
+                )
+            })
+            .collect(),
+    };
+    if let Some(existing) = <&mut PathHierarchies>::query().iter_mut(world).next() {
+        existing.hierarchies = hierarchies;
+        existing.version = version;
+        return;
+    }
+    world.push((PathHierarchies {
+        hierarchies,
+        version,
+        dirty: vec![],
+    },));
+}
+#[cfg(test)]
+mod test {
+    use super::super::{dijkstra, TrailLayer};
+    use super::*;
+    use crate::prelude::{Grid, Terrain};
+    fn cost(path: &Path) -> i32 {
+        match path.path.iter().map(|(_, w)| w.clone()).sum() {
+            GraphWeight::Some(c) => c,
+            GraphWeight::Infinity => panic!("infinite path"),
+        }
+    }
+    fn assert_connected(path: &Path, start: Vector2<i64>, goal: Vector2<i64>) {
+        assert_eq!(path.path.first().unwrap().0.node, start);
+        assert_eq!(path.endpoint().unwrap().node, goal);
+        for pair in path.path.windows(2) {
+            let delta = pair[1].0.node - pair[0].0.node;
+            assert!(delta.x.abs() <= 1 && delta.y.abs() <= 1);
+        }
+    }
+    #[test]
+    fn close_to_shortest() {
+        let terrain = Terrain::new_cone(Vector2::new(35, 35), Vector2::new(17.0, 17.0), 10.0, -0.1);
+        let grid = terrain.build_graph();
+        let layers = GraphLayerList::new(vec![&grid]);
+        let hierarchy = PathHierarchy::new(&layers, terrain.dimensions());
+        for (start, goal) in [
+            (Vector2::new(0, 0), Vector2::new(34, 30)),
+            (Vector2::new(3, 33), Vector2::new(31, 2)),
+            (Vector2::new(12, 12), Vector2::new(15, 17)),
+        ]
+        .iter()
+        {
+            let path = hierarchy
+                .find_path(&layers, &Node { node: *start }, &Node { node: *goal })
+                .unwrap();
+            assert_connected(&path, *start, *goal);
+            let shortest =
+                dijkstra(&Node { node: *start }, &Node { node: *goal }, &layers).unwrap();
+            assert!(cost(&path) as f32 <= cost(&shortest) as f32 * 1.25);
+        }
+    }
+    #[test]
+    fn unreachable() {
+        //wall along x = 12 that can not be crossed
+        let dimensions = Vector2::new(25, 25);
+        let data = (0..25)
+            .flat_map(|x| (0..25).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                GridNode::from_weights(|direction| {
+                    let to = Vector2::new(x, y) + direction;
+                    if to.x < 0 || to.y < 0 || to.x >= 25 || to.y >= 25 || to.x == 12 || x == 12 {
+                        GraphWeight::Infinity
+                    } else {
+                        GraphWeight::Some(10)
+                    }
+                })
+            })
+            .collect();
+        let grid = GraphLayer::Grid {
+            grid: Grid::from_vec(data, dimensions),
+        };
+        let layers = GraphLayerList::new(vec![&grid]);
+        let hierarchy = PathHierarchy::new(&layers, dimensions);
+        let start = Node {
+            node: Vector2::new(2, 2),
+        };
+        assert_eq!(
+            hierarchy.find_path(
+                &layers,
+                &start,
+                &Node {
+                    node: Vector2::new(20, 20)
+                }
+            ),
+            Err(PathError::Unreachable)
+        );
+        assert!(hierarchy
+            .find_path(
+                &layers,
+                &start,
+                &Node {
+                    node: Vector2::new(8, 20)
+                }
+            )
+            .is_ok());
+        assert_eq!(
+            hierarchy.find_path(
+                &layers,
+                &start,
+                &Node {
+                    node: Vector2::new(30, 2)
+                }
+            ),
+            Err(PathError::OutOfBounds)
+        );
+    }
+    #[test]
+    fn incremental_update_matches_rebuild() {
+        let terrain = Terrain::new_cone(Vector2::new(30, 30), Vector2::new(0.0, 0.0), 10.0, -0.3);
+        let grid = terrain.build_graph();
+        let tiles: Vec<Vector2<i64>> = (0..30).map(|i| Vector2::new(i, i)).collect();
+        let mut trail_tiles = tiles.clone();
+        trail_tiles.extend((0..29).map(|i| Vector2::new(i + 1, i)));
+        let trail = GraphLayer::Trail(TrailLayer::new(
+            "Run 1".to_string(),
+            Difficulty::Green,
+            &trail_tiles,
+            &terrain,
+        ));
+        let without_trail = GraphLayerList::new(vec![&grid]);
+        let with_trail = GraphLayerList::new(vec![&grid, &trail]);
+        let mut updated = PathHierarchy::new(&without_trail, terrain.dimensions());
+        updated.update(&with_trail, &trail_tiles);
+        let rebuilt = PathHierarchy::new(&with_trail, terrain.dimensions());
+        let start = Node {
+            node: Vector2::new(29, 29),
+        };
+        let goal = Node {
+            node: Vector2::new(0, 0),
+        };
+        let updated_path = updated.find_path(&with_trail, &start, &goal).unwrap();
+        let rebuilt_path = rebuilt.find_path(&with_trail, &start, &goal).unwrap();
+        assert_eq!(cost(&updated_path), cost(&rebuilt_path));
+        let old = PathHierarchy::new(&without_trail, terrain.dimensions())
+            .find_path(&without_trail, &start, &goal)
+            .unwrap();
+        assert!(cost(&updated_path) < cost(&old));
+    }
+}
@@ -0,0 +1,435 @@
+use super::{GraphLayer, GridNode, Node, Path, PathError};
+use crate::terrain::Difficulty;
+use legion::*;
+use nalgebra::Vector2;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+/// Tiles whose weights changed for skiiers of `difficulty` and above
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirtyRegion {
+    pub difficulty: Difficulty,
+    pub tiles: Vec<Vector2<i64>>,
+}
+/// Changes made to the graph by the last update
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GraphChange {
+    /// The whole graph changed, everything built from it has to be rebuilt
+    pub full: bool,
+    pub regions: Vec<DirtyRegion>,
+    /// Bases of lifts that were removed
+    pub removed_lifts: Vec<Vector2<i64>>,
+}
+impl GraphChange {
+    fn add_layer(&mut self, layer: &GraphLayer, added: bool) {
+        match layer {
+            GraphLayer::Grid { .. } => self.full = true,
+            GraphLayer::Trail(trail) => {
+                let mut tiles: Vec<Vector2<i64>> = trail.tiles().map(|n| n.node).collect();
+                tiles.sort_by_key(|t| (t.x, t.y));
+                self.regions.push(DirtyRegion {
+                    difficulty: trail.difficulty,
+                    tiles,
+                });
+            }
+            GraphLayer::Lift(lift) => {
+                if !added {
+                    self.removed_lifts.push(lift.start.node);
+                }
+            }
+        }
+    }
+}
+/// Source, destination and skill of a cached path
+type PathKey = (Node, Node, Difficulty);
+/// Cached paths along with when they were last used
+struct LruPaths {
+    capacity: usize,
+    /// Incremented on every use
+    tick: u64,
+    entries: HashMap<PathKey, (Result<Path, PathError>, u64)>,
+    /// Keys ordered by last use
+    order: BTreeMap<u64, PathKey>,
+}
+impl LruPaths {
+    fn touch(&mut self, key: &PathKey) {
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.get_mut(key) {
+            self.order.remove(used);
+            *used = self.tick;
+            self.order.insert(self.tick, key.clone());
+        }
+    }
+    fn remove(&mut self, key: &PathKey) {
+        if let Some((_, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+        }
+    }
+}
+/// Least recently used cache of paths keyed by source, destination and skill. Safe to share
+/// between skiiers, entries are removed when the graph changes under them.
+pub struct PathCache {
+    paths: Mutex<LruPaths>,
+}
+impl Default for PathCache {
+    fn default() -> Self {
+        Self::new(Self::CAPACITY)
+    }
+}
+impl PathCache {
+    /// Default number of paths kept
+    pub const CAPACITY: usize = 4096;
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            paths: Mutex::new(LruPaths {
+                capacity,
+                tick: 0,
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+            }),
+        }
+    }
+    /// Gets a cached search result. Returns none on a miss.
+    pub fn get(
+        &self,
+        source: &Node,
+        destination: &Node,
+        skill: Difficulty,
+    ) -> Option<Result<Path, PathError>> {
+        let mut paths = self.paths.lock().unwrap();
+        let key = (source.clone(), destination.clone(), skill);
+        paths.touch(&key);
+        paths.entries.get(&key).map(|(path, _)| path.clone())
+    }
+    /// Caches the result of a search, evicting the least recently used path if the cache is full
+    pub fn insert(
+        &self,
+        source: &Node,
+        destination: &Node,
+        skill: Difficulty,
+        path: Result<Path, PathError>,
+    ) {
+        let mut paths = self.paths.lock().unwrap();
+        let key = (source.clone(), destination.clone(), skill);
+        paths.remove(&key);
+        if paths.capacity == 0 {
+            return;
+        }
+        if paths.entries.len() >= paths.capacity {
+            if let Some((_, oldest)) = paths.order.iter().next().map(|(t, k)| (*t, k.clone())) {
+                paths.remove(&oldest);
+            }
+        }
+        paths.tick += 1;
+        let tick = paths.tick;
+        paths.order.insert(tick, key.clone());
+        paths.entries.insert(key, (path, tick));
+    }
+    pub fn len(&self) -> usize {
+        self.paths.lock().unwrap().entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Removes paths that may have changed after `change`: every path of skiiers that can ski a
+    /// changed region, since even paths that do not cross it may now be found differently, and
+    /// paths heading to a removed lift. What is left is exactly what a new search would find so
+    /// the order paths are cached and evicted in never changes the simulation.
+    pub fn invalidate(&self, change: &GraphChange) {
+        let mut paths = self.paths.lock().unwrap();
+        if change.full {
+            paths.entries.clear();
+            paths.order.clear();
+            return;
+        }
+        //easiest skill that can ski a region that changed
+        let changed_skill = change.regions.iter().map(|r| r.difficulty).min();
+        let stale: Vec<PathKey> = paths
+            .entries
+            .keys()
+            .filter(|(_, destination, skill)| {
+                change.removed_lifts.contains(&destination.node)
+                    || changed_skill.map(|d| d <= *skill).unwrap_or(false)
+            })
+            .cloned()
+            .collect();
+        for key in stale.iter() {
+            paths.remove(key);
+        }
+    }
+}
+/// Copy of the graph layers in the world that is only updated when layers are added or removed.
+/// The version goes up on every change so what is built from the graph knows when to update.
+pub struct NavigationGraph {
+    version: u64,
+    /// Entities the layers were copied from
+    entities: Vec<Entity>,
+    layers: Vec<GraphLayer>,
+    change: GraphChange,
+    /// Regions of grid layers edited in place since the last update
+    pending: Vec<DirtyRegion>,
+    cache: PathCache,
+}
+impl NavigationGraph {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+    pub fn layers(&self) -> Vec<&GraphLayer> {
+        self.layers.iter().collect()
+    }
+    /// Changes made by the update that produced the current version
+    pub fn last_change(&self) -> &GraphChange {
+        &self.change
+    }
+    pub fn path_cache(&self) -> &PathCache {
+        &self.cache
+    }
+    /// Dimensions of the terrain grid
+    pub fn dimensions(&self) -> Option<Vector2<usize>> {
+        self.layers.iter().find_map(|layer| match layer {
+            GraphLayer::Grid { grid } => Some(Vector2::new(grid.width(), grid.height())),
+            _ => None,
+        })
+    }
+    /// Copies nodes of a grid layer that were changed in place. The tiles are handed to what is
+    /// built from the graph by the next `update_navigation_graph`.
+    pub fn edit_grid(&mut self, entity: Entity, nodes: Vec<(Vector2<i64>, GridNode)>) {
+        let index = match self.entities.iter().position(|e| *e == entity) {
+            Some(index) => index,
+            None => return,
+        };
+        let grid = match &mut self.layers[index] {
+            GraphLayer::Grid { grid } => grid,
+            _ => return,
+        };
+        let mut tiles = vec![];
+        for (position, node) in nodes.into_iter() {
+            grid[Vector2::new(position.x as usize, position.y as usize)] = node;
+            tiles.push(position);
+        }
+        if !tiles.is_empty() {
+            //the grid is part of the graph of every skill
+            self.pending.push(DirtyRegion {
+                difficulty: Difficulty::Green,
+                tiles,
+            });
+        }
+    }
+}
+/// Brings the navigation graph up to date with the graph layers in the world. Only layers that
+/// were added or removed are copied, along with grid edits from `edit_grid`, and cached paths
+/// they affect are dropped.
+pub fn update_navigation_graph(world: &mut World) {
+    let current: HashSet<Entity> = <(Entity, &GraphLayer)>::query()
+        .iter(world)
+        .map(|(entity, _)| *entity)
+        .collect();
+    let (known, edited) = match <&NavigationGraph>::query().iter(world).next() {
+        Some(graph) => (
+            graph.entities.iter().copied().collect::<HashSet<Entity>>(),
+            !graph.pending.is_empty(),
+        ),
+        None => {
+            if current.is_empty() {
+                return;
+            }
+            let (entities, layers): (Vec<Entity>, Vec<GraphLayer>) =
+                <(Entity, &GraphLayer)>::query()
+                    .iter(world)
+                    .map(|(entity, layer)| (*entity, layer.clone()))
+                    .unzip();
+            world.push((NavigationGraph {
+                version: 0,
+                entities,
+                layers,
+                change: GraphChange {
+                    full: true,
+                    ..Default::default()
+                },
+                pending: vec![],
+                cache: PathCache::default(),
+            },));
+            return;
+        }
+    };
+    if current == known && !edited {
+        return;
+    }
+    let added: Vec<(Entity, GraphLayer)> = <(Entity, &GraphLayer)>::query()
+        .iter(world)
+        .filter(|(entity, _)| !known.contains(entity))
+        .map(|(entity, layer)| (*entity, layer.clone()))
+        .collect();
+    let graph = <&mut NavigationGraph>::query()
+        .iter_mut(world)
+        .next()
+        .unwrap();
+    let mut change = GraphChange {
+        regions: std::mem::take(&mut graph.pending),
+        ..Default::default()
+    };
+    let entities = std::mem::take(&mut graph.entities);
+    let layers = std::mem::take(&mut graph.layers);
+    for (entity, layer) in entities.into_iter().zip(layers) {
+        if current.contains(&entity) {
+            graph.entities.push(entity);
+            graph.layers.push(layer);
+        } else {
+            change.add_layer(&layer, false);
+        }
+    }
+    for (entity, layer) in added.into_iter() {
+        change.add_layer(&layer, true);
+        graph.entities.push(entity);
+        graph.layers.push(layer);
+    }
+    graph.cache.invalidate(&change);
+    graph.change = change;
+    graph.version += 1;
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{push_terrain, Terrain};
+    use crate::trail::push_trail;
+    fn path(tiles: &[(i64, i64)]) -> Result<Path, PathError> {
+        Ok(Path::new(
+            tiles
+                .iter()
+                .map(|(x, y)| {
+                    (
+                        Node {
+                            node: Vector2::new(*x, *y),
+                        },
+                        super::super::GraphWeight::Some(1),
+                    )
+                })
+                .collect(),
+        ))
+    }
+    fn node(x: i64, y: i64) -> Node {
+        Node {
+            node: Vector2::new(x, y),
+        }
+    }
+    #[test]
+    fn least_recently_used() {
+        let cache = PathCache::new(2);
+        cache.insert(
+            &node(0, 0),
+            &node(1, 0),
+            Difficulty::Green,
+            path(&[(0, 0), (1, 0)]),
+        );
+        cache.insert(
+            &node(0, 0),
+            &node(2, 0),
+            Difficulty::Green,
+            Err(PathError::Unreachable),
+        );
+        assert_eq!(
+            cache.get(&node(0, 0), &node(2, 0), Difficulty::Green),
+            Some(Err(PathError::Unreachable))
+        );
+        assert!(cache
+            .get(&node(0, 0), &node(1, 0), Difficulty::Blue)
+            .is_none());
+        //(0,0) to (1,0) is the least recently used
+        cache.insert(
+            &node(0, 0),
+            &node(3, 0),
+            Difficulty::Green,
+            Err(PathError::Unreachable),
+        );
+        assert_eq!(cache.len(), 2);
+        assert!(cache
+            .get(&node(0, 0), &node(1, 0), Difficulty::Green)
+            .is_none());
+        assert!(cache
+            .get(&node(0, 0), &node(2, 0), Difficulty::Green)
+            .is_some());
+    }
+    #[test]
+    fn invalidated_by_trails() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -1.0),
+            &mut world,
+        );
+        update_navigation_graph(&mut world);
+        let version = |world: &World| {
+            <&NavigationGraph>::query()
+                .iter(world)
+                .next()
+                .unwrap()
+                .version()
+        };
+        assert_eq!(version(&world), 0);
+        //nothing changed
+        update_navigation_graph(&mut world);
+        assert_eq!(version(&world), 0);
+        let trail = push_trail(
+            &mut world,
+            "Run 1".to_string(),
+            Difficulty::Blue,
+            &[Vector2::new(0, 0), Vector2::new(0, 1)],
+        );
+        {
+            let graph = <&NavigationGraph>::query().iter(&world).next().unwrap();
+            let cache = graph.path_cache();
+            cache.insert(
+                &node(0, 0),
+                &node(0, 2),
+                Difficulty::Green,
+                path(&[(0, 0), (0, 1), (0, 2)]),
+            );
+            cache.insert(
+                &node(0, 0),
+                &node(0, 2),
+                Difficulty::Blue,
+                path(&[(0, 0), (0, 1), (0, 2)]),
+            );
+            cache.insert(
+                &node(5, 5),
+                &node(6, 6),
+                Difficulty::Black,
+                path(&[(5, 5), (6, 6)]),
+            );
+        }
+        update_navigation_graph(&mut world);
+        {
+            let graph = <&NavigationGraph>::query().iter(&world).next().unwrap();
+            assert_eq!(graph.version(), 1);
+            assert_eq!(graph.last_change().regions.len(), 1);
+            //a new trail can shorten any path of skiiers that can ski it
+            assert_eq!(graph.path_cache().len(), 1);
+            let cache = graph.path_cache();
+            cache.insert(
+                &node(0, 0),
+                &node(0, 2),
+                Difficulty::Blue,
+                path(&[(0, 0), (0, 1), (0, 2)]),
+            );
+            cache.insert(
+                &node(5, 5),
+                &node(6, 6),
+                Difficulty::Black,
+                path(&[(5, 5), (6, 6)]),
+            );
+        }
+        world.remove(trail);
+        update_navigation_graph(&mut world);
+        let graph = <&NavigationGraph>::query().iter(&world).next().unwrap();
+        assert_eq!(graph.version(), 2);
+        //removing the trail drops every path of skiiers that could ski it, crossing it or not
+        let cache = graph.path_cache();
+        assert!(cache
+            .get(&node(0, 0), &node(0, 2), Difficulty::Blue)
+            .is_none());
+        assert!(cache
+            .get(&node(5, 5), &node(6, 6), Difficulty::Black)
+            .is_none());
+        assert!(cache
+            .get(&node(0, 0), &node(0, 2), Difficulty::Green)
+            .is_some());
+    }
+}
@@ -0,0 +1,153 @@
+use nalgebra::Vector3;
+/// Number of straight pieces used to measure the length of each segment of a spline
+pub(super) const SAMPLES: usize = 8;
+/// Longest segment that is smoothed, longer segments are straight
+const MAX_CURVED_LENGTH: f32 = 2.0;
+/// Exponent of the distance between points used to space the knots. 0.5 is centripetal
+/// Catmull-Rom which does not form loops or overshoot when points are unevenly spaced, as they
+/// are on lifts.
+const ALPHA: f32 = 0.5;
+/// Point past the end of the spline, continuing in the direction of the last segment
+fn extrapolate(end: Vector3<f32>, before: Vector3<f32>) -> Vector3<f32> {
+    end * 2.0 - before
+}
+fn knot(from: Vector3<f32>, to: Vector3<f32>) -> f32 {
+    (to - from).norm().powf(ALPHA)
+}
+fn lerp(a: Vector3<f32>, b: Vector3<f32>, t0: f32, t1: f32, t: f32) -> Vector3<f32> {
+    a * ((t1 - t) / (t1 - t0)) + b * ((t - t0) / (t1 - t0))
+}
+/// Whether a segment is short enough to curve. Long segments such as lifts stay straight and
+/// corners at their ends stay sharp so they do not bow out around the corner.
+fn curves(from: Vector3<f32>, to: Vector3<f32>) -> bool {
+    let length = (to - from).norm();
+    length > f32::EPSILON && length <= MAX_CURVED_LENGTH
+}
+/// Control points of the segment from `nodes[segment]` to `nodes[segment + 1]`. Neighbors that
+/// are missing, on top of the segment's ends or across a straight segment are replaced by
+/// extending the segment.
+fn controls(nodes: &[Vector3<f32>], segment: usize) -> [Vector3<f32>; 4] {
+    let p1 = nodes[segment];
+    let p2 = nodes[segment + 1];
+    let curved = curves(p1, p2);
+    let p0 = match segment.checked_sub(1).map(|i| nodes[i]) {
+        Some(p) if curved && curves(p, p1) => p,
+        _ => extrapolate(p1, p2),
+    };
+    let p3 = match nodes.get(segment + 2) {
+        Some(p) if curved && curves(p2, *p) => *p,
+        _ => extrapolate(p2, p1),
+    };
+    [p0, p1, p2, p3]
+}
+/// Point on a centripetal Catmull-Rom spline through `nodes`, `u` goes from 0 at
+/// `nodes[segment]` to 1 at `nodes[segment + 1]`
+pub(super) fn point(nodes: &[Vector3<f32>], segment: usize, u: f32) -> Vector3<f32> {
+    let [p0, p1, p2, p3] = controls(nodes, segment);
+    if (p2 - p1).norm() <= f32::EPSILON {
+        return p1;
+    }
+    let t0 = 0.0;
+    let t1 = t0 + knot(p0, p1);
+    let t2 = t1 + knot(p1, p2);
+    let t3 = t2 + knot(p2, p3);
+    let t = t1 + (t2 - t1) * u;
+    let a1 = lerp(p0, p1, t0, t1, t);
+    let a2 = lerp(p1, p2, t1, t2, t);
+    let a3 = lerp(p2, p3, t2, t3, t);
+    let b1 = lerp(a1, a2, t0, t2, t);
+    let b2 = lerp(a2, a3, t1, t3, t);
+    lerp(b1, b2, t1, t2, t)
+}
+/// Distance along each segment of the spline at each of its samples, starting at 0
+pub(super) fn arc_lengths(nodes: &[Vector3<f32>]) -> Vec<[f32; SAMPLES + 1]> {
+    (0..nodes.len().saturating_sub(1))
+        .map(|segment| {
+            let mut lengths = [0.0; SAMPLES + 1];
+            let mut last = nodes[segment];
+            for i in 1..=SAMPLES {
+                let next = point(nodes, segment, i as f32 / SAMPLES as f32);
+                lengths[i] = lengths[i - 1] + (next - last).norm();
+                last = next;
+            }
+            lengths
+        })
+        .collect()
+}
+/// Spline parameter of the point `fraction` of the way along a segment by distance
+pub(super) fn parameter(lengths: &[f32; SAMPLES + 1], fraction: f32) -> f32 {
+    let total = lengths[SAMPLES];
+    if total <= f32::EPSILON {
+        return fraction;
+    }
+    let distance = fraction * total;
+    for i in 1..=SAMPLES {
+        if distance <= lengths[i] {
+            let piece = lengths[i] - lengths[i - 1];
+            let within = if piece > f32::EPSILON {
+                (distance - lengths[i - 1]) / piece
+            } else {
+                0.0
+            };
+            return (i as f32 - 1.0 + within) / SAMPLES as f32;
+        }
+    }
+    1.0
+}
+/// Shortest distance from a point to a polyline
+#[cfg(test)]
+pub(super) fn distance_to(nodes: &[Vector3<f32>], p: Vector3<f32>) -> f32 {
+    nodes
+        .windows(2)
+        .map(|w| {
+            let d = w[1] - w[0];
+            let t = ((p - w[0]).dot(&d) / d.norm_squared()).clamp(0.0, 1.0);
+            (w[0] + d * t - p).norm()
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn stays_near_nodes() {
+        //a grid path with a turn, a diagonal and a long lift
+        let nodes = vec![
+            Vector3::new(0.0, 2.0, 0.0),
+            Vector3::new(1.0, 1.9, 0.0),
+            Vector3::new(2.0, 1.8, 0.0),
+            Vector3::new(3.0, 1.6, 1.0),
+            Vector3::new(3.0, 1.4, 2.0),
+            Vector3::new(3.0, 1.4, 2.0),
+            Vector3::new(3.0, 6.0, 20.0),
+            Vector3::new(4.0, 5.9, 20.0),
+        ];
+        for segment in 0..nodes.len() - 1 {
+            //passes through every node
+            assert!((point(&nodes, segment, 0.0) - nodes[segment]).norm() < 1e-4);
+            assert!((point(&nodes, segment, 1.0) - nodes[segment + 1]).norm() < 1e-4);
+            for i in 0..=20 {
+                let p = point(&nodes, segment, i as f32 / 20.0);
+                assert!(distance_to(&nodes, p) < 0.25, "{} off the path", p);
+            }
+        }
+    }
+    #[test]
+    fn arc_length() {
+        let nodes = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 0.0),
+        ];
+        let lengths = arc_lengths(&nodes);
+        assert_eq!(lengths.len(), 2);
+        //straight lines keep their length
+        assert!((lengths[0][SAMPLES] - 1.0).abs() < 1e-4);
+        assert!((lengths[1][SAMPLES] - 2.0).abs() < 1e-4);
+        //half way by distance is half way along the segment
+        let u = parameter(&lengths[1], 0.5);
+        assert!((point(&nodes, 1, u).x - 2.0).abs() < 0.05);
+        assert_eq!(parameter(&lengths[1], 0.0), 0.0);
+        assert!((parameter(&lengths[1], 1.0) - 1.0).abs() < 1e-4);
+    }
+}
@@ -6,13 +6,14 @@ mod webgl;
 #[cfg(not(target_arch = "wasm32"))]
 pub use gfx::*;
 pub use mesh::{ItemDesc, Mesh, Vertex};
-use nalgebra::{Matrix4, Vector3};
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
 #[cfg(target_arch = "wasm32")]
 pub use webgl::*;
 #[derive(Debug, Clone)]
 pub struct Transform {
     scaling: Vector3<f32>,
     translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
 }
 impl Transform {
     ///Adds a delta translation
@@ -22,14 +23,29 @@ impl Transform {
     pub fn set_translation(&mut self, translation: Vector3<f32>) {
         self.translation = translation;
     }
+    pub fn get_translation(&self) -> Vector3<f32> {
+        self.translation
+    }
     pub fn scale(&mut self, delta: Vector3<f32>) {
         self.scaling += delta;
     }
     pub fn set_scale(&mut self, scale: Vector3<f32>) {
         self.scaling = scale;
     }
+    /// Turns about the vertical axis so the model's +z axis points along `direction`
+    pub fn face(&mut self, direction: Vector3<f32>) {
+        if direction.x != 0.0 || direction.z != 0.0 {
+            self.rotation =
+                UnitQuaternion::from_axis_angle(&Vector3::y_axis(), direction.x.atan2(direction.z));
+        }
+    }
+    pub fn get_rotation(&self) -> UnitQuaternion<f32> {
+        self.rotation
+    }
     pub fn build(&self) -> Matrix4<f32> {
-        Matrix4::new_translation(&self.translation) * Matrix4::new_nonuniform_scaling(&self.scaling)
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scaling)
     }
 }
 impl Default for Transform {
@@ -37,6 +53,7 @@ impl Default for Transform {
         Self {
             scaling: Vector3::new(1.0, 1.0, 1.0),
             translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: UnitQuaternion::identity(),
         }
     }
 }
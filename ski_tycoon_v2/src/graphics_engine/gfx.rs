@@ -51,6 +51,7 @@ extern crate gfx_backend_vulkan as back;
 pub type RuntimeMesh = RuntimeGfxMesh<back::Backend>;
 pub type RuntimeTexture = RuntimeGfxTexture<back::Backend>;
 #[derive(Clone)]
+#[allow(dead_code)]
 pub struct RuntimeGfxTexture<B: gfx_hal::Backend> {
     image_buffer: B::Buffer,
     image_memory: B::Memory,
@@ -65,6 +66,7 @@ pub struct GfxFramebuffer<B: gfx_hal::Backend> {
 }
 pub type Framebuffer = GfxFramebuffer<back::Backend>;
 #[derive(Clone)]
+#[allow(dead_code)]
 pub struct RuntimeGfxMesh<B: gfx_hal::Backend> {
     vertex_buffer: B::Buffer,
     vertex_memory: B::Memory,
@@ -252,9 +254,8 @@ impl<B: gfx_hal::Backend> GfxRenderingContext<B> {
         let non_coherent_alignment = limits.non_coherent_atom_size as u64;
         let buffer_stride = mem::size_of::<Vertex>() as u64;
         let buffer_len = QUAD.len() as u64 * buffer_stride;
-        let padded_buffer_len = ((buffer_len + non_coherent_alignment - 1)
-            / non_coherent_alignment)
-            * non_coherent_alignment;
+        let padded_buffer_len =
+            buffer_len.div_ceil(non_coherent_alignment) * non_coherent_alignment;
         let mut vertex_buffer = ManuallyDrop::new(
             unsafe { device.create_buffer(padded_buffer_len, buffer::Usage::VERTEX) }.unwrap(),
         );
@@ -307,9 +308,8 @@ impl<B: gfx_hal::Backend> GfxRenderingContext<B> {
         let image_stride = 4usize;
         let row_pitch = (width * image_stride as u32 + row_alignment_mask) & !row_alignment_mask;
         let upload_size = (height * row_pitch) as u64;
-        let padded_upload_size = ((upload_size + non_coherent_alignment - 1)
-            / non_coherent_alignment)
-            * non_coherent_alignment;
+        let padded_upload_size =
+            upload_size.div_ceil(non_coherent_alignment) * non_coherent_alignment;
 
         let mut image_upload_buffer = ManuallyDrop::new(
             unsafe { device.create_buffer(padded_upload_size, buffer::Usage::TRANSFER_SRC) }
@@ -1198,14 +1198,11 @@ impl<B: gfx_hal::Backend> GfxRenderingContext<B> {
     /// Checks all preconditions,
     /// currently the only precondition is that the fence must always be unsignaled
     pub fn get_error(&mut self) {
-        assert_eq!(
-            unsafe {
-                self.device
-                    .get_fence_status(&self.submission_complete_fence)
-            }
-            .expect("failed to get fence status"),
-            false
-        );
+        assert!(!unsafe {
+            self.device
+                .get_fence_status(&self.submission_complete_fence)
+        }
+        .expect("failed to get fence status"));
         //nothing to do here
     }
 }
@@ -29,7 +29,7 @@ impl<T> CircularBuffer<T> {
 mod test {
     use super::*;
     #[test]
-    fn CircularBuffer() {
+    fn circular_buffer() {
         let mut buff = CircularBuffer::new(vec![0usize, 1usize, 2usize]);
         for i in 0..3 {
             assert_eq!(buff.get_mut(), &i);
@@ -24,6 +24,7 @@ impl UniformDataType {
     }
 }
 #[derive(Clone)]
+#[allow(dead_code)]
 pub struct UniformData {
     pub layout_binding: DescriptorSetLayoutBinding,
     pub data_type: UniformDataType,
@@ -37,8 +38,7 @@ pub struct ShaderData {
 #[allow(dead_code)]
 fn get_vec(data: &'static [u8]) -> Vec<u32> {
     assert_eq!(data.len() % 4, 0);
-    let mut out = vec![];
-    out.reserve(data.len() % 4);
+    let mut out = Vec::with_capacity(data.len() % 4);
     for i in 0..data.len() / 4 {
         let bytes: Vec<u8> = (0..4).map(|j| data[i * 4 + j]).collect();
         out.push(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
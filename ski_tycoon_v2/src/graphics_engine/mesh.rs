@@ -64,7 +64,7 @@ impl Mesh {
         let vertices: Vec<f32> = loaded_obj
             .indices
             .iter()
-            .map(|i| {
+            .flat_map(|i| {
                 vec![
                     loaded_obj.positions[*i as usize * 3],
                     loaded_obj.positions[*i as usize * 3 + 1],
@@ -76,7 +76,6 @@ impl Mesh {
                     loaded_obj.normals[*i as usize + 2],
                 ]
             })
-            .flatten()
             .collect();
         let description = ItemDesc::default_model();
         Self {
@@ -1,10 +1,19 @@
+use super::building::{building_model, building_model_id, BuildingKind};
+use super::lift::lift_model;
 use super::prelude::{
     AssetManager, DeltaCamera, ErrorType, GuiRuntimeModel, GuiTransform, Mesh, Model,
     RenderingContext, RuntimeMesh, RuntimeTexture, Shader, ShaderBind, Terrain, Transform,
 };
+use super::skiier::skiier_model;
+use super::snowmaking::snow_gun_model;
+use super::staff::{groomer_model, patroller_model};
+use super::terrain::Difficulty;
+use super::trail::{marker_model, sign_model, sign_model_id};
+use super::weather::{fog_model, snowflake_model};
 use legion::*;
 use log::debug;
 use nalgebra::Vector2;
+use std::collections::HashSet;
 pub struct RuntimeModel {
     pub mesh: RuntimeMesh,
     pub texture: RuntimeTexture,
@@ -46,19 +55,11 @@ impl RuntimeDebugMesh {
         Ok(Self { mesh })
     }
 }
-pub fn insert_terrain(
-    terrain: Terrain,
-    world: &mut World,
-    graphics: &mut RenderingContext,
-    asset_manager: &mut AssetManager<RuntimeModel>,
-    bound_shader: &Shader,
-) -> Result<(), ErrorType> {
-    let model = terrain.model();
-    let transform = model.transform.clone();
-    asset_manager.overwrite(
-        "game_terrain",
-        RuntimeModel::new(&model, graphics, bound_shader).expect("created model"),
-    );
+/// Marks that the terrain model needs to be rebuilt from the terrain
+pub struct TerrainModelOutdated {}
+/// Pushes terrain and its graph into the world. The terrain's model is built by `load_models`
+pub fn push_terrain(terrain: Terrain, world: &mut World) {
+    let transform = Transform::default();
     world.push((
         terrain.build_graph(),
         terrain,
@@ -66,7 +67,58 @@ pub fn insert_terrain(
         RuntimeModelId {
             id: "game_terrain".to_string(),
         },
+        TerrainModelOutdated {},
     ));
+}
+/// Builds models used by entities in the world that have not been loaded yet and rebuilds the
+/// terrain model if the terrain changed.
+pub fn load_models(
+    world: &mut World,
+    graphics: &mut RenderingContext,
+    asset_manager: &mut AssetManager<RuntimeModel>,
+    bound_shader: &Shader,
+) -> Result<(), ErrorType> {
+    let outdated: Vec<(Entity, Model)> = <(Entity, &Terrain, &TerrainModelOutdated)>::query()
+        .iter(world)
+        .map(|(entity, terrain, _)| (*entity, terrain.model()))
+        .collect();
+    for (entity, model) in outdated.iter() {
+        asset_manager.overwrite(
+            "game_terrain",
+            RuntimeModel::new(model, graphics, bound_shader)?,
+        );
+        if let Some(mut entry) = world.entry(*entity) {
+            entry.remove_component::<TerrainModelOutdated>();
+        }
+    }
+    let missing: HashSet<String> = <&RuntimeModelId>::query()
+        .iter(world)
+        .filter(|id| !asset_manager.contains(&id.id))
+        .map(|id| id.id.clone())
+        .collect();
+    for id in missing.iter() {
+        let model = match id.as_str() {
+            "skiier" => skiier_model(),
+            "lift" => lift_model(),
+            "patroller" => patroller_model(),
+            "groomer" => groomer_model(),
+            "snowflake" => snowflake_model(),
+            "fog" => fog_model(),
+            "snow_gun" => snow_gun_model(),
+            "trail_marker" => marker_model(),
+            _ => match Difficulty::ALL.iter().find(|d| &sign_model_id(**d) == id) {
+                Some(difficulty) => sign_model(*difficulty),
+                None => match BuildingKind::ALL
+                    .iter()
+                    .find(|k| &building_model_id(**k) == id)
+                {
+                    Some(kind) => building_model(*kind),
+                    None => panic!("no model named {}", id),
+                },
+            },
+        };
+        asset_manager.get_or_create(id, RuntimeModel::new(&model, graphics, bound_shader)?);
+    }
     Ok(())
 }
 
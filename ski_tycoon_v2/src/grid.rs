@@ -45,6 +45,13 @@ impl<T> std::ops::Index<Vector2<usize>> for Grid<T> {
         &self.data[i]
     }
 }
+impl<T> std::ops::IndexMut<Vector2<usize>> for Grid<T> {
+    fn index_mut(&mut self, index: Vector2<usize>) -> &mut Self::Output {
+        let i = index.x * self.dimensions.y + index.y;
+        assert!(i < self.data.len());
+        &mut self.data[i]
+    }
+}
 #[cfg(test)]
 mod test {
     use super::*;
@@ -45,11 +45,6 @@ impl GuiModel {
         Ok(world.push((transform, GuiRuntimeModel { model })))
     }
 }
-trait App: std::fmt::Debug {}
-#[derive(Debug, Clone)]
-struct Test<T: Clone> {
-    t: T,
-}
 #[allow(unused_must_use)]
 pub fn init_gui(screen_size: Vector2<u32>) -> (CtxRef, EguiRawInputAdaptor) {
     let mut ctx = CtxRef::default();
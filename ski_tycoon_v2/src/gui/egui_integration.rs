@@ -121,7 +121,7 @@ fn to_vertex(vertex_list: &Vec<EguiVertex>, depth: f32, screen_size: &Vector2<u3
     for vertex in vertex_list.iter() {
         let position = Vector3::new(
             vertex.pos.x / screen_x - 1.0,
-            -1.0 * vertex.pos.y / screen_y + 1.0,
+            -vertex.pos.y / screen_y + 1.0,
             depth,
         );
         let color: egui::paint::Rgba = vertex.color.into();
@@ -1,6 +1,11 @@
 mod asset_manager;
 mod bindable;
+mod building;
 mod camera;
+mod clock;
+mod commands;
+mod congestion;
+mod finances;
 mod graph;
 mod graphics_engine;
 mod graphics_system;
@@ -8,16 +13,28 @@ mod grid;
 mod gui;
 mod lift;
 mod model;
+mod objectives;
+mod replay;
+mod rng;
+mod simulation;
+mod ski_school;
 mod skiier;
+mod snow;
+mod snowmaking;
+mod staff;
+mod steering;
 mod terrain;
 mod texture;
+mod trail;
 mod utils;
+mod weather;
+mod workers;
 #[cfg(not(target_arch = "wasm32"))]
 pub use graphics_engine::Window;
 use graphics_engine::{
     ErrorType, Framebuffer, InitContext, Mesh, RenderingContext, RuntimeDepthTexture, Transform,
 };
-use log::{debug, info};
+use log::{debug, error, info};
 use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
 use texture::RGBATexture;
 mod events;
@@ -33,11 +50,15 @@ use legion::*;
 use wasm_bindgen::prelude::*;
 pub mod prelude {
     pub use super::asset_manager::AssetManager;
+    pub use super::building::{push_building, Building, BuildingKind, Need};
     pub use super::camera::DeltaCamera;
     pub use super::events::{Event, MouseButton};
     pub use super::graph::{
-        a_star, dijkstra, FollowPath, GraphLayer, GraphLayerList, GraphWeight, GridNode, LiftLayer,
-        Node, NodeFloat, Path,
+        a_star, dijkstra, euclidean_heuristic, octile_heuristic, smooth_path, theta_star,
+        update_flow_fields, update_navigation_graph, update_path_hierarchies, DirtyRegion,
+        FlowField, FlowFields, FollowPath, GraphChange, GraphLayer, GraphLayerList, GraphWeight,
+        GridNode, LiftLayer, NavigationGraph, Node, NodeFloat, Path, PathCache, PathError,
+        PathHierarchies, PathHierarchy, TrailLayer,
     };
     pub use super::graphics_engine::{
         ErrorType, Framebuffer, ItemDesc, Mesh, RenderingContext, RuntimeMesh, RuntimeTexture,
@@ -45,15 +66,16 @@ pub mod prelude {
     };
     pub type ShaderBind = super::Bindable<Shader>;
     pub use super::graphics_system::{
-        insert_terrain, RuntimeDebugMesh, RuntimeModel, RuntimeModelId,
+        push_terrain, RuntimeDebugMesh, RuntimeModel, RuntimeModelId,
     };
     pub use super::grid::Grid;
     pub use super::gui::{GuiModel, GuiRuntimeModel, GuiTransform};
-    pub use super::lift::insert_lift;
+    pub use super::lift::push_lift;
     pub use super::model::Model;
-    pub use super::skiier::build_skiier;
+    pub use super::skiier::spawn_skiier;
     pub use super::terrain::Terrain;
     pub use super::texture::RGBATexture as Texture;
+    pub use super::workers::WorkerPool;
     pub use wasm_bindgen::prelude::JsValue;
 }
 use prelude::ShaderBind;
@@ -63,6 +85,7 @@ pub struct Game {
     world_depth_texture: RuntimeDepthTexture,
     world_framebuffer: Framebuffer,
     world_render_surface: RuntimeModel,
+    systems: simulation::Systems,
 }
 impl Game {
     pub fn new(screen_size: Vector2<u32>, init_context: InitContext) -> Result<Game, ErrorType> {
@@ -103,16 +126,16 @@ impl Game {
         GuiModel::simple_box(box_transform).insert(
             &mut world,
             &mut webgl,
-            &shader_bind.get_bind(),
+            shader_bind.get_bind(),
         )?;
         webgl.get_error();
         let mut world_framebuffer_texture = webgl.build_texture(
             RGBATexture::constant_color(Vector4::new(0, 0, 0, 0), screen_size),
-            &shader_bind.get_bind(),
+            shader_bind.get_bind(),
         )?;
         let mut world_depth_texture =
-            webgl.build_depth_texture(screen_size, &shader_bind.get_bind())?;
-        let fb_mesh = webgl.build_mesh(Mesh::plane(), &shader_bind.get_bind())?;
+            webgl.build_depth_texture(screen_size, shader_bind.get_bind())?;
+        let fb_mesh = webgl.build_mesh(Mesh::plane(), shader_bind.get_bind())?;
         let world_framebuffer =
             webgl.build_framebuffer(&mut world_framebuffer_texture, &mut world_depth_texture)?;
         let world_render_surface = RuntimeModel {
@@ -135,7 +158,13 @@ impl Game {
         resources.insert(egui_adaptor);
         resources.insert(model_manager);
         resources.insert(lift::BuildLift::default());
-        resources.insert(terrain::TerrainLibrary::default());
+        resources.insert(trail::PaintTrail::default());
+        resources.insert(building::PlaceBuilding::default());
+        resources.insert(staff::StaffRoster::default());
+        resources.insert(load_terrain_library());
+        resources.insert(rng::SeededRng::from_time());
+        resources.insert(replay::ReplayRecorder::default());
+        resources.insert(commands::CommandHistory::default());
         // gui::insert_ui(&mut egui_context);
         let g = Game {
             world,
@@ -143,10 +172,18 @@ impl Game {
             world_depth_texture,
             world_framebuffer,
             world_render_surface,
+            systems: simulation::Systems::new(
+                workers::WorkerPool::default(),
+                load_behavior_library(),
+            ),
         };
         Ok(g)
     }
     pub fn run_frame(&mut self, events: Vec<Event>) {
+        let events = {
+            let recorder: &replay::ReplayRecorder = &self.resources.get().unwrap();
+            recorder.frame_events(events)
+        };
         {
             let camera: &mut DeltaCamera = &mut self.resources.get_mut().unwrap();
             for e in events.iter() {
@@ -157,11 +194,9 @@ impl Game {
                         delta_time_ms,
                         buttons_pressed,
                         ..
-                    } => {
-                        if buttons_pressed.contains(&MouseButton::RightClick) {
-                            camera.rotate_phi(delta_x * 0.001 * delta_time_ms);
-                            camera.rotate_theta(delta_y * 0.001 * delta_time_ms);
-                        }
+                    } if buttons_pressed.contains(&MouseButton::RightClick) => {
+                        camera.rotate_phi(delta_x * 0.001 * delta_time_ms);
+                        camera.rotate_theta(delta_y * 0.001 * delta_time_ms);
                     }
                     Event::ScreenSizeChange { new_size } => {
                         let shader: &mut ShaderBind = &mut self.resources.get_mut().unwrap();
@@ -181,15 +216,15 @@ impl Game {
                         let mut world_framebuffer_texture = gl
                             .build_texture(
                                 RGBATexture::constant_color(Vector4::new(0, 0, 0, 0), *new_size),
-                                &shader.get_bind(),
+                                shader.get_bind(),
                             )
                             .expect("failed to build new texture");
                         let mut world_depth_texture = gl
-                            .build_depth_texture(*new_size, &shader.get_bind())
+                            .build_depth_texture(*new_size, shader.get_bind())
                             .expect("failed to build depth texture");
                         shader.bind("world");
                         let fb_mesh = gl
-                            .build_mesh(Mesh::plane(), &shader.get_bind())
+                            .build_mesh(Mesh::plane(), shader.get_bind())
                             .expect("failed to build mesh");
 
                         shader.bind("screen");
@@ -223,34 +258,120 @@ impl Game {
 
             let gl: &mut RenderingContext = &mut self.resources.get_mut().unwrap();
             gl.bind_framebuffer(&self.world_framebuffer);
-            gl.clear_screen(Vector4::new(0.2, 0.2, 0.2, 1.0));
+            gl.clear_screen(weather::sky_color(&self.world));
 
             let shader: &mut ShaderBind = &mut self.resources.get_mut().unwrap();
             shader.bind("world");
             gl.bind_shader(shader.get_bind()).ok().unwrap();
+            gl.send_vec4_uniform(
+                &mut shader["world"],
+                "sun_color",
+                weather::sun_color(&self.world),
+            )
+            .ok()
+            .unwrap();
         }
         info!("handled sceen resize");
+        let mut commands: Vec<commands::PlayerCommand> = events
+            .iter()
+            .filter_map(|e| match e {
+                Event::Undo => Some(commands::PlayerCommand::Undo),
+                Event::Redo => Some(commands::PlayerCommand::Redo),
+                _ => None,
+            })
+            .collect();
+        {
+            let history: &commands::CommandHistory = &self.resources.get().unwrap();
+            if let Some(command) = history.draw_gui(&mut self.resources.get_mut().unwrap()) {
+                commands.push(command);
+            }
+        }
         {
             let library: &terrain::TerrainLibrary = &self.resources.get().unwrap();
-            library.draw_gui(
-                &mut self.world,
-                &mut self.resources.get_mut().unwrap(),
+            if let Some(command) = library.draw_gui(
                 &mut self.resources.get_mut().unwrap(),
                 &mut self.resources.get_mut().unwrap(),
-                &self.resources.get().unwrap(),
-            );
+            ) {
+                commands.push(command);
+            }
         }
-        //game logic
-        skiier::follow_path(&mut self.world);
         {
             let lift: &mut lift::BuildLift = &mut self.resources.get_mut().unwrap();
-            lift.build_lift(
+            if let Some(command) =
+                lift.build_lift(&mut self.world, &mut self.resources.get_mut().unwrap())
+            {
+                commands.push(command);
+            }
+        }
+        {
+            let paint: &mut trail::PaintTrail = &mut self.resources.get_mut().unwrap();
+            if let Some(command) =
+                paint.draw_gui(&mut self.world, &mut self.resources.get_mut().unwrap())
+            {
+                commands.push(command);
+            }
+        }
+        {
+            let place: &mut building::PlaceBuilding = &mut self.resources.get_mut().unwrap();
+            if let Some(command) =
+                place.draw_gui(&mut self.world, &mut self.resources.get_mut().unwrap())
+            {
+                commands.push(command);
+            }
+        }
+        if let Some(command) =
+            congestion::draw_gui(&self.world, &mut self.resources.get_mut().unwrap())
+        {
+            commands.push(command);
+        }
+        if let Some(command) =
+            ski_school::draw_gui(&self.world, &mut self.resources.get_mut().unwrap())
+        {
+            commands.push(command);
+        }
+        snow::draw_gui(&self.world, &mut self.resources.get_mut().unwrap());
+        if let Some(command) =
+            snowmaking::draw_gui(&self.world, &mut self.resources.get_mut().unwrap())
+        {
+            commands.push(command);
+        }
+        weather::draw_gui(&self.world, &mut self.resources.get_mut().unwrap());
+        {
+            let roster: &mut staff::StaffRoster = &mut self.resources.get_mut().unwrap();
+            if let Some(command) =
+                roster.draw_gui(&self.world, &mut self.resources.get_mut().unwrap())
+            {
+                commands.push(command);
+            }
+        }
+        let commands = {
+            let recorder: &mut replay::ReplayRecorder = &mut self.resources.get_mut().unwrap();
+            recorder.draw_gui(&mut self.resources.get_mut().unwrap(), &self.world);
+            recorder.frame_commands(&events, commands)
+        };
+        //game logic
+        simulation::step(
+            &mut self.world,
+            &self.resources.get().unwrap(),
+            &mut self.resources.get_mut().unwrap(),
+            &mut self.resources.get_mut().unwrap(),
+            &mut self.systems,
+            &commands,
+        );
+        {
+            let recorder: &mut replay::ReplayRecorder = &mut self.resources.get_mut().unwrap();
+            recorder.verify(&self.world);
+        }
+        weather::update_particles(&mut self.world);
+        {
+            let shader: &ShaderBind = &self.resources.get().unwrap();
+            graphics_system::load_models(
                 &mut self.world,
                 &mut self.resources.get_mut().unwrap(),
                 &mut self.resources.get_mut().unwrap(),
-                &mut self.resources.get_mut().unwrap(),
-                &self.resources.get().unwrap(),
-            );
+                shader.get_bind(),
+            )
+            .expect("failed to load models");
         }
         //rendering susten
         let mut schedule = Schedule::builder()
@@ -260,6 +381,8 @@ impl Game {
             let ctx: &mut egui::CtxRef = &mut self.resources.get_mut().unwrap();
             graph_debug::terrain_debug_window(&self.world, ctx);
             skiier::draw_skiiers(&self.world, ctx);
+            skiier::draw_progression(&self.world, ctx);
+            objectives::draw_objectives(&mut self.world, ctx);
         }
         schedule.execute(&mut self.world, &mut self.resources);
         {
@@ -315,6 +438,77 @@ impl Game {
         info!("rendered gui");
     }
 }
+/// Directory named `name` next to the executable, or the directory in the environment variable
+/// `variable` if it is set
+#[cfg(not(target_arch = "wasm32"))]
+fn data_directory(variable: &str, name: &str) -> Option<std::path::PathBuf> {
+    std::env::var_os(variable)
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|directory| directory.join(name)))
+        })
+}
+/// Loads the valid scenarios in the scenario directory, reporting each file that is invalid.
+/// Falls back to the builtin scenarios if there is no directory or none of its scenarios load.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_terrain_library() -> terrain::TerrainLibrary {
+    let directory = match data_directory(
+        terrain::SCENARIO_DIRECTORY_VARIABLE,
+        terrain::SCENARIO_DIRECTORY,
+    ) {
+        Some(directory) if directory.is_dir() => directory,
+        _ => {
+            info!("no scenario directory, using builtin scenarios");
+            return terrain::TerrainLibrary::default();
+        }
+    };
+    let (library, errors) = terrain::TerrainLibrary::from_directory(&directory);
+    for e in errors.iter() {
+        error!("{}", e);
+    }
+    if library.is_empty() {
+        terrain::TerrainLibrary::default()
+    } else {
+        library
+    }
+}
+#[cfg(target_arch = "wasm32")]
+fn load_terrain_library() -> terrain::TerrainLibrary {
+    terrain::TerrainLibrary::default()
+}
+/// Loads the valid skiier behavior trees in the behavior directory, reporting the errors in each
+/// invalid file. Falls back to the builtin trees if there is no directory or no default tree.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_behavior_library() -> skiier::BehaviorLibrary {
+    let directory = match data_directory(
+        skiier::BEHAVIOR_DIRECTORY_VARIABLE,
+        skiier::BEHAVIOR_DIRECTORY,
+    ) {
+        Some(directory) if directory.is_dir() => directory,
+        _ => {
+            info!("no behavior directory, using builtin behavior trees");
+            return skiier::BehaviorLibrary::default();
+        }
+    };
+    let (library, errors) = skiier::BehaviorLibrary::from_directory(&directory);
+    for e in errors.iter() {
+        error!("{}", e);
+    }
+    if library
+        .tree(skiier::BehaviorLibrary::DEFAULT_TREE)
+        .is_none()
+    {
+        skiier::BehaviorLibrary::default()
+    } else {
+        library
+    }
+}
+#[cfg(target_arch = "wasm32")]
+fn load_behavior_library() -> skiier::BehaviorLibrary {
+    skiier::BehaviorLibrary::default()
+}
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub struct WebGame {
@@ -1,20 +1,26 @@
+use super::commands::PlayerCommand;
 use super::prelude::{
-    AssetManager, ErrorType, GraphLayer, GraphWeight, LiftLayer, Model, Node, RenderingContext,
-    RuntimeModel, RuntimeModelId, ShaderBind, Terrain, Texture, Transform,
+    GraphLayer, GraphWeight, LiftLayer, Model, Node, RuntimeModelId, Terrain, Texture, Transform,
 };
 const SKI_LIFT: &[u8] = include_bytes!["../../assets/obj/skilift.obj"];
 use egui::CtxRef;
 use legion::*;
 use nalgebra::{Vector2, Vector3, Vector4};
-pub fn insert_lift(
+/// Model used for both ends of a lift
+pub fn lift_model() -> Model {
+    Model::from_obj(
+        SKI_LIFT,
+        SKI_LIFT,
+        Transform::default(),
+        Texture::constant_color(Vector4::new(255, 255, 0, 255), Vector2::new(10, 10)),
+    )
+}
+/// Adds a lift to the world, returning the entities of its two ends
+pub fn push_lift(
     world: &mut World,
-    graphics: &mut RenderingContext,
-    asset_manager: &mut AssetManager<RuntimeModel>,
-
-    bound_shader: &ShaderBind,
     start_position: Vector2<i64>,
     end_position: Vector2<i64>,
-) -> Result<(), ErrorType> {
+) -> [Entity; 2] {
     let mut transform = Transform::default();
     let mut end_transform = Transform::default();
     {
@@ -24,24 +30,12 @@ pub fn insert_lift(
         transform.set_translation(terrain.get_transform(&start_position).unwrap());
         end_transform.set_translation(terrain.get_transform(&end_position).unwrap());
     }
-    if !asset_manager.contains("lift") {
-        let model = Model::from_obj(
-            SKI_LIFT,
-            SKI_LIFT,
-            transform.clone(),
-            Texture::constant_color(Vector4::new(255, 255, 0, 255), Vector2::new(10, 10)),
-        );
-        asset_manager.get_or_create(
-            "lift",
-            RuntimeModel::new(&model, graphics, bound_shader.get_bind())?,
-        );
-    }
     let runtime_model = RuntimeModelId::new("lift".to_string());
     let start = Node {
         node: start_position,
     };
     let end = Node { node: end_position };
-    world.push((
+    let start_entity = world.push((
         transform,
         runtime_model.clone(),
         GraphLayer::Lift(LiftLayer {
@@ -50,8 +44,8 @@ pub fn insert_lift(
             weight: GraphWeight::Some(1),
         }),
     ));
-    world.push((end_transform, runtime_model));
-    Ok(())
+    let end_entity = world.push((end_transform, runtime_model));
+    [start_entity, end_entity]
 }
 
 /// Labels a lift as in progress
@@ -77,27 +71,6 @@ enum LiftStage {
     SecondLift,
     Done,
 }
-fn get_skilift(
-    graphics: &mut RenderingContext,
-    asset_manager: &mut AssetManager<RuntimeModel>,
-    bound_shader: &ShaderBind,
-    transform: &Transform,
-) -> RuntimeModelId {
-    if !asset_manager.contains("lift") {
-        let model = Model::from_obj(
-            SKI_LIFT,
-            SKI_LIFT,
-            transform.clone(),
-            Texture::constant_color(Vector4::new(255, 255, 0, 255), Vector2::new(10, 10)),
-        );
-        asset_manager.get_or_create(
-            "lift",
-            RuntimeModel::new(&model, graphics, bound_shader.get_bind())
-                .expect("failed to build run time model"),
-        );
-    }
-    RuntimeModelId::new("lift".to_string())
-}
 #[derive(Clone, Debug, PartialEq)]
 pub struct BuildLift {
     placing_lift: LiftStage,
@@ -105,14 +78,9 @@ pub struct BuildLift {
     end_lift: Option<Entity>,
 }
 impl BuildLift {
-    pub fn build_lift(
-        &mut self,
-        world: &mut World,
-        context: &mut CtxRef,
-        graphics: &mut RenderingContext,
-        asset_manager: &mut AssetManager<RuntimeModel>,
-        bound_shader: &ShaderBind,
-    ) {
+    /// Draws the lift placement window. Returns a command to build the lift once placement is
+    /// done
+    pub fn build_lift(&mut self, world: &mut World, context: &mut CtxRef) -> Option<PlayerCommand> {
         let previous_placing_lift = self.placing_lift.clone();
         let mut direction: Vector2<i64> = Vector2::new(0, 0);
         egui::Window::new("lift").show(context, |ui| {
@@ -150,7 +118,7 @@ impl BuildLift {
         });
         if previous_placing_lift == LiftStage::NoLift && self.placing_lift == LiftStage::FirstLift {
             let transform = Transform::default();
-            let runtime_model = get_skilift(graphics, asset_manager, bound_shader, &transform);
+            let runtime_model = RuntimeModelId::new("lift".to_string());
             self.start_lift =
                 Some(world.push((runtime_model, FirstLiftPlace::default(), transform)));
         }
@@ -163,7 +131,7 @@ impl BuildLift {
                 .unwrap()
                 .position;
             let transform = Transform::default();
-            let runtime_model = get_skilift(graphics, asset_manager, bound_shader, &transform);
+            let runtime_model = RuntimeModelId::new("lift".to_string());
 
             let lift = SecondLiftPlace {
                 bottom_position,
@@ -215,18 +183,15 @@ impl BuildLift {
             let lift = <&SecondLiftPlace>::query().iter(world).next().unwrap();
             let bottom_position = lift.bottom_position;
             let top_position = lift.position;
-            insert_lift(
-                world,
-                graphics,
-                asset_manager,
-                bound_shader,
-                bottom_position,
-                top_position,
-            )
-            .expect("failed to insert lift");
             world.remove(self.start_lift.unwrap());
             world.remove(self.end_lift.unwrap());
             self.placing_lift = LiftStage::NoLift;
+            Some(PlayerCommand::BuildLift {
+                start: (bottom_position.x, bottom_position.y),
+                end: (top_position.x, top_position.y),
+            })
+        } else {
+            None
         }
     }
 }
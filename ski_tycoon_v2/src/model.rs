@@ -28,20 +28,17 @@ impl Model {
         let mut vertices = vec![];
         for x in 0..dimensions.x - 1 {
             for y in 0..dimensions.y - 1 {
-                let x0_y0 = Vector3::new(x as f32, heights[x * dimensions.y + y] as f32, y as f32);
-                let x0_y1 = Vector3::new(
-                    x as f32,
-                    heights[x * dimensions.y + y + 1] as f32,
-                    y as f32 + 1.0,
-                );
+                let x0_y0 = Vector3::new(x as f32, heights[x * dimensions.y + y], y as f32);
+                let x0_y1 =
+                    Vector3::new(x as f32, heights[x * dimensions.y + y + 1], y as f32 + 1.0);
                 let x1_y0 = Vector3::new(
                     x as f32 + 1.0,
-                    heights[(x + 1) * dimensions.y + y] as f32,
+                    heights[(x + 1) * dimensions.y + y],
                     y as f32,
                 );
                 let x1_y1 = Vector3::new(
                     x as f32 + 1.0,
-                    heights[(x + 1) * dimensions.y + y + 1] as f32,
+                    heights[(x + 1) * dimensions.y + y + 1],
                     y as f32 + 1.0,
                 );
                 let triangle0_normal = (x0_y1 - x0_y0).cross(&(x1_y0 - x0_y0)).normalize();
@@ -121,7 +118,7 @@ impl Model {
         }
         Model {
             mesh: Mesh {
-                vertices: vertices.iter().map(|v| v.data.clone()).flatten().collect(),
+                vertices: vertices.iter().flat_map(|v| v.data.clone()).collect(),
                 description: ItemDesc::default_model(),
             },
             texture: Texture::constant_color(Vector4::new(200, 200, 200, 255), Vector2::new(8, 8)),
@@ -540,7 +537,7 @@ impl Model {
         ];
         Model {
             mesh: Mesh {
-                vertices: vertices.iter().map(|v| v.data.clone()).flatten().collect(),
+                vertices: vertices.iter().flat_map(|v| v.data.clone()).collect(),
                 description: ItemDesc::default_model(),
             },
             texture: Texture::constant_color(Vector4::new(255, 0, 0, 255), Vector2::new(8, 8)),
@@ -0,0 +1,448 @@
+use super::{
+    clock::Clock,
+    finances::Finances,
+    prelude::{GraphLayer, GraphLayerList, Terrain, TrailLayer},
+    skiier::{GuestStatistics, Satisfaction},
+    terrain::Difficulty,
+};
+use egui::CtxRef;
+use legion::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+/// Goal a scenario asks the player to reach
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum Objective {
+    /// Total number of guests that visited the resort by the end of `by_day`
+    Guests { count: u32, by_day: u32 },
+    /// Average guest satisfaction in percent
+    AverageSatisfaction { percent: f32 },
+    /// Cash on hand
+    Cash { amount: f32 },
+    /// At least one run of each difficulty
+    RunOfEveryDifficulty,
+}
+impl Objective {
+    /// Checks that the objective is possible to complete. On failure returns the offending field
+    /// and the reason it is invalid
+    pub fn validate(&self) -> Result<(), (String, String)> {
+        match self {
+            Self::Guests { count, by_day } => {
+                if *count == 0 {
+                    Err(("count".to_string(), "must be greater than zero".to_string()))
+                } else if *by_day == 0 {
+                    Err((
+                        "by_day".to_string(),
+                        "must be greater than zero".to_string(),
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            Self::AverageSatisfaction { percent } => {
+                if !(0.0..=100.0).contains(percent) {
+                    Err((
+                        "percent".to_string(),
+                        format!("{} is not between 0 and 100", percent),
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            Self::Cash { amount } => {
+                if !amount.is_finite() || *amount < 0.0 {
+                    Err((
+                        "amount".to_string(),
+                        format!("{} is not a positive amount", amount),
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            Self::RunOfEveryDifficulty => Ok(()),
+        }
+    }
+}
+impl Objective {
+    /// Gets how close the resort is to completing the objective, from 0 to 1
+    pub fn progress(&self, summary: &ResortSummary) -> f32 {
+        let progress = match self {
+            Self::Guests { count, .. } => summary.total_guests as f32 / *count as f32,
+            Self::AverageSatisfaction { percent } => {
+                if *percent == 0.0 {
+                    1.0
+                } else {
+                    summary.average_satisfaction.unwrap_or(0.0) * 100.0 / percent
+                }
+            }
+            Self::Cash { amount } => {
+                if *amount == 0.0 {
+                    1.0
+                } else {
+                    summary.cash / amount
+                }
+            }
+            Self::RunOfEveryDifficulty => {
+                Difficulty::ALL
+                    .iter()
+                    .filter(|d| summary.difficulties.contains(d))
+                    .count() as f32
+                    / Difficulty::ALL.len() as f32
+            }
+        };
+        progress.clamp(0.0, 1.0)
+    }
+    fn is_met(&self, summary: &ResortSummary) -> bool {
+        match self {
+            Self::Guests { count, .. } => summary.total_guests >= *count,
+            Self::AverageSatisfaction { percent } => summary
+                .average_satisfaction
+                .map(|s| s * 100.0 > *percent)
+                .unwrap_or(false),
+            Self::Cash { amount } => summary.cash > *amount,
+            Self::RunOfEveryDifficulty => Difficulty::ALL
+                .iter()
+                .all(|d| summary.difficulties.contains(d)),
+        }
+    }
+}
+impl std::fmt::Display for Objective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Guests { count, by_day } => write!(f, "{} guests by day {}", count, by_day),
+            Self::AverageSatisfaction { percent } => {
+                write!(f, "average satisfaction above {}%", percent)
+            }
+            Self::Cash { amount } => write!(f, "cash above ${}", amount),
+            Self::RunOfEveryDifficulty => write!(f, "a run of every difficulty"),
+        }
+    }
+}
+/// Snapshot of the resort used to check objectives
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResortSummary {
+    /// Day being evaluated
+    pub day: u32,
+    pub total_guests: u32,
+    /// Average satisfaction of guests from 0 to 1, none if there are no guests
+    pub average_satisfaction: Option<f32>,
+    pub cash: f32,
+    /// Difficulties of runs in the resort that are as steep as they are labelled
+    pub difficulties: HashSet<Difficulty>,
+}
+/// Difficulty of the steepest part of a run, none if the run is a single tile
+fn rated_difficulty(trail: &TrailLayer, terrain: &Terrain) -> Option<Difficulty> {
+    trail
+        .tiles()
+        .flat_map(|tile| {
+            trail
+                .get_children(tile)
+                .into_iter()
+                .filter_map(move |(child, _)| terrain.grade(tile.node, child.node))
+        })
+        .map(Difficulty::from_grade)
+        .max()
+}
+impl ResortSummary {
+    /// Builds summary from world. returns none if no scenario is loaded
+    pub fn from_world(world: &World) -> Option<Self> {
+        let clock = <&Clock>::query().iter(world).next()?;
+        let total_guests = <&GuestStatistics>::query()
+            .iter(world)
+            .next()
+            .map(|s| s.total_guests)
+            .unwrap_or(0);
+        let satisfaction: Vec<f32> = <&Satisfaction>::query()
+            .iter(world)
+            .map(|s| s.value)
+            .collect();
+        let average_satisfaction = if satisfaction.is_empty() {
+            None
+        } else {
+            Some(satisfaction.iter().sum::<f32>() / satisfaction.len() as f32)
+        };
+        let cash = <&Finances>::query()
+            .iter(world)
+            .next()
+            .map(|f| f.cash)
+            .unwrap_or(0.0);
+        let layers: Vec<&GraphLayer> = <&GraphLayer>::query().iter(world).collect();
+        let difficulties = match <&Terrain>::query().iter(world).next() {
+            Some(terrain) => GraphLayerList::new(layers)
+                .find_trails()
+                .iter()
+                .filter(|t| rated_difficulty(t, terrain) == Some(t.difficulty))
+                .map(|t| t.difficulty)
+                .collect(),
+            None => HashSet::new(),
+        };
+        Some(Self {
+            day: clock.day(),
+            total_guests,
+            average_satisfaction,
+            cash,
+            difficulties,
+        })
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectiveState {
+    InProgress,
+    Complete,
+    Failed,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectiveProgress {
+    pub objective: Objective,
+    pub progress: f32,
+    pub state: ObjectiveState,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Playing,
+    Won { day: u32 },
+    Lost { day: u32 },
+}
+/// Tracks progress of the current scenario's objectives
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectiveTracker {
+    objectives: Vec<ObjectiveProgress>,
+    outcome: Outcome,
+    /// whether the player closed the win/lose screen
+    outcome_dismissed: bool,
+}
+impl ObjectiveTracker {
+    pub fn new(objectives: Vec<Objective>) -> Self {
+        Self {
+            objectives: objectives
+                .iter()
+                .map(|objective| ObjectiveProgress {
+                    objective: objective.clone(),
+                    progress: 0.0,
+                    state: ObjectiveState::InProgress,
+                })
+                .collect(),
+            outcome: Outcome::Playing,
+            outcome_dismissed: false,
+        }
+    }
+    /// Updates objectives at the end of `summary.day`. Once the scenario is won or lost
+    /// objectives are no longer updated. Scenarios without objectives are sandboxes and can not be
+    /// won or lost.
+    pub fn evaluate(&mut self, summary: &ResortSummary) {
+        if self.outcome != Outcome::Playing {
+            return;
+        }
+        for entry in self.objectives.iter_mut() {
+            entry.progress = entry.objective.progress(summary);
+            let is_met = entry.objective.is_met(summary);
+            entry.state = match entry.objective {
+                Objective::Guests { by_day, .. } => {
+                    if entry.state == ObjectiveState::Complete || (is_met && summary.day <= by_day)
+                    {
+                        ObjectiveState::Complete
+                    } else if summary.day >= by_day {
+                        ObjectiveState::Failed
+                    } else {
+                        ObjectiveState::InProgress
+                    }
+                }
+                _ => {
+                    if is_met {
+                        ObjectiveState::Complete
+                    } else {
+                        ObjectiveState::InProgress
+                    }
+                }
+            };
+        }
+        if self
+            .objectives
+            .iter()
+            .any(|o| o.state == ObjectiveState::Failed)
+        {
+            self.outcome = Outcome::Lost { day: summary.day };
+        } else if !self.objectives.is_empty()
+            && self
+                .objectives
+                .iter()
+                .all(|o| o.state == ObjectiveState::Complete)
+        {
+            self.outcome = Outcome::Won { day: summary.day };
+        }
+    }
+}
+/// Evaluates objectives for the day that just ended. Should be called at the start of each new
+/// day.
+pub fn evaluate_objectives(world: &mut World) {
+    if let Some(mut summary) = ResortSummary::from_world(world) {
+        summary.day -= 1;
+        if let Some(tracker) = <&mut ObjectiveTracker>::query().iter_mut(world).next() {
+            tracker.evaluate(&summary);
+        }
+    }
+}
+pub fn draw_objectives(world: &mut World, context: &mut CtxRef) {
+    let summary = if let Some(s) = ResortSummary::from_world(world) {
+        s
+    } else {
+        return;
+    };
+    let hour = <&Clock>::query()
+        .iter(world)
+        .next()
+        .map(|c| c.hour())
+        .unwrap_or(0);
+    let tracker = if let Some(t) = <&mut ObjectiveTracker>::query().iter_mut(world).next() {
+        t
+    } else {
+        return;
+    };
+    egui::Window::new("objectives").show(context, |ui| {
+        ui.label(format!("Day {}, {}:00", summary.day, hour));
+        ui.label(format!("Cash: ${:.0}", summary.cash));
+        ui.label(format!("Guests: {}", summary.total_guests));
+        if let Some(satisfaction) = summary.average_satisfaction {
+            ui.label(format!("Satisfaction: {:.0}%", satisfaction * 100.0));
+        }
+        if tracker.objectives.is_empty() {
+            ui.label("Sandbox, no objectives");
+        }
+        for entry in tracker.objectives.iter() {
+            let state = match entry.state {
+                ObjectiveState::InProgress => "in progress",
+                ObjectiveState::Complete => "complete",
+                ObjectiveState::Failed => "failed",
+            };
+            ui.label(format!(
+                "{}: {:.0}% ({})",
+                entry.objective,
+                entry.progress * 100.0,
+                state
+            ));
+        }
+    });
+    let title = match tracker.outcome {
+        Outcome::Playing => return,
+        Outcome::Won { .. } => "Scenario Complete",
+        Outcome::Lost { .. } => "Scenario Failed",
+    };
+    if tracker.outcome_dismissed {
+        return;
+    }
+    let mut dismissed = false;
+    egui::Window::new(title).show(context, |ui| {
+        match tracker.outcome {
+            Outcome::Won { day } => ui.label(format!("All objectives completed on day {}", day)),
+            Outcome::Lost { day } => ui.label(format!("An objective was failed on day {}", day)),
+            Outcome::Playing => unreachable!(),
+        };
+        for entry in tracker.objectives.iter() {
+            ui.label(format!(
+                "{}: {:.0}%",
+                entry.objective,
+                entry.progress * 100.0
+            ));
+        }
+        if ui.button("Continue Playing").clicked {
+            dismissed = true;
+        }
+    });
+    if dismissed {
+        tracker.outcome_dismissed = true;
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn summary(day: u32, total_guests: u32, cash: f32) -> ResortSummary {
+        ResortSummary {
+            day,
+            total_guests,
+            average_satisfaction: Some(0.5),
+            cash,
+            difficulties: HashSet::new(),
+        }
+    }
+    #[test]
+    fn guests_by_day() {
+        let mut tracker = ObjectiveTracker::new(vec![Objective::Guests {
+            count: 100,
+            by_day: 3,
+        }]);
+        tracker.evaluate(&summary(1, 50, 0.0));
+        assert_eq!(tracker.outcome, Outcome::Playing);
+        assert_eq!(tracker.objectives[0].progress, 0.5);
+        tracker.evaluate(&summary(2, 100, 0.0));
+        assert_eq!(tracker.outcome, Outcome::Won { day: 2 });
+    }
+    #[test]
+    fn missed_deadline() {
+        let mut tracker = ObjectiveTracker::new(vec![
+            Objective::Guests {
+                count: 100,
+                by_day: 2,
+            },
+            Objective::Cash { amount: 10.0 },
+        ]);
+        tracker.evaluate(&summary(1, 10, 20.0));
+        assert_eq!(tracker.outcome, Outcome::Playing);
+        tracker.evaluate(&summary(2, 10, 20.0));
+        assert_eq!(tracker.outcome, Outcome::Lost { day: 2 });
+        //scenario is over so objectives are not updated
+        tracker.evaluate(&summary(3, 200, 20.0));
+        assert_eq!(tracker.outcome, Outcome::Lost { day: 2 });
+    }
+    #[test]
+    fn every_difficulty() {
+        let mut tracker = ObjectiveTracker::new(vec![Objective::RunOfEveryDifficulty]);
+        let mut s = summary(1, 0, 0.0);
+        s.difficulties.insert(Difficulty::Green);
+        s.difficulties.insert(Difficulty::Blue);
+        tracker.evaluate(&s);
+        assert_eq!(tracker.objectives[0].progress, 0.5);
+        assert_eq!(tracker.outcome, Outcome::Playing);
+        s.difficulties.insert(Difficulty::Black);
+        s.difficulties.insert(Difficulty::DoubleBlack);
+        tracker.evaluate(&s);
+        assert_eq!(tracker.outcome, Outcome::Won { day: 1 });
+    }
+    #[test]
+    fn runs_are_rated_by_slope() {
+        use crate::prelude::push_terrain;
+        use crate::trail::push_trail;
+        use nalgebra::Vector2;
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(9, 9), Vector2::new(4.0, 4.0), 4.0, -0.2),
+            &mut world,
+        );
+        world.push((Clock::default(),));
+        let gentle = [Vector2::new(3, 4), Vector2::new(2, 4), Vector2::new(1, 4)];
+        push_trail(&mut world, "Green".to_string(), Difficulty::Green, &gentle);
+        //a gentle slope labelled black and a single tile do not count
+        let labelled = [Vector2::new(5, 4), Vector2::new(6, 4), Vector2::new(7, 4)];
+        push_trail(
+            &mut world,
+            "Black".to_string(),
+            Difficulty::Black,
+            &labelled,
+        );
+        push_trail(
+            &mut world,
+            "Blue".to_string(),
+            Difficulty::Blue,
+            &[Vector2::new(4, 1)],
+        );
+        let summary = ResortSummary::from_world(&world).unwrap();
+        assert_eq!(
+            summary.difficulties,
+            [Difficulty::Green].iter().copied().collect()
+        );
+    }
+    #[test]
+    fn sandbox() {
+        let mut tracker = ObjectiveTracker::new(vec![]);
+        tracker.evaluate(&summary(100, 0, 0.0));
+        assert_eq!(tracker.outcome, Outcome::Playing);
+    }
+}
@@ -0,0 +1,233 @@
+use super::{commands::PlayerCommand, prelude::Event, simulation::world_hash};
+use egui::CtxRef;
+use legion::World;
+use log::error;
+use serde::{Deserialize, Serialize};
+/// Default location replays are saved to
+#[cfg(not(target_arch = "wasm32"))]
+pub const REPLAY_FILE: &str = "replay.ron";
+/// Inputs of a single frame
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReplayFrame {
+    pub events: Vec<Event>,
+    pub commands: Vec<PlayerCommand>,
+}
+/// Recorded session. The first frame always loads a scenario, which seeds the simulation, so
+/// playing back the frames reproduces the session exactly.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct Replay {
+    pub frames: Vec<ReplayFrame>,
+    /// Hash of the world at the end of the recording, used to check that playback matched
+    pub final_hash: Option<u64>,
+}
+impl Replay {
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string(self).expect("failed to serialize replay")
+    }
+    pub fn from_ron(data: &str) -> Result<Self, String> {
+        ron::de::from_str(data).map_err(|e| e.to_string())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &std::path::Path) -> Result<(), String> {
+        std::fs::write(path, self.to_ron()).map_err(|e| e.to_string())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        Self::from_ron(&std::fs::read_to_string(path).map_err(|e| e.to_string())?)
+    }
+}
+enum ReplayState {
+    Idle,
+    /// Recording starts once a scenario is loaded
+    Recording(Replay),
+    Playing {
+        replay: Replay,
+        frame: usize,
+    },
+}
+/// Records the player's input or plays back a recording
+pub struct ReplayRecorder {
+    state: ReplayState,
+    last_recording: Option<Replay>,
+    /// Expected hash of the world once the last frame of playback is simulated
+    pending_verification: Option<u64>,
+    /// Whether the last playback matched its recording
+    last_verification: Option<bool>,
+}
+impl Default for ReplayRecorder {
+    fn default() -> Self {
+        Self {
+            state: ReplayState::Idle,
+            last_recording: None,
+            pending_verification: None,
+            last_verification: None,
+        }
+    }
+}
+impl ReplayRecorder {
+    pub fn start_playback(&mut self, replay: Replay) {
+        self.state = ReplayState::Playing { replay, frame: 0 };
+    }
+    /// Gets events to use this frame. While playing back the recorded events replace the
+    /// player's events.
+    pub fn frame_events(&self, events: Vec<Event>) -> Vec<Event> {
+        match &self.state {
+            ReplayState::Playing { replay, frame } => replay
+                .frames
+                .get(*frame)
+                .map(|f| f.events.clone())
+                .unwrap_or_default(),
+            _ => events,
+        }
+    }
+    /// Gets commands to apply this frame and ends the frame. While recording, the frame's events
+    /// and commands are saved. While playing back the recorded commands replace the player's
+    /// commands.
+    #[allow(clippy::ptr_arg)]
+    pub fn frame_commands(
+        &mut self,
+        events: &Vec<Event>,
+        commands: Vec<PlayerCommand>,
+    ) -> Vec<PlayerCommand> {
+        match &mut self.state {
+            ReplayState::Idle => commands,
+            ReplayState::Recording(replay) => {
+                if replay.frames.is_empty() {
+                    let start = commands
+                        .iter()
+                        .position(|c| matches!(c, PlayerCommand::LoadScenario { .. }));
+                    if let Some(start) = start {
+                        replay.frames.push(ReplayFrame {
+                            events: events.clone(),
+                            commands: commands[start..].to_vec(),
+                        });
+                    }
+                } else {
+                    replay.frames.push(ReplayFrame {
+                        events: events.clone(),
+                        commands: commands.clone(),
+                    });
+                }
+                commands
+            }
+            ReplayState::Playing { replay, frame } => {
+                let commands = replay
+                    .frames
+                    .get(*frame)
+                    .map(|f| f.commands.clone())
+                    .unwrap_or_default();
+                *frame += 1;
+                if *frame >= replay.frames.len() {
+                    self.pending_verification = replay.final_hash;
+                    self.state = ReplayState::Idle;
+                }
+                commands
+            }
+        }
+    }
+    /// Checks that the world matches the recording once playback finishes. Should be called
+    /// after the frame's simulation step
+    pub fn verify(&mut self, world: &World) {
+        if let Some(expected) = self.pending_verification.take() {
+            let matches = world_hash(world) == expected;
+            if !matches {
+                error!("replay diverged from recording");
+            }
+            self.last_verification = Some(matches);
+        }
+    }
+    fn stop_recording(&mut self, world: &World) {
+        if let ReplayState::Recording(mut replay) =
+            std::mem::replace(&mut self.state, ReplayState::Idle)
+        {
+            replay.final_hash = Some(world_hash(world));
+            if !replay.frames.is_empty() {
+                self.last_recording = Some(replay);
+            }
+        }
+    }
+    pub fn draw_gui(&mut self, context: &mut CtxRef, world: &World) {
+        let mut start_recording = false;
+        let mut stop_recording = false;
+        let mut play = false;
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut save = false;
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut load = false;
+        let state = &self.state;
+        let has_recording = self.last_recording.is_some();
+        let last_verification = self.last_verification;
+        egui::Window::new("replay").show(context, |ui| match state {
+            ReplayState::Idle => {
+                match last_verification {
+                    Some(true) => {
+                        ui.label("Last playback matched its recording");
+                    }
+                    Some(false) => {
+                        ui.label("Last playback diverged from its recording");
+                    }
+                    None => (),
+                }
+                if ui.button("Record").clicked {
+                    start_recording = true;
+                }
+                if has_recording && ui.button("Play Last Recording").clicked {
+                    play = true;
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    if has_recording && ui.button("Save Recording").clicked {
+                        save = true;
+                    }
+                    if ui.button("Load Recording").clicked {
+                        load = true;
+                    }
+                }
+            }
+            ReplayState::Recording(replay) => {
+                if replay.frames.is_empty() {
+                    ui.label("Load a scenario to start recording");
+                } else {
+                    ui.label(format!("Recording frame {}", replay.frames.len()));
+                }
+                if ui.button("Stop").clicked {
+                    stop_recording = true;
+                }
+            }
+            ReplayState::Playing { replay, frame } => {
+                ui.label(format!(
+                    "Playing frame {} of {}",
+                    frame,
+                    replay.frames.len()
+                ));
+            }
+        });
+        if start_recording {
+            self.state = ReplayState::Recording(Replay::default());
+        }
+        if stop_recording {
+            self.stop_recording(world);
+        }
+        if play {
+            if let Some(replay) = self.last_recording.clone() {
+                self.start_playback(replay);
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if save {
+                if let Some(replay) = self.last_recording.as_ref() {
+                    if let Err(e) = replay.save(std::path::Path::new(REPLAY_FILE)) {
+                        error!("failed to save replay: {}", e);
+                    }
+                }
+            }
+            if load {
+                match Replay::load(std::path::Path::new(REPLAY_FILE)) {
+                    Ok(replay) => self.last_recording = Some(replay),
+                    Err(e) => error!("failed to load replay: {}", e),
+                }
+            }
+        }
+    }
+}
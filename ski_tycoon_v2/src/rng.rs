@@ -0,0 +1,69 @@
+/// Seeded random number generator. All randomness in the simulation goes through this so that a
+/// game can be reproduced from its seed. Uses
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c) which produces the same sequence on every
+/// platform.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeededRng {
+    state: u64,
+}
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    /// Seeds from the current time. Should only be used for picking seeds, never inside of the
+    /// simulation
+    pub fn from_time() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let seed = js_sys::Date::now() as u64;
+        #[cfg(not(target_arch = "wasm32"))]
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Gets float in range [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    /// Gets integer in range [0, max). panics if max is zero
+    pub fn next_below(&mut self, max: usize) -> usize {
+        assert!(max > 0);
+        (self.next_u64() % max as u64) as usize
+    }
+    /// Gets float in range [min, max)
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = SeededRng::new(42);
+        let mut b = SeededRng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(SeededRng::new(1).next_u64(), SeededRng::new(2).next_u64());
+    }
+    #[test]
+    fn ranges() {
+        let mut rng = SeededRng::new(7);
+        for _ in 0..1000 {
+            let f = rng.next_f32();
+            assert!((0.0..1.0).contains(&f));
+            assert!(rng.next_below(5) < 5);
+            let r = rng.range_f32(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&r));
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use super::{
+    building::{Building, BuildingStatistics},
+    clock::{self, Clock},
+    commands::{CommandHistory, PlayerCommand},
+    congestion,
+    finances::{self, Finances},
+    objectives,
+    prelude::{
+        update_flow_fields, update_navigation_graph, update_path_hierarchies, FollowPath,
+        GraphLayer, Terrain, Transform,
+    },
+    rng::SeededRng,
+    skiier::{self, BehaviorLibrary, GuestStatistics, Satisfaction},
+    snow, snowmaking,
+    staff::{self, Staff},
+    steering,
+    terrain::{apply_terrain_edits, TerrainLibrary},
+    trail::TrailStatistics,
+    weather::{self, Weather},
+    workers::WorkerPool,
+};
+use legion::*;
+use nalgebra::Vector2;
+use std::hash::{Hash, Hasher};
+/// Systems run every tick along with the worker pool and behaviors they use. Built once so
+/// ticks do not rebuild the schedule.
+pub struct Systems {
+    schedule: Schedule,
+    resources: Resources,
+}
+impl Systems {
+    pub fn new(pool: WorkerPool, behaviors: BehaviorLibrary) -> Self {
+        let mut resources = Resources::default();
+        resources.insert(pool);
+        resources.insert(behaviors);
+        Self {
+            schedule: Schedule::builder()
+                .add_system(skiier::follow_path_system())
+                .add_system(steering::avoid_collisions_system())
+                .build(),
+            resources,
+        }
+    }
+}
+/// Runs one tick of game logic. Does not use graphics so it can be run headless. Given the same
+/// world, rng, history, behaviors and commands the result is always the same no matter which
+/// worker pool the systems use.
+pub fn step(
+    world: &mut World,
+    library: &TerrainLibrary,
+    rng: &mut SeededRng,
+    history: &mut CommandHistory,
+    systems: &mut Systems,
+    commands: &[PlayerCommand],
+) {
+    for command in commands.iter() {
+        history.execute(command, world, library, rng);
+    }
+    apply_terrain_edits(world);
+    update_navigation_graph(world);
+    update_path_hierarchies(world);
+    update_flow_fields(world);
+    congestion::update_congestion(world);
+    systems.schedule.execute(world, &mut systems.resources);
+    staff::update_staff(world, rng);
+    snow::update_snow(world);
+    let new_day = clock::advance_clock(world);
+    weather::update_weather(world, rng);
+    snowmaking::make_snow(world);
+    if new_day {
+        finances::pay_upkeep(world);
+        skiier::guest_arrivals(world, rng);
+        objectives::evaluate_objectives(world);
+    }
+}
+/// 64 bit FNV-1a. Unlike the standard library's hasher its output is specified, so hashes saved
+/// in replays stay valid across toolchains. Integers are hashed as little endian 64 bit values
+/// so the hash is the same on every platform.
+struct StableHasher {
+    hash: u64,
+}
+impl StableHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    fn new() -> Self {
+        Self {
+            hash: Self::OFFSET_BASIS,
+        }
+    }
+}
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(Self::PRIME);
+        }
+    }
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+    fn write_i16(&mut self, i: i16) {
+        self.write_i64(i as i64);
+    }
+    fn write_i32(&mut self, i: i32) {
+        self.write_i64(i as i64);
+    }
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+/// Hash of the simulation state, used to check that two runs are identical
+pub fn world_hash(world: &World) -> u64 {
+    let mut hasher = StableHasher::new();
+    let hash_vector = |hasher: &mut StableHasher, v: &[f32]| {
+        for x in v.iter() {
+            x.to_bits().hash(hasher);
+        }
+    };
+    for clock in <&Clock>::query().iter(world) {
+        clock.tick().hash(&mut hasher);
+    }
+    for finances in <&Finances>::query().iter(world) {
+        finances.cash.to_bits().hash(&mut hasher);
+    }
+    for statistics in <&GuestStatistics>::query().iter(world) {
+        statistics.total_guests.hash(&mut hasher);
+    }
+    for layer in <&GraphLayer>::query().iter(world) {
+        if let GraphLayer::Lift(lift) = layer {
+            lift.start.hash(&mut hasher);
+            lift.end.hash(&mut hasher);
+        }
+    }
+    for terrain in <&Terrain>::query().iter(world) {
+        let dimensions = terrain.dimensions();
+        for x in 0..dimensions.x as i64 {
+            for y in 0..dimensions.y as i64 {
+                if let Some(tile) = terrain.get_tile(&Vector2::new(x, y)) {
+                    tile.tile_type.hash(&mut hasher);
+                    tile.snow_depth.to_bits().hash(&mut hasher);
+                }
+            }
+        }
+    }
+    for weather in <&Weather>::query().iter(world) {
+        weather.conditions.wind.to_bits().hash(&mut hasher);
+        weather.tomorrow.snowfall.to_bits().hash(&mut hasher);
+    }
+    for staff in <&Staff>::query().iter(world) {
+        staff.role.hash(&mut hasher);
+        staff.shift.hash(&mut hasher);
+    }
+    for (building, statistics) in <(&Building, &BuildingStatistics)>::query().iter(world) {
+        building.entrance.hash(&mut hasher);
+        statistics.visits.hash(&mut hasher);
+    }
+    for (layer, statistics) in <(&GraphLayer, &TrailStatistics)>::query().iter(world) {
+        if let GraphLayer::Trail(trail) = layer {
+            trail.name.hash(&mut hasher);
+            statistics.runs.hash(&mut hasher);
+        }
+    }
+    for (transform, path, satisfaction) in
+        <(&Transform, &FollowPath, &Satisfaction)>::query().iter(world)
+    {
+        hash_vector(&mut hasher, transform.get_translation().as_slice());
+        for node in path.nodes.iter() {
+            hash_vector(&mut hasher, node.as_slice());
+        }
+        satisfaction.value.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+#[cfg(test)]
+mod test {
+    use super::super::{
+        building::{push_building, BuildingKind},
+        replay::{Replay, ReplayFrame},
+        skiier::{Itinerary, Needs, Personality, Skill},
+        terrain::{parse_scenario, Difficulty},
+        weather::{Conditions, Forecast},
+    };
+    use super::*;
+    #[test]
+    fn stable_hash() {
+        //published FNV-1a test vectors
+        let hash = |bytes: &[u8]| {
+            let mut hasher = StableHasher::new();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+        //integers hash the same whatever their width
+        let mut narrow = StableHasher::new();
+        7u32.hash(&mut narrow);
+        let mut wide = StableHasher::new();
+        7u64.hash(&mut wide);
+        assert_eq!(narrow.finish(), wide.finish());
+    }
+    const SCENARIO: &str = r#"Scenario(
+        name: "Test",
+        terrain: Random(dimensions: (8, 8), height: 8.0, roughness: 0.5),
+        skiier_spawns: [Area(start: (0, 0), end: (2, 2))],
+        lifts: [(start: (7, 4), end: (0, 4))],
+        starting_cash: 1000.0,
+    )"#;
+    /// Same lift as `SCENARIO` on an even slope gentle enough that skiiers rarely fall
+    const GENTLE_SCENARIO: &str = r#"Scenario(
+        name: "Gentle",
+        terrain: Cone(dimensions: (8, 8), center: (0.0, 4.0), center_height: 3.0, slope: -0.3),
+        lifts: [(start: (7, 4), end: (0, 4))],
+        starting_cash: 1000.0,
+    )"#;
+    /// Plays back the commands of a replay without graphics and returns the resulting world
+    fn run_replay(replay: &Replay, library: &TerrainLibrary) -> World {
+        let mut world = World::default();
+        let mut rng = SeededRng::new(0);
+        let mut history = CommandHistory::default();
+        let mut systems = Systems::new(WorkerPool::default(), BehaviorLibrary::default());
+        for frame in replay.frames.iter() {
+            step(
+                &mut world,
+                library,
+                &mut rng,
+                &mut history,
+                &mut systems,
+                &frame.commands,
+            );
+        }
+        world
+    }
+    fn library() -> TerrainLibrary {
+        TerrainLibrary::from_scenarios(vec![
+            parse_scenario("test.ron", SCENARIO, &|_: &str| None).unwrap(),
+            parse_scenario("gentle.ron", GENTLE_SCENARIO, &|_: &str| None).unwrap(),
+        ])
+    }
+    fn replay(seed: u64) -> Replay {
+        let mut frames = vec![ReplayFrame {
+            events: vec![],
+            commands: vec![PlayerCommand::LoadScenario {
+                name: "Test".to_string(),
+                seed,
+            }],
+        }];
+        for i in 0..Clock::TICKS_PER_DAY + 20 {
+            frames.push(ReplayFrame {
+                events: vec![],
+                commands: if i == 100 {
+                    vec![PlayerCommand::BuildLift {
+                        start: (7, 2),
+                        end: (1, 2),
+                    }]
+                } else {
+                    vec![]
+                },
+            });
+        }
+        Replay {
+            frames,
+            final_hash: None,
+        }
+    }
+    #[test]
+    fn replay_is_deterministic() {
+        let library = library();
+        let replay = replay(5);
+        let first = run_replay(&replay, &library);
+        let second = run_replay(&replay, &library);
+        assert_eq!(world_hash(&first), world_hash(&second));
+        //guests arrived at the start of day 2
+        let guests = <&GuestStatistics>::query()
+            .iter(&first)
+            .next()
+            .unwrap()
+            .total_guests;
+        assert!(guests > 4);
+        let different_seed = run_replay(&self::replay(6), &library);
+        assert_ne!(world_hash(&first), world_hash(&different_seed));
+    }
+    /// Compares serial and parallel skiier planning on the "Toture Test" scenario. Run with
+    /// `cargo test --release toture_test_throughput -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn toture_test_throughput() {
+        const TICKS: u32 = 50;
+        let library = TerrainLibrary::default();
+        let mut hashes = vec![];
+        for pool in [WorkerPool::Serial, WorkerPool::Parallel].iter() {
+            let mut world = World::default();
+            let mut rng = SeededRng::new(0);
+            let mut history = CommandHistory::default();
+            let mut systems = Systems::new(*pool, BehaviorLibrary::default());
+            let load = PlayerCommand::LoadScenario {
+                name: "Toture Test".to_string(),
+                seed: 0,
+            };
+            step(
+                &mut world,
+                &library,
+                &mut rng,
+                &mut history,
+                &mut systems,
+                &[load],
+            );
+            let skiiers = <&Satisfaction>::query().iter(&world).count();
+            let start = std::time::Instant::now();
+            for _ in 0..TICKS {
+                step(
+                    &mut world,
+                    &library,
+                    &mut rng,
+                    &mut history,
+                    &mut systems,
+                    &[],
+                );
+            }
+            let seconds = start.elapsed().as_secs_f64();
+            println!(
+                "{:?}: {} skiiers, {} ticks in {:.2}s, {:.1} ticks/s",
+                pool,
+                skiiers,
+                TICKS,
+                seconds,
+                TICKS as f64 / seconds
+            );
+            hashes.push(world_hash(&world));
+        }
+        assert_eq!(hashes[0], hashes[1]);
+    }
+    #[test]
+    fn day_trip_guests_visit_a_lodge_and_leave() {
+        let library = library();
+        let mut world = World::default();
+        let mut rng = SeededRng::new(3);
+        let mut history = CommandHistory::default();
+        let mut systems = Systems::new(WorkerPool::Serial, BehaviorLibrary::default());
+        let load = PlayerCommand::LoadScenario {
+            name: "Gentle".to_string(),
+            seed: 3,
+        };
+        step(
+            &mut world,
+            &library,
+            &mut rng,
+            &mut history,
+            &mut systems,
+            &[load],
+        );
+        //a calm, clear day keeps the lift running until the next forecast at midnight
+        let calm = Forecast {
+            snowfall: 0.0,
+            temperature: -5.0,
+            wind: 0.0,
+            fog: false,
+        };
+        for weather in <&mut Weather>::query().iter_mut(&mut world) {
+            weather.today = calm.clone();
+            weather.tomorrow = calm.clone();
+            weather.conditions = Conditions::for_hour(&calm, 0, &mut rng);
+        }
+        //the lodge is at the base of the lift where the guest arrives
+        let base = Vector2::new(7, 4);
+        let lodge = push_building(&mut world, BuildingKind::Lodge, base);
+        let guest = skiier::spawn_skiier(
+            &mut world,
+            base,
+            Skill {
+                max_difficulty: Difficulty::Blue,
+            },
+            Personality::default(),
+        );
+        //tired from the drive up so warming up in the lodge beats a rest by the car
+        let mut entry = world.entry(guest).unwrap();
+        entry.add_component(Itinerary::day_trip());
+        *entry.get_component_mut::<Needs>().unwrap() = Needs {
+            hunger: 0.0,
+            fatigue: 0.8,
+        };
+        let mut ticks = 0;
+        while world.entry(guest).is_some() {
+            assert!(ticks < Clock::TICKS_PER_DAY, "guest never left");
+            step(
+                &mut world,
+                &library,
+                &mut rng,
+                &mut history,
+                &mut systems,
+                &[],
+            );
+            ticks += 1;
+        }
+        let entry = world.entry_ref(lodge).unwrap();
+        assert!(entry.get_component::<BuildingStatistics>().unwrap().visits > 0);
+    }
+    #[test]
+    fn replay_round_trip() {
+        let mut replay = replay(1);
+        replay.frames.truncate(3);
+        assert_eq!(Replay::from_ron(&replay.to_ron()), Ok(replay));
+    }
+}
@@ -0,0 +1,87 @@
+use super::building::{Building, BuildingKind};
+use super::commands::PlayerCommand;
+use super::prelude::Node;
+use super::staff::{staffed_posts, Role, Shift, Staff};
+use egui::CtxRef;
+use legion::world::EntityStore;
+use legion::*;
+use std::collections::HashSet;
+/// Ski schools that have at least one instructor on duty and can give lessons
+pub fn staffed_schools<W: EntityStore>(world: &W) -> HashSet<Entity> {
+    staffed_posts(world, Role::Instructor)
+}
+/// Whether skiiers can use a building. Ski schools are closed until they hire an instructor
+pub fn is_open(entity: &Entity, building: &Building, staffed: &HashSet<Entity>) -> bool {
+    building.kind != BuildingKind::SkiSchool || staffed.contains(entity)
+}
+/// Draws the ski school window listing each school and its instructors. Returns a command to
+/// hire an instructor if one was hired.
+pub fn draw_gui(world: &World, context: &mut CtxRef) -> Option<PlayerCommand> {
+    let mut instructors: Vec<(Entity, Node, usize)> = <(Entity, &Building)>::query()
+        .iter(world)
+        .filter(|(_, b)| b.kind == BuildingKind::SkiSchool)
+        .map(|(entity, b)| (*entity, b.entrance.clone(), 0))
+        .collect();
+    for staff in <&Staff>::query()
+        .iter(world)
+        .filter(|s| s.role == Role::Instructor)
+    {
+        if let Some(school) = instructors
+            .iter_mut()
+            .find(|(entity, _, _)| Some(*entity) == staff.post)
+        {
+            school.2 += 1;
+        }
+    }
+    let mut command = None;
+    egui::Window::new("ski school").show(context, |ui| {
+        if instructors.is_empty() {
+            ui.label("No ski schools");
+        }
+        for (_, entrance, count) in instructors.iter() {
+            ui.label(format!("school at {}: {} instructors", entrance, count));
+            if *count == 0 {
+                ui.label("    closed until an instructor is hired");
+            }
+            if ui
+                .button(format!("hire instructor at {}", entrance))
+                .clicked
+            {
+                command = Some(PlayerCommand::HireStaff {
+                    role: Role::Instructor,
+                    shift: Shift::Day,
+                    post: Some((entrance.node.x, entrance.node.y)),
+                });
+            }
+        }
+    });
+    command
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::building::push_building;
+    use crate::staff::{hire_staff, StaffError};
+    use nalgebra::Vector2;
+    #[test]
+    fn schools_open_with_instructors() {
+        let mut world = World::default();
+        let school = push_building(&mut world, BuildingKind::SkiSchool, Vector2::new(1, 1));
+        let lodge = push_building(&mut world, BuildingKind::Lodge, Vector2::new(2, 2));
+        let open = |world: &World, entity: Entity| {
+            let staffed = staffed_schools(world);
+            let entry = world.entry_ref(entity).unwrap();
+            let building = entry.get_component::<Building>().unwrap().clone();
+            is_open(&entity, &building, &staffed)
+        };
+        assert!(!open(&world, school));
+        assert!(open(&world, lodge));
+        //lodges do not need instructors
+        assert_eq!(
+            hire_staff(&mut world, Role::Instructor, Shift::AllDay, Some((2, 2))),
+            Err(StaffError::NoPost(Some((2, 2))))
+        );
+        assert!(hire_staff(&mut world, Role::Instructor, Shift::AllDay, Some((1, 1))).is_ok());
+        assert!(open(&world, school));
+    }
+}
@@ -1,12 +1,117 @@
 use super::prelude::{
-    AssetManager, ErrorType, FollowPath, GraphLayer, GraphLayerList, Model, Node, Path,
-    RenderingContext, RuntimeModel, RuntimeModelId, ShaderBind, Terrain, Transform,
+    FlowFields, FollowPath, GraphLayer, GraphLayerList, GraphWeight, Model, NavigationGraph, Node,
+    Path, PathCache, PathHierarchies, RuntimeModelId, Terrain, TrailLayer, Transform,
 };
+use super::rng::SeededRng;
+use super::ski_school::{is_open, staffed_schools};
+use super::snow::is_closed;
+use super::staff::{running_lifts, Injury, Staff};
+use super::steering::Steering;
 mod behavior_tree;
-use behavior_tree::{Number, SearchStart, TreeNode};
+mod group;
+mod motion;
+mod progression;
+mod utility;
+use super::building::{charge_visits, occupancy, Building, BuildingKind, BuildingStatistics, Need};
+use super::clock::Clock;
+use super::congestion::Congestion;
+use super::finances::{add_cash, Finances};
+use super::terrain::Difficulty;
+use super::trail::TrailStatistics;
+use super::weather::{turnout, Weather};
+use super::workers::WorkerPool;
+pub use behavior_tree::BehaviorLibrary;
+use behavior_tree::{rides_lift, Decision, Exit, Number};
+#[cfg(not(target_arch = "wasm32"))]
+pub use behavior_tree::{BEHAVIOR_DIRECTORY, BEHAVIOR_DIRECTORY_VARIABLE};
 use egui::CtxRef;
+use group::{prune_groups, spawn_group, Group, GroupMember, GroupPlan};
+use legion::systems::CommandBuffer;
+use legion::world::{EntityStore, SubWorld};
 use legion::*;
+use motion::move_along;
+pub use motion::Velocity;
 use nalgebra::{Vector2, Vector3};
+pub use progression::draw_progression;
+use progression::Progression;
+use std::collections::HashMap;
+pub use utility::{Needs, Personality};
+use utility::{Scorer, UtilityScore};
+/// How happy a guest is with the resort, from 0 to 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Satisfaction {
+    pub value: f32,
+}
+impl Satisfaction {
+    /// Change in satisfaction when a skiier gets to ride a lift
+    const LIFT_RIDE: f32 = 0.02;
+    /// Change in satisfaction when a skiier takes a break at a building or to rest
+    const BREAK: f32 = 0.01;
+    /// Change in satisfaction when a skiier can not find anything to do
+    const STUCK: f32 = -0.05;
+    pub fn change(&mut self, delta: f32) {
+        self.value = (self.value + delta).clamp(0.0, 1.0);
+    }
+}
+impl Default for Satisfaction {
+    fn default() -> Self {
+        Self { value: 0.75 }
+    }
+}
+/// Hardest run a skiier is comfortable skiing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Skill {
+    pub max_difficulty: Difficulty,
+}
+impl Skill {
+    /// Chance of a guest having each skill, in the same order as `Difficulty::ALL`
+    const DISTRIBUTION: [f32; 4] = [0.35, 0.4, 0.2, 0.05];
+    pub fn random(rng: &mut SeededRng) -> Self {
+        let mut roll = rng.next_f32();
+        for (difficulty, chance) in Difficulty::ALL.iter().zip(Self::DISTRIBUTION.iter()) {
+            if roll < *chance {
+                return Self {
+                    max_difficulty: *difficulty,
+                };
+            }
+            roll -= chance;
+        }
+        Self {
+            max_difficulty: Difficulty::DoubleBlack,
+        }
+    }
+    /// Whether the skiier gets the benefit of a run
+    fn can_ski(&self, difficulty: Difficulty) -> bool {
+        difficulty <= self.max_difficulty
+    }
+}
+/// Guest counts for the current scenario
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct GuestStatistics {
+    /// Number of guests that bought a ticket
+    pub total_guests: u32,
+    /// Lessons taken at ski schools
+    pub lessons: u32,
+    /// Number of times a guest moved up a skill level
+    pub level_ups: u32,
+}
+impl GuestStatistics {
+    /// Guests arriving per day for each lift at full satisfaction
+    const GUESTS_PER_LIFT: f32 = 40.0;
+}
+/// Behavior tree a guest follows, skiiers without one follow the default tree
+#[derive(Clone, Debug, PartialEq)]
+pub struct Itinerary {
+    pub tree: String,
+}
+impl Itinerary {
+    pub fn day_trip() -> Self {
+        Self {
+            tree: BehaviorLibrary::DAY_TRIP_TREE.to_string(),
+        }
+    }
+}
+#[derive(Clone)]
 pub struct DecisionDebugInfo {
     name: String,
     cost: Number<f32>,
@@ -14,26 +119,269 @@ pub struct DecisionDebugInfo {
     end: Node,
     path_len: usize,
 }
+impl DecisionDebugInfo {
+    /// Whether the decision is a paid visit to a building
+    fn is_visit(&self) -> bool {
+        Need::ALL.iter().any(|n| n.to_string() == self.name)
+    }
+}
+/// Shared navigation data used to plan paths. Everything is optional so skiiers can still plan
+/// before the structures are built.
+#[derive(Clone, Copy, Default)]
+struct Navigation<'a> {
+    hierarchies: Option<&'a PathHierarchies>,
+    flow_fields: Option<&'a FlowFields>,
+    path_cache: Option<&'a PathCache>,
+    /// Only set when skiiers route around crowds
+    congestion: Option<&'a Congestion>,
+    /// Skiiers on each tile, used to judge lift lines
+    occupancy: Option<&'a Congestion>,
+    buildings: &'a [&'a Building],
+}
+impl<'a> Navigation<'a> {
+    fn new<W: EntityStore>(world: &'a W) -> Self {
+        Self {
+            hierarchies: <&PathHierarchies>::query().iter(world).next(),
+            flow_fields: <&FlowFields>::query().iter(world).next(),
+            path_cache: <&NavigationGraph>::query()
+                .iter(world)
+                .next()
+                .map(|g| g.path_cache()),
+            congestion: <&Congestion>::query()
+                .iter(world)
+                .next()
+                .filter(|c| c.routing),
+            occupancy: <&Congestion>::query().iter(world).next(),
+            buildings: &[],
+        }
+    }
+}
+/// Path that keeps a skiier where they are for a few ticks before they plan again
+fn wait_at(position: Vector2<i64>, terrain: &Terrain) -> FollowPath {
+    FollowPath::new(
+        Path::new(vec![(Node { node: position }, GraphWeight::Some(0))]),
+        terrain,
+    )
+}
+/// A skiier or group at the end of their path that needs a new plan
+struct PlanRequest {
+    /// Skiiers who follow the plan
+    members: Vec<Entity>,
+    position: Vector2<i64>,
+    skill: Skill,
+    personality: Personality,
+    satisfaction: f32,
+    /// Name of the behavior tree to plan with
+    tree: String,
+    /// Lessons already taken
+    lessons: u32,
+    needs: Needs,
+    /// Group the members belong to
+    group: Option<Entity>,
+    /// Rest of the group's plan, none when a new plan is needed
+    rest: Option<GroupPlan>,
+}
+/// Requests plans for skiiers who `finished` their path. Groups only plan once every member has
+/// finished, using the weakest member's skill, the fewest lessons any member has taken, the most
+/// pressing needs of any member and the leader's personality and itinerary. Skiiers and groups
+/// who finished by heading to the exit are removed.
+fn plan_requests(
+    world: &SubWorld,
+    finished: &[(Entity, Vector2<i64>, bool)],
+    commands: &mut CommandBuffer,
+) -> Vec<PlanRequest> {
+    let skiier = |entity: Entity, position: Vector2<i64>| -> Option<PlanRequest> {
+        let entry = world.entry_ref(entity).ok()?;
+        Some(PlanRequest {
+            members: vec![entity],
+            position,
+            skill: *entry.get_component::<Skill>().ok()?,
+            personality: *entry.get_component::<Personality>().ok()?,
+            satisfaction: entry.get_component::<Satisfaction>().ok()?.value,
+            tree: entry
+                .get_component::<Itinerary>()
+                .map(|i| i.tree.clone())
+                .unwrap_or_else(|_| BehaviorLibrary::DEFAULT_TREE.to_string()),
+            lessons: entry
+                .get_component::<Progression>()
+                .map(|p| p.lessons)
+                .unwrap_or(0),
+            needs: entry.get_component::<Needs>().copied().unwrap_or_default(),
+            group: None,
+            rest: None,
+        })
+    };
+    let lookup: HashMap<Entity, (Vector2<i64>, bool)> = finished
+        .iter()
+        .map(|(entity, position, leaving)| (*entity, (*position, *leaving)))
+        .collect();
+    let mut requests = vec![];
+    for (group_entity, group) in <(Entity, &Group)>::query().iter(world) {
+        let ends: Option<Vec<(Vector2<i64>, bool)>> = group
+            .members
+            .iter()
+            .map(|member| lookup.get(member).copied())
+            .collect();
+        //members who are done wait for the rest of the group
+        let (position, leaving) = match ends.as_ref().and_then(|e| e.first()) {
+            Some(end) => *end,
+            None => continue,
+        };
+        if leaving {
+            for member in group.members.iter() {
+                commands.remove(*member);
+            }
+            commands.remove(*group_entity);
+            continue;
+        }
+        let members: Vec<PlanRequest> = group
+            .members
+            .iter()
+            .filter_map(|m| skiier(*m, position))
+            .collect();
+        let skills: Vec<Skill> = members.iter().map(|m| m.skill).collect();
+        if let (Some(skill), Some(leader)) = (Group::weakest(&skills), members.first()) {
+            requests.push(PlanRequest {
+                members: group.members.clone(),
+                position,
+                skill,
+                personality: leader.personality,
+                satisfaction: members.iter().map(|m| m.satisfaction).sum::<f32>()
+                    / members.len() as f32,
+                tree: leader.tree.clone(),
+                lessons: members.iter().map(|m| m.lessons).min().unwrap_or(0),
+                needs: Needs::most(&members.iter().map(|m| m.needs).collect::<Vec<_>>()),
+                group: Some(*group_entity),
+                rest: world
+                    .entry_ref(*group_entity)
+                    .ok()
+                    .and_then(|entry| entry.get_component::<GroupPlan>().ok().cloned())
+                    .filter(|plan| !plan.rest.is_empty()),
+            });
+        } else {
+            //nobody left who can plan
+            commands.remove(*group_entity);
+        }
+    }
+    for (entity, position, leaving) in finished.iter() {
+        let in_group = world
+            .entry_ref(*entity)
+            .map(|entry| entry.get_component::<GroupMember>().is_ok())
+            .unwrap_or(false);
+        if in_group {
+            continue;
+        }
+        if *leaving {
+            commands.remove(*entity);
+        } else if let Some(request) = skiier(*entity, *position) {
+            requests.push(request);
+        }
+    }
+    requests
+}
+/// Plans the skiier's next runs. The behavior tree lists what the skiier could do and the plan
+/// they like best is picked by its utility. Beginners follow the beginner tree while it has
+/// something for them, guests on a trip only until they have taken a lesson. Guests whose tree
+/// has nothing for them follow the default tree. Groups only follow their plan to the bottom of
+/// the first run, the rest of the plan is returned so they can carry on once they regroup. Runs
+/// harder than the skiier's skill are treated as bare terrain so skiiers prefer runs that match
+/// their skill. Skiiers wait where they are when the tree has nothing for them to do.
 #[allow(clippy::ptr_arg)]
 fn run_skiier_ai(
     layers: &Vec<&GraphLayer>,
-    start_position: Vector2<i64>,
+    request: &PlanRequest,
     terrain: &Terrain,
-) -> (FollowPath, Vec<DecisionDebugInfo>) {
-    let tree_start: Box<dyn TreeNode> = Box::new(SearchStart::default());
-    let decisions = tree_start.best_path(
-        4,
-        &GraphLayerList::new(layers.clone()),
-        Node {
-            node: start_position,
-        },
-        terrain,
-    );
+    navigation: Navigation,
+    behaviors: &BehaviorLibrary,
+) -> (
+    FollowPath,
+    Vec<DecisionDebugInfo>,
+    UtilityScore,
+    Vec<Decision>,
+) {
+    let skill = &request.skill;
+    let start_position = request.position;
+    let layers: Vec<&GraphLayer> = layers
+        .iter()
+        .filter(|layer| match layer {
+            GraphLayer::Trail(trail) => skill.can_ski(trail.difficulty),
+            _ => true,
+        })
+        .copied()
+        .collect();
+    let layers = GraphLayerList::new(layers)
+        .with_hierarchy(
+            navigation
+                .hierarchies
+                .and_then(|h| h.for_skill(skill.max_difficulty)),
+        )
+        .with_flow_fields(
+            navigation
+                .flow_fields
+                .map(|f| f.for_skill(skill.max_difficulty))
+                .unwrap_or_default(),
+        )
+        .with_path_cache(navigation.path_cache, skill.max_difficulty)
+        .with_congestion(navigation.congestion)
+        .with_buildings(navigation.buildings.to_vec());
+    let start = Node {
+        node: start_position,
+    };
+    let (mut decisions, score) = if let Some(plan) = request.rest.as_ref() {
+        //groups carry on with their plan once they have regrouped
+        (plan.rest.clone(), plan.score.clone())
+    } else {
+        //beginners take lessons when there is a ski school they can get to
+        let lessons = if skill.max_difficulty == Difficulty::Green
+            && (request.tree == BehaviorLibrary::DEFAULT_TREE || request.lessons == 0)
+        {
+            behaviors.plans(
+                BehaviorLibrary::BEGINNER_TREE,
+                &layers,
+                start.clone(),
+                terrain,
+            )
+        } else {
+            vec![]
+        };
+        let plans = if lessons.is_empty() {
+            let plans = behaviors.plans(&request.tree, &layers, start.clone(), terrain);
+            if plans.is_empty() && request.tree != BehaviorLibrary::DEFAULT_TREE {
+                behaviors.plans(BehaviorLibrary::DEFAULT_TREE, &layers, start, terrain)
+            } else {
+                plans
+            }
+        } else {
+            lessons
+        };
+        let scorer = Scorer {
+            layers: &layers,
+            occupancy: navigation.occupancy,
+            skill: *skill,
+            personality: request.personality,
+            satisfaction: request.satisfaction,
+            needs: request.needs,
+        };
+        match scorer.choose(plans) {
+            Some((plan, score)) => (plan.decisions, score),
+            None => (vec![], UtilityScore::default()),
+        }
+    };
+    //groups regroup at the bottom of every run and keep the rest of the plan for after
+    let rest = match decisions.iter().position(|d| d.name == "Down") {
+        Some(run) if request.members.len() > 1 => decisions.split_off(run + 1),
+        _ => vec![],
+    };
     let follow = decisions
         .iter()
         .fold(FollowPath::new(Path::default(), terrain), |acc, x| {
             acc.append(&x.path)
         });
+    let follow = if follow.endpoint().is_some() {
+        follow
+    } else {
+        wait_at(start_position, terrain)
+    };
 
     let decision_debug_info: Vec<DecisionDebugInfo> = decisions
         .iter()
@@ -49,40 +397,141 @@ fn run_skiier_ai(
             end: d.endpoint.clone(),
         })
         .collect();
-    (follow, decision_debug_info)
+    (follow, decision_debug_info, score, rest)
+}
+/// Change in satisfaction from carrying out a plan, the best part of the plan counts. None for
+/// plans without a lift ride, a run or a break, which leave the skiier stuck.
+fn satisfaction_change(decisions: &[DecisionDebugInfo]) -> Option<f32> {
+    decisions
+        .iter()
+        .filter(|d| d.cost != Number::Infinite)
+        .filter_map(|d| {
+            if rides_lift(&d.name) || d.name.starts_with("Ski ") {
+                Some(Satisfaction::LIFT_RIDE)
+            } else if d.name == "Rest" || Need::ALL.iter().any(|n| n.to_string() == d.name) {
+                Some(Satisfaction::BREAK)
+            } else {
+                None
+            }
+        })
+        .fold(None, |best: Option<f32>, change| {
+            Some(best.map_or(change, |b| b.max(change)))
+        })
 }
-pub fn build_skiier(
+pub fn skiier_model() -> Model {
+    Model::cube(Transform::default())
+}
+/// Adds a guest to the resort. They plan their first runs once they have looked around for a
+/// few ticks.
+pub fn spawn_skiier(
     world: &mut World,
-    graphics: &mut RenderingContext,
-
-    asset_manager: &mut AssetManager<RuntimeModel>,
-    bound_shader: &ShaderBind,
     position: Vector2<i64>,
-) -> Result<(), ErrorType> {
-    let layers: Vec<&GraphLayer> = <&GraphLayer>::query().iter(world).collect();
+    skill: Skill,
+    personality: Personality,
+) -> Entity {
     let terrain = <&Terrain>::query().iter(world).next().unwrap();
-    let (follow, decision_debug_info) = run_skiier_ai(&layers, position, terrain);
+    let follow = wait_at(position, terrain);
     let mut transform = Transform::default();
     transform.set_scale(Vector3::new(0.1, 0.1, 0.1));
-    if !asset_manager.contains("skiier") {
-        let model = Model::cube(transform.clone());
-        asset_manager.get_or_create(
-            "skiier",
-            RuntimeModel::new(&model, graphics, bound_shader.get_bind())
-                .expect("failed to build run time model"),
-        );
-    }
     let runtime_model = RuntimeModelId::new("skiier".to_string());
 
-    world.push((transform, follow, runtime_model, decision_debug_info));
-    Ok(())
+    world.push((
+        transform,
+        follow,
+        runtime_model,
+        Vec::<DecisionDebugInfo>::new(),
+        Satisfaction::default(),
+        skill,
+        Steering::default(),
+        Velocity::default(),
+        personality,
+        UtilityScore::default(),
+        Progression::new(skill),
+        Needs::default(),
+    ))
+}
+/// Spawns the day's new guests in groups at parking lots with space left, or at the bottom of
+/// random lifts once the lots are full. Every guest buys a ticket and every group that parks
+/// pays for parking. The number of guests depends on the number of lifts and the average satisfaction of
+/// guests.
+pub fn guest_arrivals(world: &mut World, rng: &mut SeededRng) {
+    let lift_bases: Vec<Vector2<i64>> = {
+        let layers: Vec<&GraphLayer> = <&GraphLayer>::query().iter(world).collect();
+        GraphLayerList::new(layers)
+            .find_lifts()
+            .iter()
+            .map(|l| l.start.node)
+            .collect()
+    };
+    if lift_bases.is_empty() || <&Terrain>::query().iter(world).next().is_none() {
+        return;
+    }
+    let satisfaction: Vec<f32> = <&Satisfaction>::query()
+        .iter(world)
+        .map(|s| s.value)
+        .collect();
+    let average_satisfaction = if satisfaction.is_empty() {
+        Satisfaction::default().value
+    } else {
+        satisfaction.iter().sum::<f32>() / satisfaction.len() as f32
+    };
+    let expected_arrivals = lift_bases.len() as f32
+        * GuestStatistics::GUESTS_PER_LIFT
+        * average_satisfaction
+        * turnout(world);
+    //arrivals vary by up to 20% each day
+    let arrivals = (expected_arrivals * rng.range_f32(0.8, 1.2)) as usize;
+    //parking lots along with the number of groups that can still park there today
+    let mut lots: Vec<(Node, u32)> = <&Building>::query()
+        .iter(world)
+        .filter(|b| b.kind == BuildingKind::Parking)
+        .map(|b| (b.entrance.clone(), b.kind.capacity()))
+        .collect();
+    let mut parked: Vec<Node> = vec![];
+    let mut spawned = 0;
+    while spawned < arrivals {
+        let open: Vec<usize> = (0..lots.len()).filter(|i| lots[*i].1 > 0).collect();
+        let position = if open.is_empty() {
+            lift_bases[rng.next_below(lift_bases.len())]
+        } else {
+            let lot = &mut lots[open[rng.next_below(open.len())]];
+            lot.1 -= 1;
+            parked.push(lot.0.clone());
+            lot.0.node
+        };
+        spawned += spawn_group(world, position, arrivals - spawned, rng);
+    }
+    let parking = charge_visits(world, &parked);
+    add_cash(world, arrivals as f32 * Finances::TICKET_PRICE + parking);
+    if let Some(statistics) = <&mut GuestStatistics>::query().iter_mut(world).next() {
+        statistics.total_guests += arrivals as u32;
+    }
 }
 
 pub fn draw_skiiers(world: &World, context: &mut CtxRef) {
     egui::Window::new("skiier decisions").show(context, |ui| {
-        let mut query = <&Vec<DecisionDebugInfo>>::query();
-        for skiier in query.iter(world) {
+        let mut query = <(
+            &Vec<DecisionDebugInfo>,
+            &Velocity,
+            &UtilityScore,
+            Option<&GroupMember>,
+        )>::query();
+        for (skiier, velocity, score, member) in query.iter(world) {
             ui.collapsing("skiier", |ui| {
+                let group = member
+                    .and_then(|m| world.entry_ref(m.group).ok())
+                    .and_then(|entry| entry.get_component::<Group>().ok().cloned());
+                if let Some(group) = group {
+                    ui.label(format!("{} of {}", group.kind, group.members.len()));
+                }
+                ui.label(format!("speed: {:.3} tiles per tick", velocity.speed()));
+                ui.label(format!("utility: {:.2}", score.total));
+                for (consideration, value, weight) in score.parts.iter() {
+                    ui.label(format!(
+                        "    {}: {:.2} x {:.2}",
+                        consideration, value, weight
+                    ));
+                }
                 for debug in skiier.iter() {
                     ui.label(format!(
                         "{}: {}, path len: {}, start: {}, end: {}",
@@ -93,34 +542,558 @@ pub fn draw_skiiers(world: &World, context: &mut CtxRef) {
         }
     });
 }
-pub fn follow_path(world: &mut World) {
-    let layers: Vec<GraphLayer> = <&GraphLayer>::query().iter(world).cloned().collect();
-    //bad hack inorder to avoid copying terrain more then needed
-    let terrain_iter = <&Terrain>::query().iter(world).next();
-    if terrain_iter.is_none() {
-        return;
+/// Names of the runs a skiier skied off the end of while moving from node `from` to node `to` of
+/// `tiles`. A run is finished when the skiier skis downhill onto its last tile before leaving it,
+/// paths that stop partway down a run only finish it at the bottom.
+fn finished_runs(
+    tiles: &[Node],
+    from: usize,
+    to: usize,
+    trails: &[&TrailLayer],
+    terrain: &Terrain,
+) -> Vec<String> {
+    let height = |n: &Node| terrain.get_transform(&n.node).map(|t| t.y);
+    let downhill = |a: &Node, b: &Node| match (height(a), height(b)) {
+        (Some(a), Some(b)) => b < a,
+        _ => false,
+    };
+    let mut runs = vec![];
+    for j in (from + 1)..=to.min(tiles.len().saturating_sub(1)) {
+        if !downhill(&tiles[j - 1], &tiles[j]) {
+            continue;
+        }
+        for trail in trails.iter() {
+            if !trail.contains(&tiles[j - 1]) || !trail.contains(&tiles[j]) {
+                continue;
+            }
+            let leaves = match tiles.get(j + 1) {
+                Some(next) => !trail.contains(next),
+                None => !trail
+                    .get_children(&tiles[j])
+                    .iter()
+                    .any(|(child, _)| downhill(&tiles[j], child)),
+            };
+            if leaves {
+                runs.push(trail.name.clone());
+            }
+        }
     }
-    let terrain: *const Terrain = &*terrain_iter.unwrap();
-    let borrow_graph_layer = layers.iter().collect();
-    let mut query = <(&mut Transform, &mut FollowPath, &mut Vec<DecisionDebugInfo>)>::query();
-    for (transform, path, debug_info) in query.iter_mut(world) {
-        if path.at_end() {
-            if let Some(endpoint) = path.endpoint() {
-                let (t_path, t_debug_info) =
-                    run_skiier_ai(&borrow_graph_layer, endpoint.node, unsafe { &*terrain });
-                if !t_path.is_empty() {
-                    let t = path.get();
+    runs
+}
+/// Moves skiiers along their paths and plans a new path for skiiers at the end of theirs.
+/// Members of a group share one plan. Planning only reads the navigation data, so it is spread
+/// over the worker pool and the plans are applied afterwards.
+#[system]
+#[read_component(Terrain)]
+#[read_component(NavigationGraph)]
+#[read_component(PathHierarchies)]
+#[read_component(FlowFields)]
+#[read_component(Congestion)]
+#[read_component(GraphLayer)]
+#[read_component(Personality)]
+#[read_component(Itinerary)]
+#[read_component(Building)]
+#[read_component(Staff)]
+#[read_component(Weather)]
+#[read_component(Clock)]
+#[read_component(Injury)]
+#[read_component(GroupMember)]
+#[write_component(Transform)]
+#[write_component(FollowPath)]
+#[write_component(Velocity)]
+#[write_component(Vec<DecisionDebugInfo>)]
+#[write_component(Satisfaction)]
+#[write_component(UtilityScore)]
+#[write_component(Skill)]
+#[write_component(Progression)]
+#[write_component(Needs)]
+#[write_component(Group)]
+#[write_component(GroupPlan)]
+#[write_component(GuestStatistics)]
+#[write_component(TrailStatistics)]
+#[write_component(BuildingStatistics)]
+#[write_component(Finances)]
+pub fn follow_path(
+    world: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] pool: &WorkerPool,
+    #[resource] behaviors: &BehaviorLibrary,
+) {
+    //skiiers at the end of their path along with where they finished and whether they are
+    //leaving
+    let mut finished: Vec<(Entity, Vector2<i64>, bool)> = vec![];
+    //number of skiiers that skied off the end of each run this tick, keyed by name
+    let mut trail_usage: HashMap<String, u32> = HashMap::new();
+    let mut lessons = 0;
+    let mut level_ups = 0;
+    //entrances of the buildings guests reached this tick
+    let mut visits: Vec<Node> = vec![];
+    {
+        let (mut skiiers, rest) = world.split::<(
+            &mut Transform,
+            &mut FollowPath,
+            &mut Velocity,
+            &mut Skill,
+            &mut Progression,
+            &mut Needs,
+            &Vec<DecisionDebugInfo>,
+        )>();
+        let terrain = match <&Terrain>::query().iter(&rest).next() {
+            Some(terrain) => terrain,
+            None => return,
+        };
+        let layers: Vec<&GraphLayer> = <&GraphLayer>::query().iter(&rest).collect();
+        let trails = GraphLayerList::new(layers).find_trails();
+        let mut query = <(
+            Entity,
+            &mut Transform,
+            &mut FollowPath,
+            &mut Velocity,
+            &mut Skill,
+            Option<&mut Progression>,
+            Option<&mut Needs>,
+            &Vec<DecisionDebugInfo>,
+        )>::query()
+        //injured skiiers wait for ski patrol
+        .filter(!component::<Injury>());
+        for (entity, transform, path, velocity, skill, progression, needs, decisions) in
+            query.iter_mut(&mut skiiers)
+        {
+            if path.at_end() {
+                if let Some(endpoint) = path.endpoint() {
+                    let leaving = decisions.last().map(|d| d.name == Exit::NAME) == Some(true);
+                    finished.push((*entity, endpoint.node, leaving));
+                }
+            } else {
+                let from = path.reached();
+                move_along(path, velocity, terrain, skill);
+                let to = path.reached();
+                for run in finished_runs(path.tiles(), from, to, &trails, terrain) {
+                    *trail_usage.entry(run).or_insert(0) += 1;
+                }
+                //experience comes from the tiles skied this tick and lessons from reaching the
+                //ski school
+                if let (Some(progression), true) = (progression, to > from) {
+                    let taken = path.tiles()[from + 1..=to]
+                        .iter()
+                        .filter(|tile| {
+                            decisions
+                                .iter()
+                                .any(|d| d.name == "Lesson" && d.end == **tile)
+                        })
+                        .count();
+                    let gained = progression::experience(
+                        &path.tiles()[from..=to],
+                        &trails,
+                        terrain,
+                        skill,
+                        taken,
+                    );
+                    progression.lessons += taken as u32;
+                    lessons += taken as u32;
+                    if progression.gain(skill, gained) {
+                        level_ups += 1;
+                    }
+                }
+                //guests pay for a visit once they get to the building
+                for tile in path.tiles()[from + 1..=to].iter() {
+                    visits.extend(
+                        decisions
+                            .iter()
+                            .filter(|d| d.end == *tile && d.is_visit())
+                            .map(|d| d.end.clone()),
+                    );
+                }
+                //skiing makes guests hungry and tired until they stop somewhere that helps
+                if let (Some(needs), true) = (needs, to > from) {
+                    needs.ski(to - from);
+                    for tile in path.tiles()[from + 1..=to].iter() {
+                        for decision in decisions.iter().filter(|d| d.end == *tile) {
+                            needs.meet(&decision.name);
+                        }
+                    }
+                }
+                if let Some(t) = path.get() {
                     transform.set_translation(t);
                 }
-                *path = t_path;
-                *debug_info = t_debug_info;
+                if let Some(heading) = path.heading() {
+                    transform.face(heading);
+                }
             }
-        } else {
-            path.incr(0.1);
-            if !path.is_empty() {
-                let t = path.get();
+        }
+    }
+    for (layer, statistics) in <(&GraphLayer, &mut TrailStatistics)>::query().iter_mut(world) {
+        if let GraphLayer::Trail(trail) = layer {
+            if let Some(runs) = trail_usage.get(&trail.name) {
+                statistics.runs += runs;
+            }
+        }
+    }
+    if let Some(statistics) = <&mut GuestStatistics>::query().iter_mut(world).next() {
+        statistics.lessons += lessons;
+        statistics.level_ups += level_ups;
+    }
+    let paid = charge_visits(world, &visits);
+    if let Some(finances) = <&mut Finances>::query().iter_mut(world).next() {
+        finances.cash += paid;
+    }
+    prune_groups(world, commands);
+    let planning = plan_requests(world, &finished, commands);
+    if planning.is_empty() {
+        return;
+    }
+    let (mut plans, mut rests) = {
+        let terrain = match <&Terrain>::query().iter(world).next() {
+            Some(terrain) => terrain,
+            None => return,
+        };
+        let graph = match <&NavigationGraph>::query().iter(world).next() {
+            Some(graph) => graph,
+            None => return,
+        };
+        let staffed = staffed_schools(world);
+        let inside = occupancy(world);
+        let buildings: Vec<&Building> = <(Entity, &Building)>::query()
+            .iter(world)
+            .filter(|(entity, building)| is_open(entity, building, &staffed))
+            .filter(|(_, building)| building.has_room(&inside))
+            .map(|(_, building)| building)
+            .collect();
+        //lifts without an operator on duty and runs with too little snow are closed
+        let running = running_lifts(world);
+        let layers: Vec<&GraphLayer> = graph
+            .layers()
+            .into_iter()
+            .filter(|layer| match layer {
+                GraphLayer::Lift(lift) => running.contains(&lift.start),
+                GraphLayer::Trail(trail) => !is_closed(trail, terrain),
+                _ => true,
+            })
+            .collect();
+        let mut navigation = Navigation {
+            buildings: &buildings,
+            ..Navigation::new(world)
+        };
+        //cached paths may use closed lifts and runs
+        if layers.len() < graph.layers().len() {
+            navigation.path_cache = None;
+        }
+        let plans = pool.map(&planning, |request| {
+            run_skiier_ai(&layers, request, terrain, navigation, behaviors)
+        });
+        //what is left of each group's plan for once they regroup
+        let rests: HashMap<Entity, GroupPlan> = planning
+            .iter()
+            .zip(plans.iter())
+            .filter_map(|(request, (_, _, score, rest))| {
+                request.group.map(|group| {
+                    (
+                        group,
+                        GroupPlan {
+                            rest: rest.clone(),
+                            score: score.clone(),
+                        },
+                    )
+                })
+            })
+            .collect();
+        let plans: HashMap<Entity, (FollowPath, Vec<DecisionDebugInfo>, UtilityScore)> = planning
+            .iter()
+            .zip(plans)
+            .flat_map(|(request, (path, debug_info, score, _))| {
+                let plan = (path, debug_info, score);
+                request.members.iter().map(move |m| (*m, plan.clone()))
+            })
+            .collect();
+        (plans, rests)
+    };
+    for (entity, plan) in <(Entity, &mut GroupPlan)>::query().iter_mut(world) {
+        if let Some(rest) = rests.remove(entity) {
+            *plan = rest;
+        }
+    }
+    let mut arrived: Vec<Node> = vec![];
+    let mut query = <(
+        Entity,
+        &mut Transform,
+        &mut FollowPath,
+        &mut Vec<DecisionDebugInfo>,
+        &mut Satisfaction,
+        &mut UtilityScore,
+        Option<&mut Needs>,
+    )>::query();
+    for (entity, transform, path, debug_info, satisfaction, score, mut needs) in
+        query.iter_mut(world)
+    {
+        if let Some((t_path, t_debug_info, t_score)) = plans.remove(entity) {
+            //decisions made where the guest already is are carried out right away, the rest
+            //once they are reached while moving
+            if let Some(start) = t_path.tiles().first() {
+                for decision in t_debug_info.iter().take_while(|d| &d.end == start) {
+                    if decision.is_visit() {
+                        arrived.push(decision.end.clone());
+                    }
+                    if let Some(needs) = needs.as_mut() {
+                        needs.meet(&decision.name);
+                    }
+                }
+            }
+            if let Some(t) = path.get().filter(|_| !t_path.is_empty()) {
                 transform.set_translation(t);
             }
+            //the plan they finished counts now that it has been carried out, a new plan with
+            //nothing to do leaves them stuck right away
+            if let Some(change) = satisfaction_change(debug_info) {
+                satisfaction.change(change);
+            }
+            if satisfaction_change(&t_debug_info).is_none() {
+                satisfaction.change(Satisfaction::STUCK);
+            }
+            *path = t_path;
+            *debug_info = t_debug_info;
+            *score = t_score;
         }
     }
+    let paid = charge_visits(world, &arrived);
+    if let Some(finances) = <&mut Finances>::query().iter_mut(world).next() {
+        finances.cash += paid;
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::building::push_building;
+    use crate::prelude::{push_terrain, update_navigation_graph};
+    use group::GroupKind;
+    #[test]
+    fn groups_regroup_and_leave_together() {
+        let terrain = Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5);
+        let mut world = World::default();
+        push_terrain(terrain.clone(), &mut world);
+        update_navigation_graph(&mut world);
+        let path = |tiles: &[Vector2<i64>]| {
+            FollowPath::new(
+                Path::new(
+                    tiles
+                        .iter()
+                        .map(|t| (Node { node: *t }, GraphWeight::Some(10)))
+                        .collect(),
+                ),
+                &terrain,
+            )
+        };
+        let run: Vec<Vector2<i64>> = (0..5).rev().map(|x| Vector2::new(x, 4)).collect();
+        let base = Vector2::new(0, 4);
+        let members: Vec<Entity> = [Difficulty::Green, Difficulty::DoubleBlack]
+            .iter()
+            .map(|max_difficulty| {
+                let skill = Skill {
+                    max_difficulty: *max_difficulty,
+                };
+                spawn_skiier(&mut world, run[0], skill, Personality::default())
+            })
+            .collect();
+        let (slow, fast) = (members[0], members[1]);
+        let group = world.push((
+            Group {
+                kind: GroupKind::Club,
+                members: members.clone(),
+            },
+            GroupPlan {
+                rest: vec![Decision {
+                    cost: Number::Finite(0.0),
+                    name: Exit::NAME.to_string(),
+                    path: path(&[base]),
+                    endpoint: Node { node: base },
+                }],
+                score: UtilityScore::default(),
+            },
+        ));
+        for member in members.iter() {
+            let mut entry = world.entry(*member).unwrap();
+            entry.add_component(GroupMember { group });
+            *entry.get_component_mut::<FollowPath>().unwrap() = path(&run);
+            *entry.get_component_mut::<Vec<DecisionDebugInfo>>().unwrap() =
+                vec![DecisionDebugInfo {
+                    name: "Down".to_string(),
+                    cost: Number::Finite(0.0),
+                    start: Node { node: run[0] },
+                    end: Node { node: base },
+                    path_len: run.len(),
+                }];
+        }
+        let mut schedule = Schedule::builder().add_system(follow_path_system()).build();
+        let mut resources = Resources::default();
+        resources.insert(WorkerPool::Serial);
+        resources.insert(BehaviorLibrary::default());
+        let at_end = |world: &World, member: Entity| {
+            world
+                .entry_ref(member)
+                .unwrap()
+                .get_component::<FollowPath>()
+                .unwrap()
+                .at_end()
+        };
+        let mut waited = false;
+        for _ in 0..1000 {
+            if world.entry_ref(group).is_err() {
+                break;
+            }
+            schedule.execute(&mut world, &mut resources);
+            //the faster member waits at the base for the slower one
+            if world.entry_ref(slow).is_ok() && at_end(&world, fast) && !at_end(&world, slow) {
+                waited = true;
+            }
+        }
+        assert!(waited);
+        //the group heads to the exit together once the slow member catches up
+        assert!(world.entry_ref(group).is_err());
+        assert!(world.entry_ref(slow).is_err());
+        assert!(world.entry_ref(fast).is_err());
+    }
+    #[test]
+    fn groups_drop_members_who_are_gone() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5),
+            &mut world,
+        );
+        update_navigation_graph(&mut world);
+        let skill = Skill {
+            max_difficulty: Difficulty::Blue,
+        };
+        let base = Vector2::new(0, 4);
+        let members: Vec<Entity> = (0..3)
+            .map(|_| spawn_skiier(&mut world, base, skill, Personality::default()))
+            .collect();
+        let group = world.push((
+            Group {
+                kind: GroupKind::Club,
+                members: members.clone(),
+            },
+            GroupPlan::default(),
+        ));
+        for member in members.iter() {
+            world
+                .entry(*member)
+                .unwrap()
+                .add_component(GroupMember { group });
+        }
+        world.remove(members[2]);
+        let mut schedule = Schedule::builder().add_system(follow_path_system()).build();
+        let mut resources = Resources::default();
+        resources.insert(WorkerPool::Serial);
+        resources.insert(BehaviorLibrary::default());
+        for _ in 0..20 {
+            schedule.execute(&mut world, &mut resources);
+        }
+        //the rest of the group stop waiting and plan, finding nothing to do
+        {
+            let entry = world.entry_ref(group).unwrap();
+            assert_eq!(
+                entry.get_component::<Group>().unwrap().members,
+                members[..2]
+            );
+            let entry = world.entry_ref(members[0]).unwrap();
+            assert!(
+                entry.get_component::<Satisfaction>().unwrap().value
+                    < Satisfaction::default().value
+            );
+        }
+        //groups with nobody left are removed
+        world.remove(members[0]);
+        world.remove(members[1]);
+        schedule.execute(&mut world, &mut resources);
+        assert!(world.entry_ref(group).is_err());
+    }
+    #[test]
+    fn visits_are_charged_on_arrival() {
+        let terrain = Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5);
+        let mut world = World::default();
+        push_terrain(terrain.clone(), &mut world);
+        update_navigation_graph(&mut world);
+        let entrance = Vector2::new(0, 4);
+        let lodge = push_building(&mut world, BuildingKind::Lodge, entrance);
+        let skill = Skill {
+            max_difficulty: Difficulty::Blue,
+        };
+        let skiier = spawn_skiier(
+            &mut world,
+            Vector2::new(4, 4),
+            skill,
+            Personality::default(),
+        );
+        {
+            let tiles: Vec<(Node, GraphWeight)> = (0..5)
+                .rev()
+                .map(|x| {
+                    (
+                        Node {
+                            node: Vector2::new(x, 4),
+                        },
+                        GraphWeight::Some(10),
+                    )
+                })
+                .collect();
+            let mut entry = world.entry(skiier).unwrap();
+            *entry.get_component_mut::<FollowPath>().unwrap() =
+                FollowPath::new(Path::new(tiles), &terrain);
+            *entry.get_component_mut::<Vec<DecisionDebugInfo>>().unwrap() =
+                vec![DecisionDebugInfo {
+                    name: Need::Eat.to_string(),
+                    cost: Number::Finite(0.0),
+                    start: Node {
+                        node: Vector2::new(4, 4),
+                    },
+                    end: Node { node: entrance },
+                    path_len: 5,
+                }];
+        }
+        let mut schedule = Schedule::builder().add_system(follow_path_system()).build();
+        let mut resources = Resources::default();
+        resources.insert(WorkerPool::Serial);
+        resources.insert(BehaviorLibrary::default());
+        let visits = |world: &World| {
+            world
+                .entry_ref(lodge)
+                .unwrap()
+                .get_component::<BuildingStatistics>()
+                .unwrap()
+                .visits
+        };
+        //nothing is charged for a plan that has only just started
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(visits(&world), 0);
+        for _ in 0..500 {
+            schedule.execute(&mut world, &mut resources);
+        }
+        assert_eq!(visits(&world), 1);
+    }
+    #[test]
+    fn runs_count_once_skied() {
+        let terrain = Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5);
+        let run = [Vector2::new(3, 4), Vector2::new(2, 4), Vector2::new(1, 4)];
+        let trail = TrailLayer::new("Run 1".to_string(), Difficulty::Green, &run, &terrain);
+        let tiles: Vec<Node> = [
+            Vector2::new(4, 4),
+            Vector2::new(3, 4),
+            Vector2::new(2, 4),
+            Vector2::new(1, 4),
+            Vector2::new(0, 4),
+        ]
+        .iter()
+        .map(|t| Node { node: *t })
+        .collect();
+        let runs = |from, to| finished_runs(&tiles, from, to, &[&trail], &terrain);
+        //still on the run
+        assert!(runs(0, 2).is_empty());
+        assert_eq!(runs(2, 3), vec!["Run 1".to_string()]);
+        assert!(runs(3, 4).is_empty());
+        //a plan that stops partway down only counts at the bottom of the run
+        assert!(finished_runs(&tiles[..3], 0, 2, &[&trail], &terrain).is_empty());
+        assert_eq!(
+            finished_runs(&tiles[..4], 0, 3, &[&trail], &terrain),
+            vec!["Run 1".to_string()]
+        );
+    }
 }
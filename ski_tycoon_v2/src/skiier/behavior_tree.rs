@@ -1,8 +1,29 @@
 use super::{
-    super::prelude::{a_star, GraphWeight, Path},
+    super::prelude::{euclidean_heuristic, smooth_path, theta_star, GraphWeight, Path},
     FollowPath, GraphLayerList, Node, Terrain,
 };
-use log::error;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::Arc;
+mod actions;
+mod tree_file;
+use crate::prelude::Need;
+pub use actions::Exit;
+use actions::{Rest, Sightsee, SkiRun, Visit};
+pub use tree_file::{parse_behavior_tree, BehaviorError, BehaviorTree};
+use tree_file::{BehaviorNode, Condition, Decorator};
+/// Directory next to the executable that behavior trees are loaded from on native platforms
+#[cfg(not(target_arch = "wasm32"))]
+pub const BEHAVIOR_DIRECTORY: &str = "behaviors";
+/// Environment variable that overrides the behavior directory
+#[cfg(not(target_arch = "wasm32"))]
+pub const BEHAVIOR_DIRECTORY_VARIABLE: &str = "SKI_TYCOON_BEHAVIORS";
+/// Behavior trees compiled into the game, used when the behavior directory is not available
+const BUILTIN_TREES: &[(&str, &str)] = &[
+    ("beginner.ron", include_str!("behaviors/beginner.ron")),
+    ("day_trip.ron", include_str!("behaviors/day_trip.ron")),
+    ("default.ron", include_str!("behaviors/default.ron")),
+];
 #[derive(Clone, Debug, PartialEq)]
 pub struct Decision {
     pub cost: Number<f32>,
@@ -49,42 +70,21 @@ impl<T: std::cmp::PartialOrd> std::cmp::PartialOrd for Number<T> {
         }
     }
 }
+/// Whether the decision named `name` rides a lift from its base
+pub fn rides_lift(name: &str) -> bool {
+    name == "Up" || name == "Sightsee"
+}
+/// An action a skiier can take, looked up by name from behavior tree files
 pub trait TreeNode {
     fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision;
-    fn children(&self) -> Vec<Box<dyn TreeNode>>;
     fn name(&self) -> String;
-    fn best_path(
-        &self,
-        search_length: usize,
-        layers: &GraphLayerList,
-        position: Node,
-        terrain: &Terrain,
-    ) -> Vec<Decision> {
-        if search_length == 0 {
-            vec![self.cost(layers, position, terrain)]
+    /// Every way of carrying out the action, by default only the cheapest
+    fn options(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Vec<Decision> {
+        let decision = self.cost(layers, position, terrain);
+        if decision.cost == Number::Infinite {
+            vec![]
         } else {
-            let self_cost = self.cost(layers, position, terrain);
-            let mut best_weight = Number::Infinite;
-            let mut best_path = vec![];
-            for child in self.children().iter() {
-                let mut child_path = child.best_path(
-                    search_length - 1,
-                    layers,
-                    self_cost.clone().endpoint.clone(),
-                    terrain,
-                );
-
-                let child_weight = child_path
-                    .iter()
-                    .fold(Number::Finite(0.0), |acc, d| acc + d.cost.clone());
-
-                if child_weight <= best_weight {
-                    best_path = vec![self_cost.clone()];
-                    best_path.append(&mut child_path);
-                    best_weight = child_weight;
-                }
-            }
-            best_path
+            vec![decision]
         }
     }
 }
@@ -130,155 +130,532 @@ impl TreeNode for Up {
             name: self.name(),
         }
     }
-    fn children(&self) -> Vec<Box<dyn TreeNode>> {
-        vec![Box::new(Up {}), Box::new(Down {})]
-    }
 }
-pub struct Down {}
-impl Down {
-    fn heuristic(start: &Node, end: &Node, _graph: &GraphLayerList) -> GraphWeight {
-        GraphWeight::Some(
-            ((end.node.x - start.node.x).abs() + (end.node.y - start.node.y).abs()) as i32,
+fn heuristic(start: &Node, end: &Node, _graph: &GraphLayerList) -> GraphWeight {
+    euclidean_heuristic(start, end)
+}
+/// Path from `position` to `target` along with its cost, none if `target` can not be reached
+fn path_to(layers: &GraphLayerList, position: &Node, target: &Node) -> Option<(i32, Path)> {
+    let search = || {
+        theta_star(
+            position,
+            target,
+            layers,
+            Box::new(heuristic),
+            layers.search_budget(),
         )
+    };
+    //crowds change every tick so skiiers avoiding them always search. Otherwise flow fields are
+    //shared by every skiier heading to the same place, large maps without one use the path
+    //hierarchy. Both only move in the grid directions so their paths are pulled tight.
+    let found = if layers.congestion().is_some() {
+        search()
+    } else {
+        layers.cached_path(position, target, || {
+            match (layers.flow_field(target), layers.hierarchy()) {
+                (Some(field), _) => field
+                    .path(layers, position)
+                    .map(|path| smooth_path(path, layers)),
+                (None, Some(hierarchy)) => hierarchy
+                    .find_path(layers, position, target)
+                    .map(|path| smooth_path(path, layers)),
+                (None, None) => search(),
+            }
+        })
+    };
+    let path = match found {
+        Ok(path) => path,
+        Err(e) => {
+            debug!("no path from {} to {}: {}", position, target, e);
+            return None;
+        }
+    };
+    let cost: GraphWeight = path.path.iter().map(|(_, weight)| weight.clone()).sum();
+    match cost {
+        GraphWeight::Some(n) => Some((n, path)),
+        GraphWeight::Infinity => None,
     }
 }
-impl TreeNode for Down {
-    fn name(&self) -> String {
-        "Down".to_string()
-    }
-    fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
-        let lift_list = layers.find_lifts();
-        let (cost, best_path) = lift_list
+pub struct Down {}
+impl Down {
+    /// Paths to every lift the skiier can reach along with their costs, in the order of the
+    /// lifts
+    fn paths_to_lifts(&self, layers: &GraphLayerList, position: &Node) -> Vec<(i32, Path)> {
+        layers
+            .find_lifts()
             .iter()
-            .map(|lift| {
-                let path_to_lift =
-                    a_star(&position, &lift.start, layers, Box::new(Self::heuristic));
-                let path_cost: GraphWeight = path_to_lift
-                    .path
-                    .iter()
-                    .map(|(_, weight)| weight.clone())
-                    .sum();
-                let total_cost = match path_cost {
-                    GraphWeight::Infinity => {
-                        error!("invalid graph weight");
-                        panic!()
-                    }
-                    GraphWeight::Some(n) => n,
-                };
-                (total_cost, path_to_lift)
-            })
-            .fold(
-                (Number::Infinite, Path::default()),
-                |acc, (x_num, x_data)| {
-                    if acc.0 > Number::Finite(x_num as f32) {
-                        (Number::Finite(x_num as f32), x_data)
-                    } else {
-                        acc
-                    }
-                },
-            );
+            .filter_map(|lift| path_to(layers, position, &lift.start))
+            .collect()
+    }
+    fn decision(
+        &self,
+        cost: Number<f32>,
+        path: Path,
+        position: Node,
+        terrain: &Terrain,
+    ) -> Decision {
         Decision {
-            cost: if best_path.len() > 1 {
+            cost: if path.len() > 1 {
                 cost
             } else {
                 Number::Infinite
             },
-            endpoint: if let Some(point) = best_path.endpoint() {
+            endpoint: if let Some(point) = path.endpoint() {
                 point.clone()
             } else {
                 position
             },
-            path: FollowPath::new(best_path, terrain),
+            path: FollowPath::new(path, terrain),
             name: self.name(),
         }
     }
-    fn children(&self) -> Vec<Box<dyn TreeNode>> {
-        vec![Box::new(Up {}), Box::new(Down {})]
+}
+impl TreeNode for Down {
+    fn name(&self) -> String {
+        "Down".to_string()
+    }
+    fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
+        let (cost, best_path) = self.paths_to_lifts(layers, &position).into_iter().fold(
+            (Number::Infinite, Path::default()),
+            |acc, (x_num, x_data)| {
+                if acc.0 > Number::Finite(x_num as f32) {
+                    (Number::Finite(x_num as f32), x_data)
+                } else {
+                    acc
+                }
+            },
+        );
+        self.decision(cost, best_path, position, terrain)
+    }
+    /// One option for each lift the skiier can reach
+    fn options(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Vec<Decision> {
+        self.paths_to_lifts(layers, &position)
+            .into_iter()
+            .map(|(cost, path)| {
+                self.decision(Number::Finite(cost as f32), path, position.clone(), terrain)
+            })
+            .filter(|d| d.cost != Number::Infinite)
+            .collect()
     }
 }
-pub struct SearchStart {}
-impl Default for SearchStart {
+/// Actions behavior trees can use, by name
+pub struct Registry {
+    nodes: HashMap<String, Box<dyn TreeNode + Send + Sync>>,
+}
+impl Default for Registry {
     fn default() -> Self {
-        SearchStart {}
+        let mut registry = Self {
+            nodes: HashMap::new(),
+        };
+        registry.register(Box::new(Up {}));
+        registry.register(Box::new(Down {}));
+        for need in Need::ALL.iter() {
+            registry.register(Box::new(Visit { need: *need }));
+        }
+        registry.register(Box::new(Rest {}));
+        registry.register(Box::new(SkiRun { run: None }));
+        registry.register(Box::new(Sightsee {}));
+        registry.register(Box::new(Exit {}));
+        registry
     }
 }
-impl TreeNode for SearchStart {
-    fn name(&self) -> String {
-        "Search Start".to_string()
+impl Registry {
+    /// Adds an action, replacing any action with the same name
+    pub fn register(&mut self, node: Box<dyn TreeNode + Send + Sync>) {
+        self.nodes.insert(node.name(), node);
     }
-    fn cost(&self, _layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
-        Decision {
+    pub fn get(&self, name: &str) -> Option<&(dyn TreeNode + Send + Sync)> {
+        self.nodes.get(name).map(|n| n.as_ref())
+    }
+}
+/// One way of carrying out a tree or part of one
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub decisions: Vec<Decision>,
+    pub cost: Number<f32>,
+    pub endpoint: Node,
+}
+impl Plan {
+    /// Nothing done yet
+    fn start(position: Node) -> Self {
+        Self {
+            decisions: vec![],
             cost: Number::Finite(0.0),
             endpoint: position,
-            path: FollowPath::new(Path::default(), terrain),
-            name: self.name(),
         }
     }
-    fn children(&self) -> Vec<Box<dyn TreeNode>> {
-        vec![Box::new(Up {}), Box::new(Down {})]
+    fn then(&self, next: Self) -> Self {
+        let mut decisions = self.decisions.clone();
+        decisions.extend(next.decisions);
+        Self {
+            decisions,
+            cost: self.cost.clone() + next.cost,
+            endpoint: next.endpoint,
+        }
     }
 }
-#[cfg(test)]
-mod test {
-    use super::super::super::prelude::Path;
-    use super::*;
-    use nalgebra::Vector2;
-    struct A {}
-    impl TreeNode for A {
-        fn name(&self) -> String {
-            "A".to_string()
+/// Everything a tree needs to look at while planning
+struct Planner<'a, 'b> {
+    registry: &'a Registry,
+    layers: &'a GraphLayerList<'b>,
+    terrain: &'a Terrain,
+}
+impl<'a, 'b> Planner<'a, 'b> {
+    /// Most partial plans kept after each step of a sequence, the cheapest are kept
+    const BEAM_WIDTH: usize = 16;
+    fn holds(&self, condition: &Condition, position: &Node) -> bool {
+        match condition {
+            Condition::AtLiftBase => self
+                .layers
+                .find_lifts()
+                .iter()
+                .any(|lift| &lift.start == position),
+            Condition::OnTrail => self
+                .layers
+                .find_trails()
+                .iter()
+                .any(|trail| trail.contains(position)),
         }
-        fn cost(&self, _layers: &GraphLayerList, position: Node) -> Decision {
-            Decision {
-                cost: Number::Finite(5.0),
-                path: FollowPath::new(Path { path: vec![] }),
-                endpoint: position,
-                name: self.name(),
+    }
+    /// Every way `action` can be carried out starting at `position`
+    fn action(&self, action: &dyn TreeNode, position: Node) -> Vec<Plan> {
+        action
+            .options(self.layers, position, self.terrain)
+            .into_iter()
+            .map(|decision| Plan {
+                cost: decision.cost.clone(),
+                endpoint: decision.endpoint.clone(),
+                decisions: vec![decision],
+            })
+            .collect()
+    }
+    /// Every way `node` can succeed starting at `position`
+    fn plans(&self, node: &BehaviorNode, position: Node) -> Vec<Plan> {
+        match node {
+            BehaviorNode::Action(name) => match self.registry.get(name) {
+                Some(action) => self.action(action, position),
+                None => vec![],
+            },
+            BehaviorNode::Run(name) => self.action(
+                &SkiRun {
+                    run: Some(name.clone()),
+                },
+                position,
+            ),
+            BehaviorNode::Condition(condition) => {
+                if self.holds(condition, &position) {
+                    vec![Plan::start(position)]
+                } else {
+                    vec![]
+                }
+            }
+            BehaviorNode::Sequence(children) => self.sequence(children.iter().collect(), position),
+            BehaviorNode::Selector(children) => children
+                .iter()
+                .flat_map(|child| self.plans(child, position.clone()))
+                .collect(),
+            BehaviorNode::Decorator(decorator, child) => match decorator {
+                Decorator::Repeat(times) => self.sequence(vec![child.as_ref(); *times], position),
+                Decorator::Weight(weight) => self
+                    .plans(child, position)
+                    .into_iter()
+                    .map(|mut plan| {
+                        if let Number::Finite(cost) = plan.cost {
+                            plan.cost = Number::Finite(cost * weight);
+                        }
+                        plan
+                    })
+                    .collect(),
+                Decorator::Invert => {
+                    if self.plans(child, position.clone()).is_empty() {
+                        vec![Plan::start(position)]
+                    } else {
+                        vec![]
+                    }
+                }
+            },
+        }
+    }
+    fn sequence(&self, children: Vec<&BehaviorNode>, position: Node) -> Vec<Plan> {
+        let mut partial = vec![Plan::start(position)];
+        for child in children.iter() {
+            partial = partial
+                .iter()
+                .flat_map(|before| {
+                    self.plans(child, before.endpoint.clone())
+                        .into_iter()
+                        .map(move |after| before.then(after))
+                })
+                .collect();
+            if partial.len() > Self::BEAM_WIDTH {
+                partial.sort_by(|a, b| {
+                    a.cost
+                        .partial_cmp(&b.cost)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                partial.truncate(Self::BEAM_WIDTH);
             }
         }
-        fn children(&self) -> Vec<Box<dyn TreeNode>> {
-            vec![Box::new(A {}), Box::new(B {})]
+        partial
+    }
+}
+impl BehaviorTree {
+    /// Every plan the tree allows from `position`. Long sequences only keep their cheapest
+    /// plans. Empty when nothing in the tree can succeed.
+    pub fn plans(
+        &self,
+        registry: &Registry,
+        layers: &GraphLayerList,
+        position: Node,
+        terrain: &Terrain,
+    ) -> Vec<Plan> {
+        let planner = Planner {
+            registry,
+            layers,
+            terrain,
+        };
+        planner.plans(&self.root, position)
+    }
+}
+/// Behavior trees skiiers can follow along with the actions they use. Cheap to clone so it can
+/// be handed to each tick of the simulation.
+#[derive(Clone)]
+pub struct BehaviorLibrary {
+    registry: Arc<Registry>,
+    trees: Arc<Vec<BehaviorTree>>,
+}
+impl Default for BehaviorLibrary {
+    /// Loads the builtin trees. panics if one of them is invalid
+    fn default() -> Self {
+        let registry = Registry::default();
+        let trees = BUILTIN_TREES
+            .iter()
+            .map(|(file, source)| {
+                parse_behavior_tree(file, source, &registry)
+                    .unwrap_or_else(|errors| panic!("invalid builtin behavior tree: {}", errors[0]))
+            })
+            .collect();
+        Self {
+            registry: Arc::new(registry),
+            trees: Arc::new(trees),
         }
     }
-    impl TreeNode for B {
+}
+impl BehaviorLibrary {
+    /// Tree skiiers without an itinerary follow, and guests once their own tree has nothing left
+    pub const DEFAULT_TREE: &'static str = "Default";
+    /// Tree guests arriving for the day follow
+    pub const DAY_TRIP_TREE: &'static str = "Day Trip";
+    /// Tree green skiiers follow while there is a ski school open
+    pub const BEGINNER_TREE: &'static str = "Beginner";
+    /// Loads every valid `.ron` file in `directory` in alphabetical order, along with the errors
+    /// in each file that could not be loaded and an error if there is no default tree
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_directory(directory: &std::path::Path) -> (Self, Vec<BehaviorError>) {
+        let read_error = |error: std::io::Error| BehaviorError::Io {
+            file: directory.display().to_string(),
+            error: error.to_string(),
+        };
+        let paths = std::fs::read_dir(directory)
+            .map_err(read_error)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(read_error)
+            });
+        let mut errors = vec![];
+        let mut paths = match paths {
+            Ok(paths) => paths,
+            Err(e) => {
+                errors.push(e);
+                vec![]
+            }
+        };
+        paths.retain(|p| p.extension().map(|e| e == "ron").unwrap_or(false));
+        paths.sort();
+        let registry = Registry::default();
+        let mut trees = vec![];
+        for path in paths.iter() {
+            let file = path.display().to_string();
+            let tree = std::fs::read_to_string(path)
+                .map_err(|e| {
+                    vec![BehaviorError::Io {
+                        file: file.clone(),
+                        error: e.to_string(),
+                    }]
+                })
+                .and_then(|source| parse_behavior_tree(&file, &source, &registry));
+            match tree {
+                Ok(t) => trees.push(t),
+                Err(e) => errors.extend(e),
+            }
+        }
+        if !trees.iter().any(|t| t.name == Self::DEFAULT_TREE) {
+            errors.push(BehaviorError::InvalidNode {
+                file: directory.display().to_string(),
+                location: "name".to_string(),
+                reason: format!("no tree named \"{}\"", Self::DEFAULT_TREE),
+            });
+        }
+        (
+            Self {
+                registry: Arc::new(registry),
+                trees: Arc::new(trees),
+            },
+            errors,
+        )
+    }
+    pub fn tree(&self, name: &str) -> Option<&BehaviorTree> {
+        self.trees.iter().find(|t| t.name == name)
+    }
+    /// Plans the tree named `name` allows, there are none if there is no such tree
+    pub fn plans(
+        &self,
+        name: &str,
+        layers: &GraphLayerList,
+        position: Node,
+        terrain: &Terrain,
+    ) -> Vec<Plan> {
+        match self.tree(name) {
+            Some(tree) => tree.plans(&self.registry, layers, position, terrain),
+            None => vec![],
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::super::utility::{Needs, Personality, Scorer};
+    use super::super::Skill;
+    use super::*;
+    use crate::terrain::Difficulty;
+    use nalgebra::Vector2;
+    /// Moves one tile along x for a fixed cost
+    struct Step {
+        name: &'static str,
+        cost: f32,
+    }
+    impl TreeNode for Step {
         fn name(&self) -> String {
-            "B".to_string()
+            self.name.to_string()
         }
-        fn cost(&self, _layers: &GraphLayerList, position: Node) -> Decision {
+        fn cost(&self, _layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
             Decision {
-                cost: Number::Finite(15.0),
-                path: FollowPath::new(Path { path: vec![] }),
-                endpoint: position,
+                cost: Number::Finite(self.cost),
+                path: FollowPath::new(Path::default(), terrain),
+                endpoint: Node {
+                    node: position.node + Vector2::new(1, 0),
+                },
                 name: self.name(),
             }
         }
-        fn children(&self) -> Vec<Box<dyn TreeNode>> {
-            vec![Box::new(A {}), Box::new(B {})]
-        }
     }
-    struct B {}
-    #[test]
-    fn simple_create() {
-        let a = A {};
-        let p = a.best_path(
-            4,
-            &GraphLayerList::new(vec![]),
+    fn registry() -> Registry {
+        let mut registry = Registry::default();
+        registry.register(Box::new(Step {
+            name: "A",
+            cost: 5.0,
+        }));
+        registry.register(Box::new(Step {
+            name: "B",
+            cost: 15.0,
+        }));
+        registry
+    }
+    fn plan(source: &str) -> Vec<String> {
+        let registry = registry();
+        let tree = parse_behavior_tree("test.ron", source, &registry).unwrap();
+        let terrain = Terrain::new_cone(Vector2::new(4, 4), Vector2::new(0.0, 0.0), 0.0, 0.0);
+        let layers = GraphLayerList::new(vec![]);
+        let plans = tree.plans(
+            &registry,
+            &layers,
             Node {
                 node: Vector2::new(0, 0),
             },
+            &terrain,
         );
-        for i in 0..4 {
-            assert_eq!(
-                p[i],
-                Decision {
-                    cost: Number::Finite(5.0),
-                    path: FollowPath::new(Path { path: vec![] }),
-                    endpoint: Node {
-                        node: Vector2::new(0, 0),
-                    },
-                    name: "A".to_string(),
-                }
-            );
+        //picked the same way skiiers pick, plans only differ in cost
+        let scorer = Scorer {
+            layers: &layers,
+            occupancy: None,
+            skill: Skill {
+                max_difficulty: Difficulty::Blue,
+            },
+            personality: Personality::default(),
+            satisfaction: 0.5,
+            needs: Needs::default(),
+        };
+        scorer
+            .choose(plans)
+            .map(|(plan, _)| plan.decisions.iter().map(|d| d.name.clone()).collect())
+            .unwrap_or_default()
+    }
+    #[test]
+    fn cheapest_plan() {
+        assert_eq!(
+            plan(
+                r#"BehaviorTree(
+                    name: "Test",
+                    root: Decorator(Repeat(3), Selector([Action("A"), Action("B")])),
+                )"#
+            ),
+            vec!["A", "A", "A"]
+        );
+        //weighting makes the cheaper action less attractive
+        assert_eq!(
+            plan(
+                r#"BehaviorTree(
+                    name: "Test",
+                    root: Selector([Decorator(Weight(4.0), Action("A")), Action("B")]),
+                )"#
+            ),
+            vec!["B"]
+        );
+    }
+    #[test]
+    fn conditions() {
+        //there are no lifts so the sequence fails and only the other branch is left
+        assert_eq!(
+            plan(
+                r#"BehaviorTree(
+                    name: "Test",
+                    root: Selector([
+                        Sequence([Condition(AtLiftBase), Action("A")]),
+                        Sequence([Decorator(Invert, Condition(OnTrail)), Action("B"), Action("B")]),
+                    ]),
+                )"#
+            ),
+            vec!["B", "B"]
+        );
+        //the builtin tree has nowhere to go without lifts
+        assert!(plan(include_str!("behaviors/default.ron")).is_empty());
+    }
+    #[test]
+    fn builtin_trees() {
+        let library = BehaviorLibrary::default();
+        assert!(library.tree(BehaviorLibrary::DEFAULT_TREE).is_some());
+        assert!(library.tree(BehaviorLibrary::DAY_TRIP_TREE).is_some());
+        assert!(library.tree(BehaviorLibrary::BEGINNER_TREE).is_some());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn invalid_trees_are_skipped() {
+        let directory = std::env::temp_dir().join("ski_tycoon_invalid_trees_are_skipped");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("default.ron"),
+            include_str!("behaviors/default.ron"),
+        )
+        .unwrap();
+        std::fs::write(directory.join("bad.ron"), "BehaviorTree(name: ").unwrap();
+        let (library, errors) = BehaviorLibrary::from_directory(&directory);
+        std::fs::remove_dir_all(&directory).ok();
+        assert!(library.tree(BehaviorLibrary::DEFAULT_TREE).is_some());
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            BehaviorError::Parse { file, .. } => assert!(file.ends_with("bad.ron")),
+            _ => panic!("expected parse error"),
         }
     }
     #[test]
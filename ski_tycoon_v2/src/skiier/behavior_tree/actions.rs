@@ -0,0 +1,276 @@
+use super::{path_to, Decision, Number, TreeNode};
+use crate::prelude::{
+    BuildingKind, FollowPath, GraphLayerList, GraphWeight, Need, Node, Path, Terrain,
+};
+use std::cmp::Ordering;
+/// Decision that can not be carried out
+fn impossible(name: String, position: Node, terrain: &Terrain) -> Decision {
+    Decision {
+        cost: Number::Infinite,
+        path: FollowPath::new(Path::default(), terrain),
+        endpoint: position,
+        name,
+    }
+}
+/// Cheapest of `options`, the first one wins ties
+fn cheapest(options: Vec<Decision>) -> Option<Decision> {
+    options.into_iter().fold(None, |best, option| match best {
+        Some(best) if best.cost <= option.cost => Some(best),
+        _ => Some(option),
+    })
+}
+/// Path that stays on `position`
+fn stay(position: &Node) -> Path {
+    Path::new(vec![(position.clone(), GraphWeight::Some(0))])
+}
+/// Orders tiles by height and then by position so ties are broken the same way every time
+fn compare_height(a: &Node, b: &Node, terrain: &Terrain) -> Ordering {
+    let height = |n: &Node| terrain.get_transform(&n.node).map(|t| t.y).unwrap_or(0.0);
+    height(a)
+        .partial_cmp(&height(b))
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| (a.node.x, a.node.y).cmp(&(b.node.x, b.node.y)))
+}
+/// Walks to a building that takes care of `need` and spends some time inside. One option for
+/// each building the skiier can reach.
+pub struct Visit {
+    pub need: Need,
+}
+impl Visit {
+    /// Cost of the time spent inside on top of getting there
+    const COST: f32 = 10.0;
+    /// Ticks spent inside
+    const TICKS: u32 = 60;
+}
+impl TreeNode for Visit {
+    fn name(&self) -> String {
+        self.need.to_string()
+    }
+    fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
+        cheapest(self.options(layers, position.clone(), terrain))
+            .unwrap_or_else(|| impossible(self.name(), position, terrain))
+    }
+    fn options(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Vec<Decision> {
+        layers
+            .find_buildings()
+            .iter()
+            .filter(|building| building.kind.serves(self.need))
+            .filter_map(|building| path_to(layers, &position, &building.entrance))
+            .map(|(cost, path)| Decision {
+                cost: Number::Finite(cost as f32 + Self::COST),
+                endpoint: path.endpoint().cloned().unwrap_or_else(|| position.clone()),
+                path: FollowPath::new(path, terrain).with_wait(Self::TICKS),
+                name: self.name(),
+            })
+            .collect()
+    }
+}
+/// Stands still for a while to catch their breath
+pub struct Rest {}
+impl Rest {
+    const COST: f32 = 5.0;
+    /// Ticks spent resting
+    const TICKS: u32 = 30;
+}
+impl TreeNode for Rest {
+    fn name(&self) -> String {
+        "Rest".to_string()
+    }
+    fn cost(&self, _layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
+        Decision {
+            cost: Number::Finite(Self::COST),
+            path: FollowPath::new(stay(&position), terrain).with_wait(Self::TICKS),
+            endpoint: position,
+            name: self.name(),
+        }
+    }
+}
+/// Gets to the top of a run and skis it to the bottom. With a run name only that run is
+/// skied, otherwise there is one option for each run the skiier can reach. Runs that are too
+/// hard for the skiier are not in their layers so they are never picked.
+pub struct SkiRun {
+    pub run: Option<String>,
+}
+impl TreeNode for SkiRun {
+    fn name(&self) -> String {
+        match &self.run {
+            Some(run) => format!("Ski {}", run),
+            None => "SkiRun".to_string(),
+        }
+    }
+    fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
+        cheapest(self.options(layers, position.clone(), terrain))
+            .unwrap_or_else(|| impossible(self.name(), position, terrain))
+    }
+    fn options(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Vec<Decision> {
+        layers
+            .find_trails()
+            .iter()
+            .filter(|trail| self.run.as_ref().is_none_or(|run| run == &trail.name))
+            .filter_map(|trail| {
+                let top = trail.tiles().max_by(|a, b| compare_height(a, b, terrain))?;
+                let bottom = trail.tiles().min_by(|a, b| compare_height(a, b, terrain))?;
+                if top == bottom {
+                    return None;
+                }
+                let (to_top, path_to_top) = path_to(layers, &position, top)?;
+                let (down, path_down) = path_to(layers, top, bottom)?;
+                Some(Decision {
+                    cost: Number::Finite((to_top + down) as f32),
+                    endpoint: bottom.clone(),
+                    path: FollowPath::new(path_to_top.append(&path_down), terrain),
+                    name: format!("Ski {}", trail.name),
+                })
+            })
+            .collect()
+    }
+}
+/// Rides a lift from its base to enjoy the view from the top. Costs more than riding the lift
+/// to ski.
+pub struct Sightsee {}
+impl Sightsee {
+    const COST: f32 = 3.0;
+    /// Ticks spent looking around at the top
+    const TICKS: u32 = 40;
+}
+impl TreeNode for Sightsee {
+    fn name(&self) -> String {
+        "Sightsee".to_string()
+    }
+    fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
+        cheapest(self.options(layers, position.clone(), terrain))
+            .unwrap_or_else(|| impossible(self.name(), position, terrain))
+    }
+    fn options(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Vec<Decision> {
+        layers
+            .find_lifts()
+            .iter()
+            .filter(|lift| lift.start == position)
+            .map(|lift| {
+                let path = Path::new(vec![
+                    (lift.start.clone(), GraphWeight::Some(0)),
+                    (lift.end.clone(), GraphWeight::Some(1)),
+                ]);
+                Decision {
+                    cost: Number::Finite(Self::COST),
+                    endpoint: lift.end.clone(),
+                    path: FollowPath::new(path, terrain).with_wait(Self::TICKS),
+                    name: self.name(),
+                }
+            })
+            .collect()
+    }
+}
+/// Heads back to the closest parking lot and leaves. Without a parking lot they leave from the
+/// base of the resort, the bottom of its lowest lift, or from where they are when there are no
+/// lifts.
+pub struct Exit {}
+impl Exit {
+    pub const NAME: &'static str = "Exit";
+}
+impl TreeNode for Exit {
+    fn name(&self) -> String {
+        Self::NAME.to_string()
+    }
+    fn cost(&self, layers: &GraphLayerList, position: Node, terrain: &Terrain) -> Decision {
+        let parking = layers
+            .find_buildings()
+            .iter()
+            .filter(|building| building.kind == BuildingKind::Parking)
+            .filter_map(|building| {
+                path_to(layers, &position, &building.entrance).map(|(cost, path)| Decision {
+                    cost: Number::Finite(cost as f32),
+                    endpoint: building.entrance.clone(),
+                    path: FollowPath::new(path, terrain),
+                    name: self.name(),
+                })
+            })
+            .collect();
+        if let Some(decision) = cheapest(parking) {
+            return decision;
+        }
+        let lifts = layers.find_lifts();
+        let base = lifts
+            .iter()
+            .map(|lift| &lift.start)
+            .min_by(|a, b| compare_height(a, b, terrain))
+            .unwrap_or(&position);
+        match path_to(layers, &position, base) {
+            Some((cost, path)) => Decision {
+                cost: Number::Finite(cost as f32),
+                endpoint: base.clone(),
+                path: FollowPath::new(path, terrain),
+                name: self.name(),
+            },
+            None => impossible(self.name(), position, terrain),
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::Building;
+    use nalgebra::Vector2;
+    #[test]
+    fn visits() {
+        let terrain = Terrain::new_cone(Vector2::new(6, 6), Vector2::new(0.0, 0.0), 0.0, 0.0);
+        let layer = terrain.build_graph();
+        let buildings = [
+            Building {
+                kind: BuildingKind::Lodge,
+                entrance: Node {
+                    node: Vector2::new(4, 0),
+                },
+            },
+            Building {
+                kind: BuildingKind::Restroom,
+                entrance: Node {
+                    node: Vector2::new(1, 0),
+                },
+            },
+        ];
+        let layers = GraphLayerList::new(vec![&layer]).with_buildings(buildings.iter().collect());
+        let start = Node {
+            node: Vector2::new(0, 0),
+        };
+        let eat = Visit { need: Need::Eat };
+        let options = eat.options(&layers, start.clone(), &terrain);
+        //only the lodge serves food
+        assert_eq!(options.len(), 1);
+        assert_eq!(options[0].endpoint, buildings[0].entrance);
+        assert_eq!(options[0].name, "Eat");
+        let mut path = options[0].path.clone();
+        let mut ticks = 0;
+        while !path.at_end() {
+            if !path.wait() {
+                path.incr(0.5);
+            }
+            ticks += 1;
+        }
+        //stays inside for a while
+        assert!(ticks > Visit::TICKS);
+        let rest = Rest {}.cost(&layers, start.clone(), &terrain);
+        assert_eq!(rest.endpoint, start);
+        assert_eq!(rest.cost, Number::Finite(Rest::COST));
+        //leaves from where they are without lifts or parking
+        let exit = Exit {}.cost(&layers, start.clone(), &terrain);
+        assert_eq!(exit.endpoint, start);
+        let parking = Building {
+            kind: BuildingKind::Parking,
+            entrance: Node {
+                node: Vector2::new(0, 3),
+            },
+        };
+        let with_parking = GraphLayerList::new(vec![&layer]).with_buildings(vec![&parking]);
+        let exit = Exit {}.cost(&with_parking, start.clone(), &terrain);
+        assert_eq!(exit.endpoint, parking.entrance);
+        //nothing to ski or ride
+        assert!(SkiRun { run: None }
+            .options(&layers, start.clone(), &terrain)
+            .is_empty());
+        assert_eq!(
+            Sightsee {}.cost(&layers, start, &terrain).cost,
+            Number::Infinite
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use super::Registry;
+use serde::Deserialize;
+/// Checked against where the skiier is when the tree reaches it
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum Condition {
+    /// Skiier is at the bottom of a lift
+    AtLiftBase,
+    /// Skiier is on a run they can ski
+    OnTrail,
+}
+/// Changes how a child node is run
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum Decorator {
+    /// Runs the child this many times in a row
+    Repeat(usize),
+    /// Multiplies the cost of the child, above 1 makes the child less likely to be picked
+    Weight(f32),
+    /// Succeeds without doing anything when the child fails and fails when it succeeds
+    Invert,
+}
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum BehaviorNode {
+    /// Action from the registry, fails when the action has infinite cost
+    Action(String),
+    /// Skis the run with this name, fails when the skiier can not reach it or it is too hard
+    /// for them
+    Run(String),
+    /// Succeeds without doing anything when the condition holds
+    Condition(Condition),
+    /// Runs every child in order, fails if any of them fail
+    Sequence(Vec<BehaviorNode>),
+    /// Runs whichever child leads to the cheapest plan
+    Selector(Vec<BehaviorNode>),
+    Decorator(Decorator, Box<BehaviorNode>),
+}
+/// On disk layout of a behavior tree
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct BehaviorTree {
+    pub name: String,
+    pub(super) root: BehaviorNode,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum BehaviorError {
+    Io {
+        file: String,
+        error: String,
+    },
+    /// File is not valid RON or does not match the behavior tree layout
+    Parse {
+        file: String,
+        error: String,
+    },
+    /// Action that is not in the registry
+    UnknownNode {
+        file: String,
+        location: String,
+        name: String,
+    },
+    /// Node that can never do anything useful
+    InvalidNode {
+        file: String,
+        location: String,
+        reason: String,
+    },
+}
+impl std::fmt::Display for BehaviorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { file, error } => write!(f, "{}: failed to read: {}", file, error),
+            Self::Parse { file, error } => write!(f, "{}: {}", file, error),
+            Self::UnknownNode {
+                file,
+                location,
+                name,
+            } => write!(f, "{}: {}: unknown node \"{}\"", file, location, name),
+            Self::InvalidNode {
+                file,
+                location,
+                reason,
+            } => write!(f, "{}: {}: {}", file, location, reason),
+        }
+    }
+}
+fn check_node(
+    node: &BehaviorNode,
+    location: String,
+    file: &str,
+    registry: &Registry,
+    errors: &mut Vec<BehaviorError>,
+) {
+    let invalid = |reason: &str| BehaviorError::InvalidNode {
+        file: file.to_string(),
+        location: location.clone(),
+        reason: reason.to_string(),
+    };
+    match node {
+        BehaviorNode::Action(name) => {
+            if registry.get(name).is_none() {
+                errors.push(BehaviorError::UnknownNode {
+                    file: file.to_string(),
+                    location: location.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+        BehaviorNode::Run(name) => {
+            if name.trim().is_empty() {
+                errors.push(invalid("run name must not be empty"));
+            }
+        }
+        BehaviorNode::Condition(_) => (),
+        BehaviorNode::Sequence(children) | BehaviorNode::Selector(children) => {
+            if children.is_empty() {
+                errors.push(invalid("must have at least one child"));
+            }
+            for (i, child) in children.iter().enumerate() {
+                check_node(
+                    child,
+                    format!("{}[{}]", location, i),
+                    file,
+                    registry,
+                    errors,
+                );
+            }
+        }
+        BehaviorNode::Decorator(decorator, child) => {
+            match decorator {
+                Decorator::Repeat(0) => errors.push(invalid("must repeat at least once")),
+                Decorator::Weight(w) if !w.is_finite() || *w <= 0.0 => {
+                    errors.push(invalid("weight must be a positive number"))
+                }
+                _ => (),
+            }
+            check_node(child, format!("{}.child", location), file, registry, errors);
+        }
+    }
+}
+impl BehaviorTree {
+    /// Finds every node that is not in the registry or can never do anything useful
+    pub fn validate(&self, file: &str, registry: &Registry) -> Vec<BehaviorError> {
+        let mut errors = vec![];
+        if self.name.trim().is_empty() {
+            errors.push(BehaviorError::InvalidNode {
+                file: file.to_string(),
+                location: "name".to_string(),
+                reason: "must not be empty".to_string(),
+            });
+        }
+        check_node(&self.root, "root".to_string(), file, registry, &mut errors);
+        errors
+    }
+}
+/// Parses a behavior tree and checks that every node is valid
+pub fn parse_behavior_tree(
+    file: &str,
+    source: &str,
+    registry: &Registry,
+) -> Result<BehaviorTree, Vec<BehaviorError>> {
+    let tree: BehaviorTree = ron::de::from_str(source).map_err(|e| {
+        vec![BehaviorError::Parse {
+            file: file.to_string(),
+            error: e.to_string(),
+        }]
+    })?;
+    let errors = tree.validate(file, registry);
+    if errors.is_empty() {
+        Ok(tree)
+    } else {
+        Err(errors)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn unknown_nodes() {
+        let errors = parse_behavior_tree(
+            "tree.ron",
+            r#"BehaviorTree(
+                name: "Test",
+                root: Selector([
+                    Action("Up"),
+                    Decorator(Repeat(2), Action("Jump")),
+                    Sequence([Condition(OnTrail), Action("Dance")]),
+                ]),
+            )"#,
+            &Registry::default(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                BehaviorError::UnknownNode {
+                    file: "tree.ron".to_string(),
+                    location: "root[1].child".to_string(),
+                    name: "Jump".to_string(),
+                },
+                BehaviorError::UnknownNode {
+                    file: "tree.ron".to_string(),
+                    location: "root[2][1]".to_string(),
+                    name: "Dance".to_string(),
+                },
+            ]
+        );
+    }
+    #[test]
+    fn invalid_nodes() {
+        let errors = parse_behavior_tree(
+            "tree.ron",
+            r#"BehaviorTree(
+                name: "Test",
+                root: Sequence([
+                    Decorator(Repeat(0), Action("Down")),
+                    Selector([]),
+                    Run("Bunny Hill"),
+                    Run(""),
+                    Decorator(Weight(0.0), Action("Down")),
+                ]),
+            )"#,
+            &Registry::default(),
+        )
+        .err()
+        .unwrap();
+        let locations: Vec<String> = errors
+            .iter()
+            .map(|e| match e {
+                BehaviorError::InvalidNode { location, .. } => location.clone(),
+                _ => panic!("expected invalid node"),
+            })
+            .collect();
+        assert_eq!(locations, vec!["root[0]", "root[1]", "root[3]", "root[4]"]);
+    }
+    #[test]
+    fn syntax_error() {
+        let errors = parse_behavior_tree("bad.ron", "BehaviorTree(name: ", &Registry::default())
+            .err()
+            .unwrap();
+        match &errors[0] {
+            BehaviorError::Parse { file, .. } => assert_eq!(file, "bad.ron"),
+            _ => panic!("expected parse error"),
+        }
+    }
+}
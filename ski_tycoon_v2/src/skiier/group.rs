@@ -0,0 +1,165 @@
+use super::behavior_tree::Decision;
+use super::utility::UtilityScore;
+use super::{spawn_skiier, Itinerary, Personality, Satisfaction, Skill};
+use crate::rng::SeededRng;
+use crate::terrain::Difficulty;
+use legion::systems::CommandBuffer;
+use legion::world::SubWorld;
+use legion::*;
+use nalgebra::Vector2;
+use std::collections::HashSet;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupKind {
+    /// Parents of any skill along with children who are beginners
+    Family,
+    /// Skiiers of the same skill
+    Club,
+}
+impl GroupKind {
+    /// Chance of an arriving group being a family
+    const FAMILY_CHANCE: f32 = 0.6;
+    pub fn random(rng: &mut SeededRng) -> Self {
+        if rng.next_f32() < Self::FAMILY_CHANCE {
+            Self::Family
+        } else {
+            Self::Club
+        }
+    }
+    /// Smallest and largest number of members, inclusive
+    fn size_range(&self) -> (usize, usize) {
+        match self {
+            Self::Family => (2, 5),
+            Self::Club => (3, 6),
+        }
+    }
+    /// Skills of a new group of `size` skiiers
+    fn skills(&self, size: usize, rng: &mut SeededRng) -> Vec<Skill> {
+        match self {
+            Self::Family => {
+                let parents = size.min(2);
+                (0..size)
+                    .map(|i| {
+                        if i < parents {
+                            Skill::random(rng)
+                        } else {
+                            Skill {
+                                max_difficulty: Difficulty::Green,
+                            }
+                        }
+                    })
+                    .collect()
+            }
+            Self::Club => vec![Skill::random(rng); size],
+        }
+    }
+}
+impl std::fmt::Display for GroupKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Family => write!(f, "family"),
+            Self::Club => write!(f, "ski club"),
+        }
+    }
+}
+/// Guests who ski together. Members only pick terrain every member can ski, wait for each other
+/// at the end of every run and leave together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub kind: GroupKind,
+    /// The first member leads the group and picks where it goes
+    pub members: Vec<Entity>,
+}
+impl Group {
+    /// Skill of the weakest of `skills`
+    pub fn weakest(skills: &[Skill]) -> Option<Skill> {
+        skills.iter().copied().min_by_key(|s| s.max_difficulty)
+    }
+}
+/// What is left of a group's plan while they regroup at the bottom of a run, empty when the
+/// group needs a new plan
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupPlan {
+    pub rest: Vec<Decision>,
+    /// Utility of the whole plan
+    pub score: UtilityScore,
+}
+/// Links a skiier to the entity of their group
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroupMember {
+    pub group: Entity,
+}
+/// Adds a group of at most `max_size` guests at `position` who are here for the day, returning
+/// the number of guests added
+pub fn spawn_group(
+    world: &mut World,
+    position: Vector2<i64>,
+    max_size: usize,
+    rng: &mut SeededRng,
+) -> usize {
+    let kind = GroupKind::random(rng);
+    let (min, max) = kind.size_range();
+    let size = (min + rng.next_below(max - min + 1)).min(max_size);
+    if size == 0 {
+        return 0;
+    }
+    let members: Vec<Entity> = kind
+        .skills(size, rng)
+        .into_iter()
+        .map(|skill| {
+            let personality = Personality::random(rng);
+            spawn_skiier(world, position, skill, personality)
+        })
+        .collect();
+    let group = world.push((
+        Group {
+            kind,
+            members: members.clone(),
+        },
+        GroupPlan::default(),
+    ));
+    for member in members.iter() {
+        if let Some(mut entry) = world.entry(*member) {
+            entry.add_component(GroupMember { group });
+            entry.add_component(Itinerary::day_trip());
+        }
+    }
+    size
+}
+/// Drops members who are no longer skiing from their group so the rest do not wait for them,
+/// and removes groups with nobody left
+pub fn prune_groups(world: &mut SubWorld, commands: &mut CommandBuffer) {
+    let skiiers: HashSet<Entity> = <(Entity, &Skill, &Personality, &Satisfaction)>::query()
+        .iter(world)
+        .map(|(entity, _, _, _)| *entity)
+        .collect();
+    for (entity, group) in <(Entity, &mut Group)>::query().iter_mut(world) {
+        group.members.retain(|member| skiiers.contains(member));
+        if group.members.is_empty() {
+            commands.remove(*entity);
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn weakest() {
+        let skill = |max_difficulty| Skill { max_difficulty };
+        assert_eq!(
+            Group::weakest(&[
+                skill(Difficulty::Black),
+                skill(Difficulty::Green),
+                skill(Difficulty::Blue)
+            ]),
+            Some(skill(Difficulty::Green))
+        );
+        assert_eq!(Group::weakest(&[]), None);
+        //families always bring a beginner when there are children
+        let mut rng = SeededRng::new(2);
+        let skills = GroupKind::Family.skills(4, &mut rng);
+        assert_eq!(Group::weakest(&skills), Some(skill(Difficulty::Green)));
+        //clubs share a skill
+        let skills = GroupKind::Club.skills(3, &mut rng);
+        assert!(skills.iter().all(|s| *s == skills[0]));
+    }
+}
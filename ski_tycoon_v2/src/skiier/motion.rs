@@ -0,0 +1,160 @@
+use super::Skill;
+use crate::prelude::{FollowPath, Terrain};
+use crate::terrain::{Difficulty, TileType};
+use nalgebra::{Vector2, Vector3};
+/// Speed and direction a skiier is moving in, in tiles per tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity {
+    pub velocity: Vector3<f32>,
+}
+impl Default for Velocity {
+    fn default() -> Self {
+        Self {
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+impl Velocity {
+    /// Acceleration due to gravity, in tiles per tick squared
+    const GRAVITY: f32 = 0.01;
+    /// Air drag, slows skiiers down in proportion to the square of their speed
+    const DRAG: f32 = 0.5;
+    /// Speed skiiers can keep up on flat ground by skating and poling
+    const MIN_SPEED: f32 = 0.02;
+    /// Fraction of speed above a skiier's comfortable speed that they scrub off each tick by
+    /// turning
+    const TURN_CONTROL: f32 = 0.5;
+    /// Fraction of a segment travelled per tick when going uphill. Skiiers only go uphill when
+    /// carried by a lift so this is independent of the length of the segment
+    const CARRIED_RATE: f32 = 0.1;
+    pub fn speed(&self) -> f32 {
+        self.velocity.norm()
+    }
+}
+impl Skill {
+    /// Fastest a skiier is comfortable going before turning to slow down
+    fn comfortable_speed(&self) -> f32 {
+        match self.max_difficulty {
+            Difficulty::Green => 0.08,
+            Difficulty::Blue => 0.12,
+            Difficulty::Black => 0.16,
+            Difficulty::DoubleBlack => 0.2,
+        }
+    }
+}
+/// Speed after one tick skiing down a slope in `direction`, a unit vector with y pointing up
+fn next_speed(speed: f32, direction: Vector3<f32>, tile_type: &TileType, skill: &Skill) -> f32 {
+    let sin = -direction.y;
+    let cos = (1.0 - sin * sin).max(0.0).sqrt();
+    let mut speed = speed + Velocity::GRAVITY * (sin - tile_type.friction() * cos)
+        - Velocity::DRAG * speed * speed;
+    let comfortable = skill.comfortable_speed();
+    if speed > comfortable {
+        speed -= (speed - comfortable) * Velocity::TURN_CONTROL;
+    }
+    speed.max(Velocity::MIN_SPEED)
+}
+/// Moves a skiier along their path for one tick. Skiiers speed up on steep pitches, slow down on
+/// flats and turn to stay at a speed they are comfortable with. Skiiers stand still while waiting
+/// at a node.
+pub fn move_along(
+    path: &mut FollowPath,
+    velocity: &mut Velocity,
+    terrain: &Terrain,
+    skill: &Skill,
+) {
+    if path.wait() {
+        *velocity = Velocity::default();
+        return;
+    }
+    let (start, end) = match path.segment() {
+        Some(segment) => segment,
+        None => {
+            path.incr(Velocity::CARRIED_RATE);
+            *velocity = Velocity::default();
+            return;
+        }
+    };
+    let delta = end - start;
+    let length = delta.norm();
+    if delta.y > 0.0 || length <= f32::EPSILON {
+        path.incr((length * Velocity::CARRIED_RATE).max(Velocity::MIN_SPEED));
+        velocity.velocity = delta * Velocity::CARRIED_RATE;
+        return;
+    }
+    let direction = delta / length;
+    let tile = Vector2::new(start.x.round() as i64, start.z.round() as i64);
+    let tile_type = terrain
+        .get_tile(&tile)
+        .map(|t| t.tile_type.clone())
+        .unwrap_or(TileType::Snow);
+    let speed = next_speed(velocity.speed(), direction, &tile_type, skill);
+    path.incr(speed);
+    velocity.velocity = direction * speed;
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{GraphWeight, Node, Path};
+    fn direction(rise: f32) -> Vector3<f32> {
+        Vector3::new(1.0, rise, 0.0).normalize()
+    }
+    #[test]
+    fn speed() {
+        let expert = Skill {
+            max_difficulty: Difficulty::DoubleBlack,
+        };
+        let beginner = Skill {
+            max_difficulty: Difficulty::Green,
+        };
+        let settle = |rise: f32, skill: &Skill| {
+            let mut speed = 0.0;
+            for _ in 0..500 {
+                speed = next_speed(speed, direction(rise), &TileType::Snow, skill);
+            }
+            speed
+        };
+        //steeper pitches are faster
+        assert!(settle(-0.5, &expert) > settle(-0.2, &expert));
+        //on the flat skiiers keep moving by skating
+        assert_eq!(settle(0.0, &expert), Velocity::MIN_SPEED);
+        //beginners turn to control their speed
+        assert!(settle(-0.8, &beginner) < settle(-0.8, &expert));
+        //skiiers accelerate from a stop
+        let first = next_speed(0.0, direction(-0.5), &TileType::Snow, &expert);
+        assert!(next_speed(first, direction(-0.5), &TileType::Snow, &expert) > first);
+        //ice is faster than powder
+        let ice = next_speed(first, direction(-0.5), &TileType::Ice, &expert);
+        assert!(ice > next_speed(first, direction(-0.5), &TileType::Powder, &expert));
+    }
+    #[test]
+    fn advances_by_speed() {
+        let terrain = Terrain::new_cone(Vector2::new(5, 5), Vector2::new(0.0, 0.0), 1.0, -0.5);
+        let path = Path::new(
+            (0..4)
+                .map(|i| {
+                    (
+                        Node {
+                            node: Vector2::new(i, 0),
+                        },
+                        GraphWeight::Some(10),
+                    )
+                })
+                .collect(),
+        );
+        let mut follow = FollowPath::new(path, &terrain);
+        let mut velocity = Velocity::default();
+        let skill = Skill {
+            max_difficulty: Difficulty::Blue,
+        };
+        let mut ticks = 0;
+        while !follow.at_end() {
+            let before = follow.get().unwrap();
+            move_along(&mut follow, &mut velocity, &terrain, &skill);
+            //never moves further than its speed
+            assert!((follow.get().unwrap() - before).norm() <= velocity.speed() + 1e-4);
+            ticks += 1;
+            assert!(ticks < 1000);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use super::{GuestStatistics, Skill};
+use crate::prelude::{Node, Terrain, TrailLayer};
+use crate::terrain::Difficulty;
+use egui::CtxRef;
+use legion::*;
+/// How a guest's skiing has improved since they arrived
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progression {
+    /// Progress towards the next skill level, from 0 to 1
+    pub experience: f32,
+    pub lessons: u32,
+    /// Skill the guest arrived with
+    pub starting_skill: Difficulty,
+    /// Number of times the guest moved up a skill level
+    pub level_ups: u32,
+}
+impl Progression {
+    /// Experience from a lesson
+    const LESSON: f32 = 0.35;
+    /// Experience from each tile skied one level above the skiier's skill
+    const STRETCH_TILE: f32 = 0.02;
+    /// Experience from each tile skied at the skiier's skill
+    const LEVEL_TILE: f32 = 0.002;
+    pub fn new(skill: Skill) -> Self {
+        Self {
+            experience: 0.0,
+            lessons: 0,
+            starting_skill: skill.max_difficulty,
+            level_ups: 0,
+        }
+    }
+    /// Adds experience, moving `skill` up a level once there is enough. Returns whether the
+    /// skiier moved up.
+    pub fn gain(&mut self, skill: &mut Skill, experience: f32) -> bool {
+        self.experience += experience;
+        if self.experience < 1.0 {
+            return false;
+        }
+        match next_level(skill.max_difficulty) {
+            Some(next) => {
+                skill.max_difficulty = next;
+                self.experience = 0.0;
+                self.level_ups += 1;
+                true
+            }
+            None => {
+                self.experience = 1.0;
+                false
+            }
+        }
+    }
+}
+fn next_level(difficulty: Difficulty) -> Option<Difficulty> {
+    let index = Difficulty::ALL.iter().position(|d| *d == difficulty)?;
+    Difficulty::ALL.get(index + 1).copied()
+}
+/// Experience a skiier of `skill` gains from skiing down `tiles` and taking `lessons` lessons.
+/// Tiles on a run count as the run's difficulty, other tiles as the difficulty of their slope.
+pub fn experience(
+    tiles: &[Node],
+    trails: &[&TrailLayer],
+    terrain: &Terrain,
+    skill: &Skill,
+    lessons: usize,
+) -> f32 {
+    let stretch = next_level(skill.max_difficulty);
+    let height = |n: &Node| terrain.get_transform(&n.node).map(|t| t.y);
+    let skied: f32 = tiles
+        .windows(2)
+        .filter(|w| match (height(&w[0]), height(&w[1])) {
+            (Some(a), Some(b)) => b < a,
+            _ => false,
+        })
+        .filter_map(|w| {
+            trails
+                .iter()
+                .find(|t| t.contains(&w[0]) && t.contains(&w[1]))
+                .map(|t| t.difficulty)
+                .or_else(|| {
+                    terrain
+                        .grade(w[0].node, w[1].node)
+                        .map(Difficulty::from_grade)
+                })
+        })
+        .map(|difficulty| {
+            if Some(difficulty) == stretch {
+                Progression::STRETCH_TILE
+            } else if difficulty == skill.max_difficulty {
+                Progression::LEVEL_TILE
+            } else {
+                0.0
+            }
+        })
+        .sum();
+    skied + lessons as f32 * Progression::LESSON
+}
+/// Draws how guests' skill has changed over the season
+pub fn draw_progression(world: &World, context: &mut CtxRef) {
+    let statistics = <&GuestStatistics>::query().iter(world).next().cloned();
+    let mut arrived = [0; 4];
+    let mut now = [0; 4];
+    for (skill, progression) in <(&Skill, &Progression)>::query().iter(world) {
+        let index = |d: Difficulty| Difficulty::ALL.iter().position(|x| *x == d).unwrap_or(0);
+        arrived[index(progression.starting_skill)] += 1;
+        now[index(skill.max_difficulty)] += 1;
+    }
+    egui::Window::new("skill progression").show(context, |ui| {
+        if let Some(statistics) = statistics {
+            ui.label(format!("lessons this season: {}", statistics.lessons));
+            ui.label(format!(
+                "skill level ups this season: {}",
+                statistics.level_ups
+            ));
+        }
+        ui.label("guests on the mountain, at arrival and now:");
+        for (i, difficulty) in Difficulty::ALL.iter().enumerate() {
+            ui.label(format!("    {}: {} -> {}", difficulty, arrived[i], now[i]));
+        }
+    });
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::Vector2;
+    #[test]
+    fn level_up() {
+        let mut skill = Skill {
+            max_difficulty: Difficulty::Black,
+        };
+        let mut progression = Progression::new(skill);
+        assert!(!progression.gain(&mut skill, 0.5));
+        assert!(progression.gain(&mut skill, 0.6));
+        assert_eq!(skill.max_difficulty, Difficulty::DoubleBlack);
+        assert_eq!(progression.experience, 0.0);
+        //there is nothing above double black
+        assert!(!progression.gain(&mut skill, 2.0));
+        assert_eq!(progression.level_ups, 1);
+        assert_eq!(progression.starting_skill, Difficulty::Black);
+    }
+    #[test]
+    fn stretching_teaches_more() {
+        //steep enough to be a blue run
+        let terrain = Terrain::new_cone(Vector2::new(10, 3), Vector2::new(0.0, 0.0), 0.0, -0.3);
+        let tiles: Vec<Node> = (0..10)
+            .map(|x| Node {
+                node: Vector2::new(x, 0),
+            })
+            .collect();
+        let green = Skill {
+            max_difficulty: Difficulty::Green,
+        };
+        let blue = Skill {
+            max_difficulty: Difficulty::Blue,
+        };
+        let stretched = experience(&tiles, &[], &terrain, &green, 0);
+        let comfortable = experience(&tiles, &[], &terrain, &blue, 0);
+        assert!(stretched > comfortable);
+        assert!(comfortable > 0.0);
+        //skiing uphill teaches nothing
+        let mut uphill = tiles.clone();
+        uphill.reverse();
+        assert_eq!(experience(&uphill, &[], &terrain, &green, 0), 0.0);
+        assert_eq!(
+            experience(&[], &[], &terrain, &green, 2),
+            2.0 * Progression::LESSON
+        );
+    }
+}
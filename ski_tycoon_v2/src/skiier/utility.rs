@@ -0,0 +1,392 @@
+use super::behavior_tree::{rides_lift, Exit, Number, Plan};
+use super::Skill;
+use crate::building::Need;
+use crate::congestion::Congestion;
+use crate::prelude::GraphLayerList;
+use crate::rng::SeededRng;
+use crate::terrain::Difficulty;
+/// Something a skiier weighs when choosing between plans
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Consideration {
+    /// Cheaper plans, shorter with less climbing and fewer stops, leave more time for skiing
+    Distance,
+    /// Runs close to the skiier's skill are the most fun
+    DifficultyMatch,
+    /// Nobody likes waiting at the bottom of a crowded lift
+    QueueLength,
+    /// Hungry guests want to eat, tired guests want a break or to go home and everyone else
+    /// wants to get on a lift
+    Needs,
+}
+impl Consideration {
+    pub const ALL: [Consideration; 4] = [
+        Consideration::Distance,
+        Consideration::DifficultyMatch,
+        Consideration::QueueLength,
+        Consideration::Needs,
+    ];
+    fn index(&self) -> usize {
+        match self {
+            Self::Distance => 0,
+            Self::DifficultyMatch => 1,
+            Self::QueueLength => 2,
+            Self::Needs => 3,
+        }
+    }
+}
+impl std::fmt::Display for Consideration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Distance => write!(f, "distance"),
+            Self::DifficultyMatch => write!(f, "difficulty match"),
+            Self::QueueLength => write!(f, "queue length"),
+            Self::Needs => write!(f, "needs"),
+        }
+    }
+}
+/// How much a skiier cares about each consideration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Personality {
+    weights: [f32; 4],
+}
+impl Default for Personality {
+    fn default() -> Self {
+        Self { weights: [1.0; 4] }
+    }
+}
+impl Personality {
+    /// Range of randomly picked weights
+    const MIN_WEIGHT: f32 = 0.5;
+    const MAX_WEIGHT: f32 = 1.5;
+    pub fn random(rng: &mut SeededRng) -> Self {
+        let mut weights = [1.0; 4];
+        for weight in weights.iter_mut() {
+            *weight = rng.range_f32(Self::MIN_WEIGHT, Self::MAX_WEIGHT);
+        }
+        Self { weights }
+    }
+    pub fn weight(&self, consideration: Consideration) -> f32 {
+        self.weights[consideration.index()]
+    }
+}
+/// How hungry and how tired a guest is, from 0 to 1. Both grow while skiing and are taken
+/// care of by stopping at a building or resting.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Needs {
+    pub hunger: f32,
+    pub fatigue: f32,
+}
+impl Needs {
+    /// Growth of hunger for each tile skied
+    const HUNGER_RATE: f32 = 0.004;
+    /// Growth of fatigue for each tile skied
+    const FATIGUE_RATE: f32 = 0.003;
+    /// Fatigue left after resting
+    const RESTED: f32 = 0.5;
+    /// Adds the hunger and fatigue from skiing `tiles` tiles
+    pub fn ski(&mut self, tiles: usize) {
+        self.hunger = (self.hunger + Self::HUNGER_RATE * tiles as f32).min(1.0);
+        self.fatigue = (self.fatigue + Self::FATIGUE_RATE * tiles as f32).min(1.0);
+    }
+    /// Takes care of the needs met by the decision named `name`
+    pub fn meet(&mut self, name: &str) {
+        if name == Need::Eat.to_string() {
+            self.hunger = 0.0;
+        } else if name == Need::WarmUp.to_string() {
+            self.fatigue = 0.0;
+        } else if name == "Rest" {
+            self.fatigue = self.fatigue.min(Self::RESTED);
+        }
+    }
+    /// Most pressing of the needs of `needs`, used for groups who stop together
+    pub fn most(needs: &[Needs]) -> Self {
+        needs.iter().fold(Self::default(), |most, n| Self {
+            hunger: most.hunger.max(n.hunger),
+            fatigue: most.fatigue.max(n.fatigue),
+        })
+    }
+    /// How much the decision named `name` does for the guest, from 0 to 1
+    fn value(&self, name: &str) -> f32 {
+        if name == Need::Eat.to_string() || name == Need::Restroom.to_string() {
+            self.hunger
+        } else if name == Need::WarmUp.to_string() || name == Exit::NAME {
+            self.fatigue
+        } else if name == "Rest" {
+            (self.fatigue - Self::RESTED).max(0.0)
+        } else if rides_lift(name) {
+            1.0 - self.hunger.max(self.fatigue)
+        } else {
+            0.0
+        }
+    }
+}
+/// Utility of a plan from 0 to 1 along with the score each consideration gave it, kept so the
+/// skiier's choice can be explained
+#[derive(Clone, Debug, PartialEq)]
+pub struct UtilityScore {
+    pub total: f32,
+    /// Score from 0 to 1 and the weight of each consideration
+    pub parts: Vec<(Consideration, f32, f32)>,
+}
+impl Default for UtilityScore {
+    fn default() -> Self {
+        Self {
+            total: 0.0,
+            parts: vec![],
+        }
+    }
+}
+/// Scores plans for one skiier
+pub struct Scorer<'a, 'b> {
+    pub layers: &'a GraphLayerList<'b>,
+    /// Skiiers on each tile, used to judge how long lift lines are
+    pub occupancy: Option<&'a Congestion>,
+    pub skill: Skill,
+    pub personality: Personality,
+    /// Satisfaction of the skiier from 0 to 1
+    pub satisfaction: f32,
+    pub needs: Needs,
+}
+impl<'a, 'b> Scorer<'a, 'b> {
+    /// Plan cost at which the distance score is one half, about 50 tiles of flat terrain
+    const DISTANCE_SCALE: f32 = 500.0;
+    /// Number of skiiers waiting at a lift at which the queue score is one half
+    const QUEUE_SCALE: f32 = 3.0;
+    /// Score of skiing a tile that is not on a run
+    const OFF_TRAIL: f32 = 0.2;
+    fn difficulty_match(&self, difficulty: Difficulty) -> f32 {
+        let index = |d: Difficulty| Difficulty::ALL.iter().position(|x| *x == d).unwrap_or(0);
+        match index(self.skill.max_difficulty) as i32 - index(difficulty) as i32 {
+            0 => 1.0,
+            1 => 0.6,
+            _ => 0.3,
+        }
+    }
+    fn consideration(&self, consideration: Consideration, plan: &Plan) -> f32 {
+        let rides: Vec<_> = plan
+            .decisions
+            .iter()
+            .filter(|d| rides_lift(&d.name))
+            .collect();
+        match consideration {
+            //the cost includes any weighting from the behavior tree
+            Consideration::Distance => match plan.cost {
+                Number::Finite(cost) => 1.0 / (1.0 + cost.max(0.0) / Self::DISTANCE_SCALE),
+                Number::Infinite => 0.0,
+            },
+            Consideration::DifficultyMatch => {
+                let trails = self.layers.find_trails();
+                let scores: Vec<f32> = plan
+                    .decisions
+                    .iter()
+                    .filter(|d| !rides_lift(&d.name))
+                    .flat_map(|d| d.path.tiles().iter())
+                    .map(|tile| {
+                        trails
+                            .iter()
+                            .filter(|t| t.contains(tile))
+                            .map(|t| self.difficulty_match(t.difficulty))
+                            .fold(Self::OFF_TRAIL, f32::max)
+                    })
+                    .collect();
+                if scores.is_empty() {
+                    0.5
+                } else {
+                    scores.iter().sum::<f32>() / scores.len() as f32
+                }
+            }
+            Consideration::QueueLength => {
+                if rides.is_empty() {
+                    return 1.0;
+                }
+                let waiting: f32 = rides
+                    .iter()
+                    .filter_map(|d| d.path.start())
+                    .map(|base| {
+                        self.occupancy
+                            .map(|o| o.occupancy(base.node) as f32)
+                            .unwrap_or(0.0)
+                    })
+                    .sum();
+                1.0 / (1.0 + waiting / rides.len() as f32 / Self::QUEUE_SCALE)
+            }
+            Consideration::Needs => {
+                if plan.decisions.is_empty() {
+                    0.0
+                } else {
+                    plan.decisions
+                        .iter()
+                        .map(|d| self.needs.value(&d.name))
+                        .sum::<f32>()
+                        / plan.decisions.len() as f32
+                }
+            }
+        }
+    }
+    fn weight(&self, consideration: Consideration) -> f32 {
+        let weight = self.personality.weight(consideration);
+        match consideration {
+            //the less happy a skiier is the more their needs matter
+            Consideration::Needs => weight * (1.5 - self.satisfaction),
+            _ => weight,
+        }
+    }
+    pub fn score(&self, plan: &Plan) -> UtilityScore {
+        let parts: Vec<(Consideration, f32, f32)> = Consideration::ALL
+            .iter()
+            .map(|c| (*c, self.consideration(*c, plan), self.weight(*c)))
+            .collect();
+        let total_weight: f32 = parts.iter().map(|(_, _, w)| w).sum();
+        let total = if total_weight > 0.0 {
+            parts.iter().map(|(_, s, w)| s * w).sum::<f32>() / total_weight
+        } else {
+            0.0
+        };
+        UtilityScore { total, parts }
+    }
+    /// Plan with the highest utility, the first one found wins ties
+    pub fn choose(&self, plans: Vec<Plan>) -> Option<(Plan, UtilityScore)> {
+        plans
+            .into_iter()
+            .map(|plan| {
+                let score = self.score(&plan);
+                (plan, score)
+            })
+            .fold(None, |best, (plan, score)| match best {
+                Some((best_plan, best_score)) if best_score.total >= score.total => {
+                    Some((best_plan, best_score))
+                }
+                _ => Some((plan, score)),
+            })
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::super::behavior_tree::{Decision, Number};
+    use super::*;
+    use crate::prelude::{FollowPath, GraphWeight, Node, Path, Terrain};
+    use nalgebra::Vector2;
+    fn plan(names: &[&str], tiles: i64, terrain: &Terrain) -> Plan {
+        let decisions = names
+            .iter()
+            .map(|name| Decision {
+                cost: Number::Finite(1.0),
+                name: name.to_string(),
+                path: FollowPath::new(
+                    Path::new(
+                        (0..tiles)
+                            .map(|x| {
+                                (
+                                    Node {
+                                        node: Vector2::new(x, 0),
+                                    },
+                                    GraphWeight::Some(1),
+                                )
+                            })
+                            .collect(),
+                    ),
+                    terrain,
+                ),
+                endpoint: Node {
+                    node: Vector2::new(0, 0),
+                },
+            })
+            .collect();
+        Plan {
+            decisions,
+            cost: Number::Finite((names.len() as i64 * tiles * 10) as f32),
+            endpoint: Node {
+                node: Vector2::new(0, 0),
+            },
+        }
+    }
+    #[test]
+    fn personality() {
+        let terrain = Terrain::new_cone(Vector2::new(40, 4), Vector2::new(0.0, 0.0), 0.0, 0.0);
+        let layers = GraphLayerList::new(vec![]);
+        let scorer = |personality: Personality, satisfaction: f32| Scorer {
+            layers: &layers,
+            occupancy: None,
+            skill: Skill {
+                max_difficulty: Difficulty::Blue,
+            },
+            personality,
+            satisfaction,
+            needs: Needs::default(),
+        };
+        let short = plan(&["Down", "Down"], 3, &terrain);
+        let rides = plan(&["Down", "Up"], 30, &terrain);
+        //skiiers who only care about distance pick the short plan
+        let mut hurried = Personality::default();
+        hurried.weights[Consideration::Needs.index()] = 0.0;
+        let (chosen, score) = scorer(hurried, 1.0)
+            .choose(vec![rides.clone(), short.clone()])
+            .unwrap();
+        assert_eq!(chosen.decisions[1].name, "Down");
+        assert!(score.total > 0.0 && score.total <= 1.0);
+        assert_eq!(score.parts.len(), Consideration::ALL.len());
+        //unhappy skiiers want to ride a lift even if it is further away
+        let mut eager = Personality::default();
+        eager.weights[Consideration::Distance.index()] = 0.1;
+        let (chosen, _) = scorer(eager, 0.0).choose(vec![short, rides]).unwrap();
+        assert_eq!(chosen.decisions[1].name, "Up");
+        assert!(scorer(Personality::default(), 0.5).choose(vec![]).is_none());
+    }
+    #[test]
+    fn needs() {
+        let terrain = Terrain::new_cone(Vector2::new(40, 4), Vector2::new(0.0, 0.0), 0.0, 0.0);
+        let layers = GraphLayerList::new(vec![]);
+        let scorer = |needs: Needs| Scorer {
+            layers: &layers,
+            occupancy: None,
+            skill: Skill {
+                max_difficulty: Difficulty::Blue,
+            },
+            personality: Personality::default(),
+            satisfaction: 0.5,
+            needs,
+        };
+        let choose = |needs: Needs| {
+            let plans = vec![
+                plan(&["Up"], 3, &terrain),
+                plan(&["Eat"], 3, &terrain),
+                plan(&[Exit::NAME], 3, &terrain),
+            ];
+            let (chosen, _) = scorer(needs).choose(plans).unwrap();
+            chosen.decisions[0].name.clone()
+        };
+        //fresh guests want to ski
+        assert_eq!(choose(Needs::default()), "Up");
+        //hungry guests head to the lodge
+        let hungry = Needs {
+            hunger: 0.9,
+            fatigue: 0.2,
+        };
+        assert_eq!(choose(hungry), "Eat");
+        //tired guests go home
+        let tired = Needs {
+            hunger: 0.2,
+            fatigue: 0.9,
+        };
+        assert_eq!(choose(tired), Exit::NAME);
+        //eating takes care of hunger and skiing brings it back
+        let mut needs = hungry;
+        needs.meet("Eat");
+        assert_eq!(needs.hunger, 0.0);
+        needs.ski(10);
+        assert!(needs.hunger > 0.0 && needs.fatigue > hungry.fatigue);
+        assert_eq!(Needs::most(&[hungry, tired]).fatigue, 0.9);
+        //warming up does more for tired guests than resting, which only takes the edge off
+        assert!(tired.value("WarmUp") > tired.value("Rest"));
+        assert_eq!(Needs::default().value("Rest"), 0.0);
+    }
+    #[test]
+    fn random_personality() {
+        let mut rng = SeededRng::new(3);
+        let personality = Personality::random(&mut rng);
+        for c in Consideration::ALL.iter() {
+            let w = personality.weight(*c);
+            assert!((Personality::MIN_WEIGHT..=Personality::MAX_WEIGHT).contains(&w));
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use super::prelude::{GraphLayer, Grid, Terrain, TrailLayer, Transform};
+use super::skiier::{Satisfaction, Skill, Velocity};
+use super::terrain::{Difficulty, TileType};
+use egui::CtxRef;
+use legion::*;
+use nalgebra::Vector2;
+/// Skiier traffic on each tile since its snow last changed
+pub struct SnowConditions {
+    traffic: Grid<u32>,
+    /// Centimeters of snow that fell since the runs were last covered in powder
+    fresh: f32,
+}
+impl SnowConditions {
+    /// Ticks skiiers spend on a tile before its snow wears down
+    pub const WEAR_TICKS: u32 = 300;
+    /// Depth below which snow wears down twice as fast
+    pub const THIN_DEPTH: f32 = 30.0;
+    /// Depth below which rocks show through the snow
+    pub const ROCKY_DEPTH: f32 = 10.0;
+    /// Share of rocky tiles at which a run closes
+    pub const CLOSING_SHARE: f32 = 0.25;
+    /// Fresh snow needed to cover the runs in powder
+    pub const POWDER_DEPTH: f32 = 5.0;
+    pub fn new(dimensions: Vector2<usize>) -> Self {
+        Self {
+            traffic: Grid::from_vec(vec![0; dimensions.x * dimensions.y], dimensions),
+            fresh: 0.0,
+        }
+    }
+    fn index(&self, tile: Vector2<i64>) -> Option<Vector2<usize>> {
+        if tile.x >= 0
+            && tile.y >= 0
+            && tile.x < self.traffic.width() as i64
+            && tile.y < self.traffic.height() as i64
+        {
+            Some(Vector2::new(tile.x as usize, tile.y as usize))
+        } else {
+            None
+        }
+    }
+    /// Adds a tick of traffic to a tile, thin snow counts double. Returns true when the tile has
+    /// seen enough traffic to wear down, starting the count over.
+    fn add_traffic(&mut self, tile: Vector2<i64>, thin: bool) -> bool {
+        match self.index(tile) {
+            Some(index) => {
+                self.traffic[index] += if thin { 2 } else { 1 };
+                if self.traffic[index] >= Self::WEAR_TICKS {
+                    self.traffic[index] = 0;
+                    true
+                } else {
+                    false
+                }
+            }
+            None => false,
+        }
+    }
+    /// Forgets the traffic on a tile once its snow is renewed
+    pub fn reset(&mut self, tile: Vector2<i64>) {
+        if let Some(index) = self.index(tile) {
+            self.traffic[index] = 0;
+        }
+    }
+}
+/// Snow a tile wears down to under skiier traffic. Fresh and groomed snow gets packed down,
+/// packed snow turns into moguls on steep slopes and ice everywhere else.
+pub fn worn(tile_type: &TileType, steep: bool) -> Option<TileType> {
+    match tile_type {
+        TileType::Powder | TileType::Groomed => Some(TileType::Snow),
+        TileType::Snow if steep => Some(TileType::Moguls),
+        TileType::Snow => Some(TileType::Ice),
+        TileType::Moguls | TileType::Ice | TileType::Building | TileType::Rocks => None,
+    }
+}
+/// Whether a tile is steep enough to form moguls, black or harder towards any neighbor
+fn is_steep(terrain: &Terrain, tile: Vector2<i64>) -> bool {
+    (-1..=1)
+        .flat_map(|x| (-1..=1).map(move |y| Vector2::new(x, y)))
+        .filter_map(|offset| terrain.grade(tile, tile + offset))
+        .any(|grade| Difficulty::from_grade(grade) >= Difficulty::Black)
+}
+/// Change in satisfaction for each tick a skiier spends skiing a tile. Everyone likes groomed
+/// runs and nobody likes ice, experts enjoy the powder and moguls that others struggle with.
+pub fn enjoyment(tile_type: &TileType, skill: &Skill) -> f32 {
+    let expert = skill.max_difficulty >= Difficulty::Black;
+    match tile_type {
+        TileType::Groomed => 0.0002,
+        TileType::Powder | TileType::Moguls if expert => 0.0004,
+        TileType::Powder | TileType::Moguls => -0.0003,
+        TileType::Ice => -0.0003,
+        TileType::Rocks => -0.001,
+        TileType::Snow | TileType::Building => 0.0,
+    }
+}
+/// Adds `change` centimeters of snow to a tile, removing snow if negative. Rocks show through
+/// snow thinner than `ROCKY_DEPTH` and are covered again once the snow is deep enough.
+pub fn change_depth(terrain: &mut Terrain, tile: &Vector2<i64>, change: f32) {
+    let (tile_type, depth) = match terrain.get_tile(tile) {
+        Some(t) => (t.tile_type.clone(), (t.snow_depth + change).max(0.0)),
+        None => return,
+    };
+    terrain.set_snow_depth(tile, depth);
+    let rocky = depth < SnowConditions::ROCKY_DEPTH;
+    match tile_type {
+        TileType::Building => (),
+        TileType::Rocks if !rocky => {
+            terrain.set_tile_type(tile, TileType::Snow);
+        }
+        TileType::Rocks => (),
+        _ if rocky => {
+            terrain.set_tile_type(tile, TileType::Rocks);
+        }
+        _ => (),
+    }
+}
+/// Whether so much of a run is rocky that it is closed to skiiers
+pub fn is_closed(trail: &TrailLayer, terrain: &Terrain) -> bool {
+    let (tiles, rocky) = trail.tiles().fold((0, 0), |(tiles, rocky), t| {
+        match terrain.get_tile(&t.node) {
+            Some(tile) if tile.tile_type == TileType::Rocks => (tiles + 1, rocky + 1),
+            _ => (tiles + 1, rocky),
+        }
+    });
+    tiles > 0 && rocky as f32 >= tiles as f32 * SnowConditions::CLOSING_SHARE
+}
+/// Creates the snow conditions for a new scenario
+fn add_conditions(world: &mut World) {
+    if <&SnowConditions>::query().iter(world).next().is_none() {
+        if let Some(terrain) = <&Terrain>::query().iter(world).next() {
+            let conditions = SnowConditions::new(terrain.dimensions());
+            world.push((conditions,));
+        }
+    }
+}
+/// Wears down the snow under skiiers heading downhill and changes how satisfied they are with
+/// the snow they are skiing
+pub fn update_snow(world: &mut World) {
+    add_conditions(world);
+    let (mut skiiers, mut rest) =
+        world.split::<(&Transform, &Skill, &Velocity, &mut Satisfaction)>();
+    let (mut conditions, mut rest) = rest.split::<&mut SnowConditions>();
+    let terrain = match <&mut Terrain>::query().iter_mut(&mut rest).next() {
+        Some(terrain) => terrain,
+        None => return,
+    };
+    let conditions = match <&mut SnowConditions>::query()
+        .iter_mut(&mut conditions)
+        .next()
+    {
+        Some(conditions) => conditions,
+        None => return,
+    };
+    let mut query = <(&Transform, &Skill, &Velocity, &mut Satisfaction)>::query();
+    for (transform, skill, velocity, satisfaction) in query.iter_mut(&mut skiiers) {
+        if velocity.velocity.y >= 0.0 {
+            continue;
+        }
+        let t = transform.get_translation();
+        let tile = Vector2::new(t.x.round() as i64, t.z.round() as i64);
+        let (tile_type, thin) = match terrain.get_tile(&tile) {
+            Some(t) => (
+                t.tile_type.clone(),
+                t.snow_depth < SnowConditions::THIN_DEPTH,
+            ),
+            None => continue,
+        };
+        satisfaction.change(enjoyment(&tile_type, skill));
+        if conditions.add_traffic(tile, thin) {
+            if let Some(worn) = worn(&tile_type, is_steep(terrain, tile)) {
+                terrain.set_tile_type(&tile, worn);
+            }
+        }
+    }
+}
+/// Adds `change` centimeters of snow to every tile
+fn change_all_depths(world: &mut World, change: f32) {
+    if let Some(terrain) = <&mut Terrain>::query().iter_mut(world).next() {
+        let dimensions = terrain.dimensions();
+        for x in 0..dimensions.x as i64 {
+            for y in 0..dimensions.y as i64 {
+                change_depth(terrain, &Vector2::new(x, y), change);
+            }
+        }
+    }
+}
+/// Adds `depth` centimeters of fresh snow to every tile. Once enough has fallen the runs are
+/// covered in powder.
+pub fn snowfall(world: &mut World, depth: f32) {
+    add_conditions(world);
+    change_all_depths(world, depth);
+    let (mut conditions, mut rest) = world.split::<&mut SnowConditions>();
+    let conditions = match <&mut SnowConditions>::query()
+        .iter_mut(&mut conditions)
+        .next()
+    {
+        Some(conditions) => conditions,
+        None => return,
+    };
+    conditions.fresh += depth;
+    if conditions.fresh < SnowConditions::POWDER_DEPTH {
+        return;
+    }
+    conditions.fresh = 0.0;
+    for x in 0..conditions.traffic.width() {
+        for y in 0..conditions.traffic.height() {
+            conditions.traffic[Vector2::new(x, y)] = 0;
+        }
+    }
+    if let Some(terrain) = <&mut Terrain>::query().iter_mut(&mut rest).next() {
+        let dimensions = terrain.dimensions();
+        for x in 0..dimensions.x as i64 {
+            for y in 0..dimensions.y as i64 {
+                let tile = Vector2::new(x, y);
+                let covered = terrain.get_tile(&tile).map(|t| t.tile_type.is_snow());
+                if covered == Some(true) {
+                    terrain.set_tile_type(&tile, TileType::Powder);
+                }
+            }
+        }
+    }
+}
+/// Melts `depth` centimeters of snow off every tile
+pub fn melt(world: &mut World, depth: f32) {
+    change_all_depths(world, -depth);
+}
+/// Draws the snow window with the conditions on each run
+pub fn draw_gui(world: &World, context: &mut CtxRef) {
+    let terrain = match <&Terrain>::query().iter(world).next() {
+        Some(terrain) => terrain,
+        None => return,
+    };
+    let mut runs: Vec<(String, String)> = <&GraphLayer>::query()
+        .iter(world)
+        .filter_map(|layer| match layer {
+            GraphLayer::Trail(trail) => Some(trail),
+            _ => None,
+        })
+        .map(|trail| {
+            let types: Vec<TileType> = trail
+                .tiles()
+                .filter_map(|t| terrain.get_tile(&t.node))
+                .map(|t| t.tile_type.clone())
+                .collect();
+            let conditions: Vec<String> = [
+                TileType::Groomed,
+                TileType::Powder,
+                TileType::Snow,
+                TileType::Moguls,
+                TileType::Ice,
+                TileType::Rocks,
+            ]
+            .iter()
+            .map(|surface| (surface, types.iter().filter(|t| *t == surface).count()))
+            .filter(|(_, count)| *count > 0)
+            .map(|(surface, count)| format!("{} {}", count, surface))
+            .collect();
+            let depths: Vec<f32> = trail.tiles().map(|t| terrain.snow_depth(&t.node)).collect();
+            let depth = depths.iter().sum::<f32>() / depths.len().max(1) as f32;
+            let closed = if is_closed(trail, terrain) {
+                "closed, "
+            } else {
+                ""
+            };
+            (
+                trail.name.clone(),
+                format!("{}{:.0} cm, {}", closed, depth, conditions.join(", ")),
+            )
+        })
+        .collect();
+    runs.sort();
+    egui::Window::new("snow").show(context, |ui| {
+        if runs.is_empty() {
+            ui.label("No runs");
+        }
+        for (name, conditions) in runs.iter() {
+            ui.label(format!("{}: {}", name, conditions));
+        }
+    });
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::{
+        dijkstra, push_terrain, update_navigation_graph, GraphLayerList, NavigationGraph, Node,
+    };
+    use crate::terrain::{apply_terrain_edits, Tile};
+    use nalgebra::Vector3;
+    #[test]
+    fn traffic_wears_snow() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.2),
+            &mut world,
+        );
+        let mut transform = Transform::default();
+        transform.set_translation(Vector3::new(1.0, 0.0, 4.0));
+        let skiier = world.push((
+            transform,
+            Skill {
+                max_difficulty: Difficulty::Green,
+            },
+            Velocity {
+                velocity: Vector3::new(0.1, -0.1, 0.0),
+            },
+            Satisfaction::default(),
+        ));
+        let tile = |world: &World| {
+            let terrain = <&Terrain>::query().iter(world).next().unwrap();
+            terrain
+                .get_tile(&Vector2::new(1, 4))
+                .unwrap()
+                .tile_type
+                .clone()
+        };
+        snowfall(&mut world, SnowConditions::POWDER_DEPTH);
+        assert_eq!(tile(&world), TileType::Powder);
+        for _ in 0..SnowConditions::WEAR_TICKS {
+            update_snow(&mut world);
+        }
+        assert_eq!(tile(&world), TileType::Snow);
+        //beginners struggle in powder
+        {
+            let entry = world.entry_ref(skiier).unwrap();
+            let satisfaction = entry.get_component::<Satisfaction>().unwrap();
+            assert!(satisfaction.value < Satisfaction::default().value);
+        }
+        //the slope is too gentle for moguls
+        for _ in 0..SnowConditions::WEAR_TICKS {
+            update_snow(&mut world);
+        }
+        assert_eq!(tile(&world), TileType::Ice);
+        for _ in 0..SnowConditions::WEAR_TICKS {
+            update_snow(&mut world);
+        }
+        assert_eq!(tile(&world), TileType::Ice);
+    }
+    #[test]
+    fn snow_piles_up_and_melts() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.2),
+            &mut world,
+        );
+        let mut transform = Transform::default();
+        transform.set_translation(Vector3::new(1.0, 0.0, 4.0));
+        world.push((
+            transform,
+            Skill {
+                max_difficulty: Difficulty::Black,
+            },
+            Velocity {
+                velocity: Vector3::new(0.1, -0.1, 0.0),
+            },
+            Satisfaction::default(),
+        ));
+        let tile = Vector2::new(1, 4);
+        let conditions = |world: &World| {
+            let terrain = <&Terrain>::query().iter(world).next().unwrap();
+            let tile = terrain.get_tile(&tile).unwrap();
+            (tile.tile_type.clone(), tile.snow_depth)
+        };
+        snowfall(&mut world, 2.0);
+        assert_eq!(
+            conditions(&world),
+            (TileType::Snow, Tile::STARTING_SNOW_DEPTH + 2.0)
+        );
+        snowfall(&mut world, 3.0);
+        assert_eq!(conditions(&world).0, TileType::Powder);
+        melt(&mut world, Tile::STARTING_SNOW_DEPTH - 15.0);
+        assert_eq!(conditions(&world), (TileType::Powder, 20.0));
+        //thin snow wears down twice as fast
+        for _ in 0..SnowConditions::WEAR_TICKS / 2 {
+            update_snow(&mut world);
+        }
+        assert_eq!(conditions(&world).0, TileType::Snow);
+        melt(&mut world, 100.0);
+        assert_eq!(conditions(&world), (TileType::Rocks, 0.0));
+        snowfall(&mut world, SnowConditions::ROCKY_DEPTH);
+        assert_eq!(conditions(&world).0, TileType::Powder);
+    }
+    #[test]
+    fn rocky_runs_close() {
+        let mut terrain = Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.2);
+        let tiles = [
+            Vector2::new(1, 4),
+            Vector2::new(2, 4),
+            Vector2::new(3, 4),
+            Vector2::new(4, 4),
+            Vector2::new(5, 4),
+        ];
+        let trail = TrailLayer::new("Run 1".to_string(), Difficulty::Green, &tiles, &terrain);
+        assert!(!is_closed(&trail, &terrain));
+        change_depth(&mut terrain, &tiles[0], -Tile::STARTING_SNOW_DEPTH);
+        assert_eq!(
+            terrain.get_tile(&tiles[0]).unwrap().tile_type,
+            TileType::Rocks
+        );
+        assert!(!is_closed(&trail, &terrain));
+        change_depth(&mut terrain, &tiles[1], -Tile::STARTING_SNOW_DEPTH);
+        assert!(is_closed(&trail, &terrain));
+        change_depth(&mut terrain, &tiles[1], SnowConditions::ROCKY_DEPTH);
+        assert_eq!(
+            terrain.get_tile(&tiles[1]).unwrap().tile_type,
+            TileType::Snow
+        );
+        assert!(!is_closed(&trail, &terrain));
+    }
+    #[test]
+    fn closed_runs_are_not_routed_through() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.2),
+            &mut world,
+        );
+        let tiles: Vec<Vector2<i64>> = (1..6).rev().map(|x| Vector2::new(x, 4)).collect();
+        let trail = {
+            let terrain = <&Terrain>::query().iter(&world).next().unwrap();
+            TrailLayer::new("Run 1".to_string(), Difficulty::Green, &tiles, terrain)
+        };
+        world.push((GraphLayer::Trail(trail),));
+        update_navigation_graph(&mut world);
+        let route = |world: &World| {
+            let terrain = <&Terrain>::query().iter(world).next().unwrap();
+            let graph = <&NavigationGraph>::query().iter(world).next().unwrap();
+            //skiiers plan without closed runs
+            let layers: Vec<&GraphLayer> = graph
+                .layers()
+                .into_iter()
+                .filter(|layer| match layer {
+                    GraphLayer::Trail(trail) => !is_closed(trail, terrain),
+                    _ => true,
+                })
+                .collect();
+            let path = dijkstra(
+                &Node { node: tiles[0] },
+                &Node { node: tiles[4] },
+                &GraphLayerList::new(layers),
+            )
+            .unwrap();
+            path.path
+                .iter()
+                .map(|(node, _)| node.node)
+                .collect::<Vec<Vector2<i64>>>()
+        };
+        assert!(route(&world).contains(&tiles[2]));
+        let rocky = &tiles[1..4];
+        {
+            let terrain = <&mut Terrain>::query().iter_mut(&mut world).next().unwrap();
+            for tile in rocky.iter() {
+                change_depth(terrain, tile, -Tile::STARTING_SNOW_DEPTH);
+            }
+            assert!(rocky
+                .iter()
+                .all(|t| terrain.get_tile(t).unwrap().tile_type == TileType::Rocks));
+        }
+        apply_terrain_edits(&mut world);
+        update_navigation_graph(&mut world);
+        //skiiers go around the bare run instead of down it
+        let route = route(&world);
+        assert_eq!(route.last(), Some(&tiles[4]));
+        assert!(route.iter().all(|t| !rocky.contains(t)));
+    }
+    #[test]
+    fn moguls_form_on_steep_slopes() {
+        assert_eq!(worn(&TileType::Snow, true), Some(TileType::Moguls));
+        assert_eq!(worn(&TileType::Groomed, true), Some(TileType::Snow));
+        assert_eq!(worn(&TileType::Building, true), None);
+        let terrain = Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -1.0);
+        assert!(is_steep(&terrain, Vector2::new(1, 4)));
+    }
+}
@@ -0,0 +1,298 @@
+use super::clock::Clock;
+use super::commands::PlayerCommand;
+use super::finances::add_cash;
+use super::prelude::{GraphLayer, Model, RuntimeModelId, Terrain, Texture, TrailLayer, Transform};
+use super::snow::change_depth;
+use super::terrain::TileType;
+use super::weather::Weather;
+use egui::CtxRef;
+use legion::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+/// Snow gun that blows snow onto the tiles around it whenever it is cold enough
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnowGun {
+    pub tile: Vector2<i64>,
+    /// Whether the gun made snow during the last hour
+    pub running: bool,
+}
+impl SnowGun {
+    /// Tiles within this distance of a gun get snow
+    pub const RADIUS: f32 = 3.0;
+    /// Cost of the water and power a gun uses in an hour of running
+    pub const HOURLY_COST: f32 = 25.0;
+    /// Warmest temperature guns can make snow at
+    pub const MAX_TEMPERATURE: f32 = -2.0;
+    /// Centimeters of snow made each hour under the gun for every degree below `MAX_TEMPERATURE`
+    const OUTPUT_PER_DEGREE: f32 = 0.5;
+    /// Most centimeters of snow made each hour under the gun
+    const MAX_OUTPUT: f32 = 4.0;
+    /// Guns stop once every tile they cover has this much snow
+    pub const TARGET_DEPTH: f32 = 60.0;
+    /// Centimeters of snow made each hour under a gun, it gets more done the colder it is
+    pub fn output(temperature: f32) -> f32 {
+        ((Self::MAX_TEMPERATURE - temperature) * Self::OUTPUT_PER_DEGREE)
+            .clamp(0.0, Self::MAX_OUTPUT)
+    }
+    /// Tiles covered by the gun along with the share of the output they get, less further away
+    pub fn coverage(&self) -> Vec<(Vector2<i64>, f32)> {
+        let reach = Self::RADIUS.floor() as i64;
+        (-reach..=reach)
+            .flat_map(|x| (-reach..=reach).map(move |y| Vector2::new(x, y)))
+            .filter_map(|offset| {
+                let distance = ((offset.x * offset.x + offset.y * offset.y) as f32).sqrt();
+                if distance <= Self::RADIUS {
+                    Some((self.tile + offset, 1.0 - distance / (Self::RADIUS + 1.0)))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+/// Reason a snow gun can not be placed
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnowGunError {
+    NoTerrain,
+    OffTerrain,
+    /// Guns go on runs or the tiles next to them
+    NotAlongRun,
+    /// Tile is covered by a building or already has a gun
+    Occupied,
+}
+impl std::fmt::Display for SnowGunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoTerrain => write!(f, "there is no terrain to place on"),
+            Self::OffTerrain => write!(f, "snow gun is off the terrain"),
+            Self::NotAlongRun => write!(f, "snow guns must be placed along a run"),
+            Self::Occupied => write!(f, "tile is already taken"),
+        }
+    }
+}
+impl std::error::Error for SnowGunError {}
+/// Grey post for snow guns
+pub fn snow_gun_model() -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(Vector4::new(120, 120, 130, 255), Vector2::new(8, 8));
+    model
+}
+/// Places a snow gun on a run or a tile next to one
+pub fn place_snow_gun(world: &mut World, tile: Vector2<i64>) -> Result<Entity, SnowGunError> {
+    let transform = {
+        let terrain = <&Terrain>::query()
+            .iter(world)
+            .next()
+            .ok_or(SnowGunError::NoTerrain)?;
+        let translation = terrain
+            .get_transform(&tile)
+            .ok_or(SnowGunError::OffTerrain)?;
+        if terrain
+            .get_tile(&tile)
+            .map(|t| t.tile_type != TileType::Building)
+            != Some(true)
+            || <&SnowGun>::query().iter(world).any(|gun| gun.tile == tile)
+        {
+            return Err(SnowGunError::Occupied);
+        }
+        let along_run = <&GraphLayer>::query().iter(world).any(|layer| match layer {
+            GraphLayer::Trail(trail) => trail
+                .tiles()
+                .any(|t| (t.node.x - tile.x).abs() <= 1 && (t.node.y - tile.y).abs() <= 1),
+            _ => false,
+        });
+        if !along_run {
+            return Err(SnowGunError::NotAlongRun);
+        }
+        let mut transform = Transform::default();
+        transform.set_scale(Vector3::new(0.1, 0.4, 0.1));
+        transform.set_translation(translation + Vector3::new(0.0, 0.4, 0.0));
+        transform
+    };
+    Ok(world.push((
+        SnowGun {
+            tile,
+            running: false,
+        },
+        transform,
+        RuntimeModelId::new("snow_gun".to_string()),
+    )))
+}
+/// Runs the snow guns every hour while it is cold enough and some tile they cover is below the
+/// target depth, paying for the water and power of each gun that runs
+pub fn make_snow(world: &mut World) {
+    let tick = match <&Clock>::query().iter(world).next() {
+        Some(clock) => clock.tick(),
+        None => return,
+    };
+    if tick % Clock::TICKS_PER_HOUR != 0 {
+        return;
+    }
+    let output = match <&Weather>::query().iter(world).next() {
+        Some(weather) => SnowGun::output(weather.conditions.temperature),
+        None => return,
+    };
+    let running = {
+        let (mut guns, mut rest) = world.split::<&mut SnowGun>();
+        let terrain = match <&mut Terrain>::query().iter_mut(&mut rest).next() {
+            Some(terrain) => terrain,
+            None => return,
+        };
+        let mut running = 0;
+        for gun in <&mut SnowGun>::query().iter_mut(&mut guns) {
+            let coverage: Vec<(Vector2<i64>, f32)> = gun
+                .coverage()
+                .into_iter()
+                //rocky tiles are covered again once the guns make enough snow
+                .filter(|(tile, _)| {
+                    terrain
+                        .get_tile(tile)
+                        .map(|t| t.tile_type != TileType::Building)
+                        == Some(true)
+                })
+                .collect();
+            gun.running = output > 0.0
+                && coverage
+                    .iter()
+                    .any(|(tile, _)| terrain.snow_depth(tile) < SnowGun::TARGET_DEPTH);
+            if gun.running {
+                for (tile, share) in coverage.iter() {
+                    change_depth(terrain, tile, output * share);
+                }
+                running += 1;
+            }
+        }
+        running
+    };
+    add_cash(world, -(running as f32) * SnowGun::HOURLY_COST);
+}
+/// First tile of a run, in order of position, that no snow gun covers yet
+fn uncovered_tile(trail: &TrailLayer, guns: &[Vector2<i64>]) -> Option<Vector2<i64>> {
+    let mut tiles: Vec<Vector2<i64>> = trail.tiles().map(|t| t.node).collect();
+    tiles.sort_by_key(|t| (t.x, t.y));
+    tiles.into_iter().find(|tile| {
+        guns.iter().all(|gun| {
+            let offset = gun - tile;
+            ((offset.x * offset.x + offset.y * offset.y) as f32).sqrt() > SnowGun::RADIUS
+        })
+    })
+}
+/// Draws the snowmaking window with the guns and what they cost to run. Returns a command to
+/// place a gun on the first part of a run without snowmaking if one was added.
+pub fn draw_gui(world: &World, context: &mut CtxRef) -> Option<PlayerCommand> {
+    let guns: Vec<&SnowGun> = <&SnowGun>::query().iter(world).collect();
+    let tiles: Vec<Vector2<i64>> = guns.iter().map(|gun| gun.tile).collect();
+    let running = guns.iter().filter(|gun| gun.running).count();
+    let temperature = <&Weather>::query()
+        .iter(world)
+        .next()
+        .map(|w| w.conditions.temperature);
+    let mut runs: Vec<(String, Option<Vector2<i64>>)> = <&GraphLayer>::query()
+        .iter(world)
+        .filter_map(|layer| match layer {
+            GraphLayer::Trail(trail) => Some((trail.name.clone(), uncovered_tile(trail, &tiles))),
+            _ => None,
+        })
+        .collect();
+    runs.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut command = None;
+    egui::Window::new("snowmaking").show(context, |ui| {
+        ui.label(format!(
+            "{} snow guns, {} running for ${:.0} an hour",
+            guns.len(),
+            running,
+            running as f32 * SnowGun::HOURLY_COST
+        ));
+        match temperature {
+            Some(t) if SnowGun::output(t) > 0.0 => ui.label(format!(
+                "making up to {:.1} cm of snow an hour at {:.0}°C",
+                SnowGun::output(t),
+                t
+            )),
+            Some(t) => ui.label(format!("too warm to make snow at {:.0}°C", t)),
+            None => ui.label("Load a scenario to make snow"),
+        };
+        for (name, tile) in runs.iter() {
+            match tile {
+                Some(tile) => {
+                    if ui.button(format!("add snow gun on {}", name)).clicked {
+                        command = Some(PlayerCommand::PlaceSnowGun {
+                            tile: (tile.x, tile.y),
+                        });
+                    }
+                }
+                None => {
+                    ui.label(format!("{} is covered by snowmaking", name));
+                }
+            }
+        }
+    });
+    command
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::finances::Finances;
+    use crate::prelude::push_terrain;
+    use crate::rng::SeededRng;
+    use crate::terrain::{Difficulty, Tile};
+    use crate::trail::push_trail;
+    #[test]
+    fn output_depends_on_temperature() {
+        assert_eq!(SnowGun::output(0.0), 0.0);
+        assert_eq!(SnowGun::output(SnowGun::MAX_TEMPERATURE), 0.0);
+        assert!(SnowGun::output(-4.0) < SnowGun::output(-8.0));
+        assert_eq!(SnowGun::output(-40.0), SnowGun::MAX_OUTPUT);
+    }
+    #[test]
+    fn guns_cover_runs() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(12, 12), Vector2::new(6.0, 6.0), 4.0, -0.2),
+            &mut world,
+        );
+        let mut weather = Weather::new(&mut SeededRng::new(1));
+        weather.conditions.temperature = -10.0;
+        world.push((Clock::default(), Finances::new(1000.0), weather));
+        let tiles = [Vector2::new(2, 6), Vector2::new(3, 6), Vector2::new(4, 6)];
+        push_trail(&mut world, "Run 1".to_string(), Difficulty::Green, &tiles);
+        assert_eq!(
+            place_snow_gun(&mut world, Vector2::new(10, 10)),
+            Err(SnowGunError::NotAlongRun)
+        );
+        assert_eq!(
+            place_snow_gun(&mut world, Vector2::new(20, 20)),
+            Err(SnowGunError::OffTerrain)
+        );
+        place_snow_gun(&mut world, Vector2::new(3, 7)).unwrap();
+        assert_eq!(
+            place_snow_gun(&mut world, Vector2::new(3, 7)),
+            Err(SnowGunError::Occupied)
+        );
+        let depth = |world: &World, tile: Vector2<i64>| {
+            <&Terrain>::query()
+                .iter(world)
+                .next()
+                .unwrap()
+                .snow_depth(&tile)
+        };
+        let cash = |world: &World| <&Finances>::query().iter(world).next().unwrap().cash;
+        //guns stay off while the snow is deep enough
+        make_snow(&mut world);
+        assert_eq!(depth(&world, Vector2::new(3, 7)), Tile::STARTING_SNOW_DEPTH);
+        assert_eq!(cash(&world), 1000.0);
+        crate::snow::melt(&mut world, Tile::STARTING_SNOW_DEPTH);
+        make_snow(&mut world);
+        assert_eq!(depth(&world, Vector2::new(3, 7)), SnowGun::MAX_OUTPUT);
+        assert!(depth(&world, Vector2::new(3, 6)) > 0.0);
+        assert!(depth(&world, Vector2::new(3, 6)) < SnowGun::MAX_OUTPUT);
+        assert_eq!(depth(&world, Vector2::new(3, 11)), 0.0);
+        assert_eq!(cash(&world), 1000.0 - SnowGun::HOURLY_COST);
+        //no snow is made when it is too warm
+        for weather in <&mut Weather>::query().iter_mut(&mut world) {
+            weather.conditions.temperature = 0.0;
+        }
+        make_snow(&mut world);
+        assert_eq!(depth(&world, Vector2::new(3, 7)), SnowGun::MAX_OUTPUT);
+        assert_eq!(cash(&world), 1000.0 - SnowGun::HOURLY_COST);
+    }
+}
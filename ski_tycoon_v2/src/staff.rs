@@ -0,0 +1,387 @@
+use super::building::{Building, BuildingKind};
+use super::clock::Clock;
+use super::commands::PlayerCommand;
+use super::prelude::{GraphLayer, GraphLayerList, Node, Terrain};
+use super::rng::SeededRng;
+use super::weather::wind_hold;
+use egui::CtxRef;
+use legion::world::EntityStore;
+use legion::*;
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+mod grooming;
+mod patrol;
+pub use grooming::groomer_model;
+pub use patrol::{patroller_model, Injury};
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Role {
+    /// Runs a lift, lifts do not run without one
+    LiftOperator,
+    /// Answers skiiers who get hurt
+    Patroller,
+    /// Drives a grooming vehicle over the runs at night
+    Groomer,
+    /// Teaches lessons at a ski school
+    Instructor,
+}
+impl Role {
+    pub const ALL: [Role; 4] = [
+        Role::LiftOperator,
+        Role::Patroller,
+        Role::Groomer,
+        Role::Instructor,
+    ];
+    pub fn hourly_wage(&self) -> f32 {
+        match self {
+            Self::LiftOperator => 15.0,
+            Self::Patroller => 20.0,
+            Self::Groomer => 18.0,
+            Self::Instructor => 25.0,
+        }
+    }
+    /// Whether the role works at a lift or building
+    pub fn has_post(&self) -> bool {
+        matches!(self, Self::LiftOperator | Self::Instructor)
+    }
+}
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LiftOperator => write!(f, "lift operator"),
+            Self::Patroller => write!(f, "ski patroller"),
+            Self::Groomer => write!(f, "groomer"),
+            Self::Instructor => write!(f, "instructor"),
+        }
+    }
+}
+/// Hours of the day a staff member works
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Shift {
+    /// 6:00 to 18:00
+    Day,
+    /// 18:00 to 6:00
+    Night,
+    AllDay,
+}
+impl Shift {
+    pub const ALL: [Shift; 3] = [Shift::Day, Shift::Night, Shift::AllDay];
+    pub fn on_duty(&self, hour: u32) -> bool {
+        let day = (6..18).contains(&hour);
+        match self {
+            Self::Day => day,
+            Self::Night => !day,
+            Self::AllDay => true,
+        }
+    }
+    pub fn hours(&self) -> u32 {
+        match self {
+            Self::Day | Self::Night => 12,
+            Self::AllDay => 24,
+        }
+    }
+}
+impl std::fmt::Display for Shift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Day => write!(f, "day"),
+            Self::Night => write!(f, "night"),
+            Self::AllDay => write!(f, "all day"),
+        }
+    }
+}
+/// Someone the resort employs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Staff {
+    pub role: Role,
+    pub shift: Shift,
+    /// Lift or building the staff member works at
+    pub post: Option<Entity>,
+}
+impl Staff {
+    /// Paid at the start of every day
+    pub fn daily_wage(&self) -> f32 {
+        self.role.hourly_wage() * self.shift.hours() as f32
+    }
+}
+/// Reason a staff member can not be hired or fired
+#[derive(Clone, Debug, PartialEq)]
+pub enum StaffError {
+    /// There is no lift or building for the role at the post
+    NoPost(Option<(i64, i64)>),
+    /// Nobody with the role, shift and post works at the resort
+    NotEmployed,
+}
+impl std::fmt::Display for StaffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPost(Some((x, y))) => write!(f, "nowhere to work at ({}, {})", x, y),
+            Self::NoPost(None) => write!(f, "role needs somewhere to work"),
+            Self::NotEmployed => write!(f, "nobody to fire"),
+        }
+    }
+}
+impl std::error::Error for StaffError {}
+/// Current hour of the day, midnight when no scenario is loaded
+fn hour<W: EntityStore>(world: &W) -> u32 {
+    <&Clock>::query()
+        .iter(world)
+        .next()
+        .map(|c| c.hour())
+        .unwrap_or(0)
+}
+/// Base of the lowest lift where staff start out, the corner of the map without lifts
+fn base(world: &World) -> Vector2<i64> {
+    let terrain = <&Terrain>::query().iter(world).next();
+    let layers: Vec<&GraphLayer> = <&GraphLayer>::query().iter(world).collect();
+    let height = |n: &Vector2<i64>| {
+        terrain
+            .and_then(|t| t.get_transform(n))
+            .map(|t| t.y)
+            .unwrap_or(0.0)
+    };
+    GraphLayerList::new(layers)
+        .find_lifts()
+        .iter()
+        .map(|lift| lift.start.node)
+        .min_by(|a, b| {
+            height(a)
+                .partial_cmp(&height(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (a.x, a.y).cmp(&(b.x, b.y)))
+        })
+        .unwrap_or_else(|| Vector2::new(0, 0))
+}
+/// Finds the lift, by its base, or ski school, by its entrance, that `role` works at
+fn find_post(
+    world: &World,
+    role: Role,
+    post: Option<(i64, i64)>,
+) -> Result<Option<Entity>, StaffError> {
+    let node = match (role.has_post(), post) {
+        (false, _) => return Ok(None),
+        (true, Some((x, y))) => Node {
+            node: Vector2::new(x, y),
+        },
+        (true, None) => return Err(StaffError::NoPost(None)),
+    };
+    let found = match role {
+        Role::LiftOperator => <(Entity, &GraphLayer)>::query()
+            .iter(world)
+            .find(|(_, layer)| matches!(layer, GraphLayer::Lift(lift) if lift.start == node))
+            .map(|(entity, _)| *entity),
+        _ => <(Entity, &Building)>::query()
+            .iter(world)
+            .find(|(_, b)| b.kind == BuildingKind::SkiSchool && b.entrance == node)
+            .map(|(entity, _)| *entity),
+    };
+    found.map(Some).ok_or(StaffError::NoPost(post))
+}
+/// Adds a staff member to the world. Patrollers and groomers start at the base of the resort.
+pub fn push_staff(world: &mut World, staff: Staff) -> Entity {
+    match staff.role {
+        Role::Patroller => patrol::push_patroller(world, staff),
+        Role::Groomer => grooming::push_groomer(world, staff),
+        _ => world.push((staff,)),
+    }
+}
+/// Hires someone for `role` working `shift` at the lift or ski school at `post`
+pub fn hire_staff(
+    world: &mut World,
+    role: Role,
+    shift: Shift,
+    post: Option<(i64, i64)>,
+) -> Result<Entity, StaffError> {
+    let post = find_post(world, role, post)?;
+    Ok(push_staff(world, Staff { role, shift, post }))
+}
+/// Fires someone with `role` working `shift` at `post`, returning who was fired
+pub fn fire_staff(
+    world: &mut World,
+    role: Role,
+    shift: Shift,
+    post: Option<(i64, i64)>,
+) -> Result<Staff, StaffError> {
+    let post = find_post(world, role, post)?;
+    let (entity, staff) = <(Entity, &Staff)>::query()
+        .iter(world)
+        .find(|(_, s)| s.role == role && s.shift == shift && s.post == post)
+        .map(|(entity, staff)| (*entity, staff.clone()))
+        .ok_or(StaffError::NotEmployed)?;
+    world.remove(entity);
+    Ok(staff)
+}
+/// Lifts or buildings with at least one staff member of `role` on duty
+pub fn staffed_posts<W: EntityStore>(world: &W, role: Role) -> HashSet<Entity> {
+    let hour = hour(world);
+    <&Staff>::query()
+        .iter(world)
+        .filter(|s| s.role == role && s.shift.on_duty(hour))
+        .filter_map(|s| s.post)
+        .collect()
+}
+/// Bases of lifts that have an operator on duty, none while high wind closes the lifts
+pub fn running_lifts<W: EntityStore>(world: &W) -> HashSet<Node> {
+    if wind_hold(world) {
+        return HashSet::new();
+    }
+    let staffed = staffed_posts(world, Role::LiftOperator);
+    <(Entity, &GraphLayer)>::query()
+        .iter(world)
+        .filter(|(entity, _)| staffed.contains(entity))
+        .filter_map(|(_, layer)| match layer {
+            GraphLayer::Lift(lift) => Some(lift.start.clone()),
+            _ => None,
+        })
+        .collect()
+}
+/// Total wages paid each day
+pub fn daily_wages(world: &World) -> f32 {
+    <&Staff>::query().iter(world).map(|s| s.daily_wage()).sum()
+}
+/// Runs one tick of staff work: skiiers get hurt and patrollers go to them, groomers groom
+pub fn update_staff(world: &mut World, rng: &mut SeededRng) {
+    patrol::injure_skiiers(world, rng);
+    patrol::respond(world);
+    grooming::drive(world);
+}
+/// Staff roster window along with the shift new staff are hired for
+pub struct StaffRoster {
+    shift: Shift,
+}
+impl Default for StaffRoster {
+    fn default() -> Self {
+        Self { shift: Shift::Day }
+    }
+}
+impl StaffRoster {
+    /// Draws the staff roster. Returns a command if someone was hired or fired
+    pub fn draw_gui(&mut self, world: &World, context: &mut CtxRef) -> Option<PlayerCommand> {
+        let hour = hour(world);
+        //where each role can work, by lift base or ski school entrance
+        let lifts: Vec<(i64, i64)> = <&GraphLayer>::query()
+            .iter(world)
+            .filter_map(|layer| match layer {
+                GraphLayer::Lift(lift) => Some((lift.start.node.x, lift.start.node.y)),
+                _ => None,
+            })
+            .collect();
+        let schools: Vec<(i64, i64)> = <&Building>::query()
+            .iter(world)
+            .filter(|b| b.kind == BuildingKind::SkiSchool)
+            .map(|b| (b.entrance.node.x, b.entrance.node.y))
+            .collect();
+        let post_of = |entity: Option<Entity>| -> Option<(i64, i64)> {
+            let entry = world.entry_ref(entity?).ok()?;
+            if let Ok(GraphLayer::Lift(lift)) = entry.get_component::<GraphLayer>() {
+                return Some((lift.start.node.x, lift.start.node.y));
+            }
+            let building = entry.get_component::<Building>().ok()?;
+            Some((building.entrance.node.x, building.entrance.node.y))
+        };
+        let roster: Vec<(Staff, Option<(i64, i64)>)> = <&Staff>::query()
+            .iter(world)
+            .map(|s| (s.clone(), post_of(s.post)))
+            .collect();
+        let injuries = <&Injury>::query().iter(world).count();
+        let wages = daily_wages(world);
+        let mut command = None;
+        let shift = &mut self.shift;
+        egui::Window::new("staff").show(context, |ui| {
+            ui.label(format!(
+                "{} staff, ${:.0} in wages a day",
+                roster.len(),
+                wages
+            ));
+            ui.label(format!("skiiers waiting for ski patrol: {}", injuries));
+            for (staff, post) in roster.iter() {
+                let at = post
+                    .map(|(x, y)| format!(" at ({}, {})", x, y))
+                    .unwrap_or_default();
+                let duty = if staff.shift.on_duty(hour) {
+                    "on duty"
+                } else {
+                    "off duty"
+                };
+                ui.label(format!(
+                    "{}{}, {} shift, {}, ${:.0} a day",
+                    staff.role,
+                    at,
+                    staff.shift,
+                    duty,
+                    staff.daily_wage()
+                ));
+                if ui.button("fire").clicked {
+                    command = Some(PlayerCommand::FireStaff {
+                        role: staff.role,
+                        shift: staff.shift,
+                        post: *post,
+                    });
+                }
+            }
+            ui.label(format!("hiring for the {} shift", shift));
+            for s in Shift::ALL.iter() {
+                if ui.button(format!("{} shift", s)).clicked {
+                    *shift = *s;
+                }
+            }
+            for role in Role::ALL.iter() {
+                let posts: Vec<Option<(i64, i64)>> = match role {
+                    Role::LiftOperator => lifts.iter().map(|p| Some(*p)).collect(),
+                    Role::Instructor => schools.iter().map(|p| Some(*p)).collect(),
+                    _ => vec![None],
+                };
+                for post in posts.iter() {
+                    let at = post
+                        .map(|(x, y)| format!(" at ({}, {})", x, y))
+                        .unwrap_or_default();
+                    if ui.button(format!("hire {}{}", role, at)).clicked {
+                        command = Some(PlayerCommand::HireStaff {
+                            role: *role,
+                            shift: *shift,
+                            post: *post,
+                        });
+                    }
+                }
+            }
+        });
+        command
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lift::push_lift;
+    use crate::prelude::push_terrain;
+    #[test]
+    fn operators_run_lifts() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5),
+            &mut world,
+        );
+        world.push((Clock::default(),));
+        push_lift(&mut world, Vector2::new(0, 0), Vector2::new(4, 4));
+        assert!(running_lifts(&world).is_empty());
+        assert_eq!(
+            hire_staff(&mut world, Role::LiftOperator, Shift::Day, Some((1, 1))),
+            Err(StaffError::NoPost(Some((1, 1))))
+        );
+        hire_staff(&mut world, Role::LiftOperator, Shift::Day, Some((0, 0))).unwrap();
+        //the clock starts at midnight, before the day shift
+        assert!(running_lifts(&world).is_empty());
+        hire_staff(&mut world, Role::LiftOperator, Shift::Night, Some((0, 0))).unwrap();
+        let base = Node {
+            node: Vector2::new(0, 0),
+        };
+        assert!(running_lifts(&world).contains(&base));
+        assert_eq!(daily_wages(&world), 24.0 * Role::LiftOperator.hourly_wage());
+        let fired = fire_staff(&mut world, Role::LiftOperator, Shift::Night, Some((0, 0)));
+        assert_eq!(fired.map(|s| s.shift), Ok(Shift::Night));
+        assert!(running_lifts(&world).is_empty());
+        assert_eq!(
+            fire_staff(&mut world, Role::Groomer, Shift::Day, None),
+            Err(StaffError::NotEmployed)
+        );
+    }
+}
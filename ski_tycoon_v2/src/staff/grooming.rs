@@ -0,0 +1,178 @@
+use super::{base, Role, Shift, Staff};
+use crate::clock::Clock;
+use crate::prelude::{GraphLayer, Model, RuntimeModelId, Terrain, Texture, Transform};
+use crate::snow::SnowConditions;
+use crate::terrain::TileType;
+use legion::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+use std::collections::VecDeque;
+/// Grooming vehicle along with the tiles it still has to groom, in order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Groomer {
+    pub position: Vector2<i64>,
+    pub route: VecDeque<Vector2<i64>>,
+}
+impl Groomer {
+    /// Ticks a groomer takes to groom a tile
+    const TICKS_PER_TILE: u64 = 10;
+}
+/// Yellow block for grooming vehicles
+pub fn groomer_model() -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(Vector4::new(240, 200, 0, 255), Vector2::new(8, 8));
+    model
+}
+fn groomer_transform(position: Vector2<i64>, terrain: &Terrain) -> Transform {
+    let mut transform = Transform::default();
+    transform.set_scale(Vector3::new(0.3, 0.2, 0.3));
+    if let Some(t) = terrain.get_transform(&position) {
+        transform.set_translation(t + Vector3::new(0.0, 0.2, 0.0));
+    }
+    transform
+}
+/// Adds a groomer with their vehicle parked at the base of the resort
+pub(super) fn push_groomer(world: &mut World, staff: Staff) -> Entity {
+    let position = base(world);
+    let transform = <&Terrain>::query()
+        .iter(world)
+        .next()
+        .map(|t| groomer_transform(position, t))
+        .unwrap_or_default();
+    world.push((
+        staff,
+        Groomer {
+            position,
+            route: VecDeque::new(),
+        },
+        transform,
+        RuntimeModelId::new("groomer".to_string()),
+    ))
+}
+fn needs_grooming(terrain: &Terrain, tile: &Vector2<i64>) -> bool {
+    terrain
+        .get_tile(tile)
+        .map(|t| t.tile_type.is_snow() && t.tile_type != TileType::Groomed)
+        == Some(true)
+}
+/// Drives the grooming vehicles overnight while the runs are closed. Runs are shared out between
+/// groomers on duty in order of name, each groomer works down its runs from the top grooming a
+/// tile every few ticks.
+pub(super) fn drive(world: &mut World) {
+    let clock = match <&Clock>::query().iter(world).next() {
+        Some(clock) => clock.clone(),
+        None => return,
+    };
+    if clock.tick() % Groomer::TICKS_PER_TILE != 0 || !Shift::Night.on_duty(clock.hour()) {
+        return;
+    }
+    let (mut vehicles, mut rest) = world.split::<(&Staff, &mut Groomer, &mut Transform)>();
+    let (mut conditions, mut rest) = rest.split::<&mut SnowConditions>();
+    let mut conditions = <&mut SnowConditions>::query()
+        .iter_mut(&mut conditions)
+        .next();
+    //tiles of each run from the top down, runs in order of name
+    let mut runs: Vec<(String, Vec<Vector2<i64>>)> = {
+        let terrain = match <&Terrain>::query().iter(&rest).next() {
+            Some(terrain) => terrain,
+            None => return,
+        };
+        let height = |t: &Vector2<i64>| terrain.get_tile(t).map(|t| t.height).unwrap_or(0.0);
+        <&GraphLayer>::query()
+            .iter(&rest)
+            .filter_map(|layer| match layer {
+                GraphLayer::Trail(trail) => Some(trail),
+                _ => None,
+            })
+            .map(|trail| {
+                let mut tiles: Vec<Vector2<i64>> = trail.tiles().map(|t| t.node).collect();
+                tiles.sort_by(|a, b| {
+                    height(b)
+                        .partial_cmp(&height(a))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| (a.x, a.y).cmp(&(b.x, b.y)))
+                });
+                (trail.name.clone(), tiles)
+            })
+            .collect()
+    };
+    runs.sort_by(|a, b| a.0.cmp(&b.0));
+    let terrain = match <&mut Terrain>::query().iter_mut(&mut rest).next() {
+        Some(terrain) => terrain,
+        None => return,
+    };
+    let mut query = <(&Staff, &mut Groomer, &mut Transform)>::query();
+    let mut groomers: Vec<(&mut Groomer, &mut Transform)> = query
+        .iter_mut(&mut vehicles)
+        .filter(|(staff, _, _)| staff.role == Role::Groomer && staff.shift.on_duty(clock.hour()))
+        .map(|(_, groomer, transform)| (groomer, transform))
+        .collect();
+    let count = groomers.len();
+    for (i, (groomer, transform)) in groomers.iter_mut().enumerate() {
+        if groomer.route.is_empty() {
+            groomer.route = runs
+                .iter()
+                .skip(i)
+                .step_by(count)
+                .flat_map(|(_, tiles)| tiles.iter())
+                .filter(|t| needs_grooming(terrain, t))
+                .copied()
+                .collect();
+        }
+        //another groomer may have got to tiles on the route first
+        while let Some(tile) = groomer.route.pop_front() {
+            if needs_grooming(terrain, &tile) {
+                terrain.set_tile_type(&tile, TileType::Groomed);
+                if let Some(conditions) = conditions.as_mut() {
+                    conditions.reset(tile);
+                }
+                groomer.position = tile;
+                **transform = groomer_transform(tile, terrain);
+                break;
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::push_terrain;
+    use crate::staff::hire_staff;
+    use crate::terrain::Difficulty;
+    use crate::trail::push_trail;
+    #[test]
+    fn groomers_groom_runs_overnight() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5),
+            &mut world,
+        );
+        world.push((Clock::default(),));
+        let tiles = [Vector2::new(3, 4), Vector2::new(2, 4), Vector2::new(1, 4)];
+        push_trail(&mut world, "Run 1".to_string(), Difficulty::Green, &tiles);
+        let groomed = |world: &World| {
+            let terrain = <&Terrain>::query().iter(world).next().unwrap();
+            tiles
+                .iter()
+                .map(|t| terrain.get_tile(t).unwrap().tile_type == TileType::Groomed)
+                .collect::<Vec<bool>>()
+        };
+        drive(&mut world);
+        assert_eq!(groomed(&world), vec![false, false, false]);
+        //day shift groomers do not groom at night
+        hire_staff(&mut world, Role::Groomer, Shift::Day, None).unwrap();
+        drive(&mut world);
+        assert_eq!(groomed(&world), vec![false, false, false]);
+        let groomer = hire_staff(&mut world, Role::Groomer, Shift::Night, None).unwrap();
+        drive(&mut world);
+        //grooming starts at the top of the run
+        assert_eq!(groomed(&world), vec![true, false, false]);
+        drive(&mut world);
+        drive(&mut world);
+        assert_eq!(groomed(&world), vec![true, true, true]);
+        let entry = world.entry_ref(groomer).unwrap();
+        assert_eq!(
+            entry.get_component::<Groomer>().unwrap().position,
+            Vector2::new(1, 4)
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use super::{base, hour, Role, Staff};
+use crate::prelude::{Model, RuntimeModelId, Terrain, Texture, Transform};
+use crate::rng::SeededRng;
+use crate::skiier::{Satisfaction, Velocity};
+use legion::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+/// Skiier who fell and waits for ski patrol before skiing on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Injury {
+    /// Ticks since the skiier got hurt
+    pub waited: u32,
+    /// Patroller on their way
+    pub responder: Option<Entity>,
+}
+impl Injury {
+    /// Chance of getting hurt each tick for every tile per tick of speed
+    const CHANCE_PER_SPEED: f32 = 0.0005;
+    /// Change in satisfaction when a skiier gets hurt
+    const SATISFACTION: f32 = -0.1;
+    /// Change in satisfaction for every tick spent waiting for help
+    const WAIT_SATISFACTION: f32 = -0.0002;
+    /// Ticks after which a skiier no patroller has reached gets up on their own
+    const UNATTENDED_TICKS: u32 = 600;
+}
+/// Where a patroller is and who they are going to help
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patrol {
+    pub position: Vector2<f32>,
+    pub target: Option<Entity>,
+}
+impl Patrol {
+    /// Tiles travelled each tick
+    const SPEED: f32 = 0.3;
+    /// Distance at which a patroller reaches a skiier
+    const REACH: f32 = 0.5;
+}
+/// Red block for patrollers
+pub fn patroller_model() -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(Vector4::new(220, 0, 0, 255), Vector2::new(8, 8));
+    model
+}
+fn patrol_transform(position: Vector2<f32>, terrain: &Terrain) -> Transform {
+    let mut transform = Transform::default();
+    transform.set_scale(Vector3::new(0.12, 0.12, 0.12));
+    transform
+        .set_translation(terrain.get_transform_rounded(&position) + Vector3::new(0.0, 0.12, 0.0));
+    transform
+}
+/// Adds a patroller waiting at the base of the resort
+pub(super) fn push_patroller(world: &mut World, staff: Staff) -> Entity {
+    let base = base(world);
+    let position = Vector2::new(base.x as f32, base.y as f32);
+    let transform = <&Terrain>::query()
+        .iter(world)
+        .next()
+        .map(|t| patrol_transform(position, t))
+        .unwrap_or_default();
+    world.push((
+        staff,
+        Patrol {
+            position,
+            target: None,
+        },
+        transform,
+        RuntimeModelId::new("patroller".to_string()),
+    ))
+}
+/// Skiiers heading downhill may fall, more likely the faster they go
+pub(super) fn injure_skiiers(world: &mut World, rng: &mut SeededRng) {
+    let mut query = <(Entity, &Velocity)>::query().filter(!component::<Injury>());
+    let hurt: Vec<Entity> = query
+        .iter(world)
+        .filter(|(_, v)| v.velocity.y < 0.0)
+        .filter(|(_, v)| rng.next_f32() < v.speed() * Injury::CHANCE_PER_SPEED)
+        .map(|(entity, _)| *entity)
+        .collect();
+    for entity in hurt.into_iter() {
+        if let Some(mut entry) = world.entry(entity) {
+            if let Ok(satisfaction) = entry.get_component_mut::<Satisfaction>() {
+                satisfaction.change(Injury::SATISFACTION);
+            }
+            if let Ok(velocity) = entry.get_component_mut::<Velocity>() {
+                *velocity = Velocity::default();
+            }
+            entry.add_component(Injury {
+                waited: 0,
+                responder: None,
+            });
+        }
+    }
+}
+/// Gets a skiier back on their feet, they are less happy the longer they waited
+fn recover(world: &mut World, skiier: Entity) {
+    if let Some(mut entry) = world.entry(skiier) {
+        let waited = match entry.get_component::<Injury>() {
+            Ok(injury) => injury.waited,
+            Err(_) => return,
+        };
+        if let Ok(satisfaction) = entry.get_component_mut::<Satisfaction>() {
+            satisfaction.change(waited as f32 * Injury::WAIT_SATISFACTION);
+        }
+        entry.remove_component::<Injury>();
+    }
+}
+/// Sends idle patrollers on duty to the closest skiier waiting for help, moves patrollers
+/// towards skiiers and gets skiiers up once a patroller reaches them
+pub(super) fn respond(world: &mut World) {
+    let hour = hour(world);
+    //skiiers waiting for help along with where they are
+    let mut injuries: Vec<(Entity, Vector2<f32>, Option<Entity>)> = vec![];
+    let mut unattended = vec![];
+    for (entity, injury, transform) in <(Entity, &mut Injury, &Transform)>::query().iter_mut(world)
+    {
+        injury.waited += 1;
+        if injury.waited > Injury::UNATTENDED_TICKS {
+            unattended.push(*entity);
+        } else {
+            let t = transform.get_translation();
+            injuries.push((*entity, Vector2::new(t.x, t.z), injury.responder));
+        }
+    }
+    for skiier in unattended.into_iter() {
+        recover(world, skiier);
+    }
+    let mut assigned: Vec<(Entity, Entity)> = vec![];
+    {
+        let mut patrollers: Vec<(Entity, &mut Patrol)> = <(Entity, &Staff, &mut Patrol)>::query()
+            .iter_mut(world)
+            .filter(|(_, staff, _)| staff.role == Role::Patroller && staff.shift.on_duty(hour))
+            .map(|(entity, _, patrol)| (*entity, patrol))
+            .collect();
+        for (skiier, position, _) in injuries.iter().filter(|(_, _, r)| r.is_none()) {
+            let closest = patrollers
+                .iter_mut()
+                .filter(|(_, patrol)| patrol.target.is_none())
+                .min_by(|a, b| {
+                    (a.1.position - position)
+                        .norm()
+                        .partial_cmp(&(b.1.position - position).norm())
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            if let Some((patroller, patrol)) = closest {
+                patrol.target = Some(*skiier);
+                assigned.push((*skiier, *patroller));
+            }
+        }
+    }
+    for (skiier, patroller) in assigned.iter() {
+        if let Some(mut entry) = world.entry(*skiier) {
+            if let Ok(injury) = entry.get_component_mut::<Injury>() {
+                injury.responder = Some(*patroller);
+            }
+        }
+    }
+    let mut reached = vec![];
+    let mut moved = vec![];
+    for (entity, staff, patrol) in <(Entity, &Staff, &mut Patrol)>::query().iter_mut(world) {
+        let target = patrol.target.and_then(|target| {
+            injuries
+                .iter()
+                .find(|(skiier, _, _)| *skiier == target)
+                .map(|(skiier, position, _)| (*skiier, *position))
+        });
+        let (skiier, position) = match target {
+            Some(target) => target,
+            None => {
+                patrol.target = None;
+                continue;
+            }
+        };
+        if !staff.shift.on_duty(hour) {
+            continue;
+        }
+        let delta = position - patrol.position;
+        if delta.norm() <= Patrol::REACH {
+            reached.push(skiier);
+            patrol.target = None;
+        } else {
+            patrol.position += delta.normalize() * delta.norm().min(Patrol::SPEED);
+            moved.push((*entity, patrol.position));
+        }
+    }
+    let transforms: Vec<(Entity, Transform)> = match <&Terrain>::query().iter(world).next() {
+        Some(terrain) => moved
+            .into_iter()
+            .map(|(entity, position)| (entity, patrol_transform(position, terrain)))
+            .collect(),
+        None => vec![],
+    };
+    for (entity, transform) in transforms.into_iter() {
+        if let Some(mut entry) = world.entry(entity) {
+            if let Ok(t) = entry.get_component_mut::<Transform>() {
+                *t = transform;
+            }
+        }
+    }
+    for skiier in reached.into_iter() {
+        recover(world, skiier);
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::super::Shift;
+    use super::*;
+    use crate::prelude::push_terrain;
+    fn injured(world: &mut World, position: Vector3<f32>) -> Entity {
+        let mut transform = Transform::default();
+        transform.set_translation(position);
+        world.push((
+            transform,
+            Satisfaction::default(),
+            Injury {
+                waited: 0,
+                responder: None,
+            },
+        ))
+    }
+    #[test]
+    fn patrol_responds() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5),
+            &mut world,
+        );
+        let skiier = injured(&mut world, Vector3::new(3.0, 0.0, 0.0));
+        let patroller = push_patroller(
+            &mut world,
+            Staff {
+                role: Role::Patroller,
+                shift: Shift::AllDay,
+                post: None,
+            },
+        );
+        let waiting = |world: &World| {
+            world
+                .entry_ref(skiier)
+                .unwrap()
+                .get_component::<Injury>()
+                .is_ok()
+        };
+        respond(&mut world);
+        {
+            let entry = world.entry_ref(patroller).unwrap();
+            assert_eq!(
+                entry.get_component::<Patrol>().unwrap().target,
+                Some(skiier)
+            );
+        }
+        for _ in 0..20 {
+            respond(&mut world);
+        }
+        assert!(!waiting(&world));
+        let entry = world.entry_ref(skiier).unwrap();
+        assert!(
+            entry.get_component::<Satisfaction>().unwrap().value < Satisfaction::default().value
+        );
+    }
+    #[test]
+    fn unattended_skiiers_get_up() {
+        let mut world = World::default();
+        let skiier = injured(&mut world, Vector3::new(3.0, 0.0, 0.0));
+        for _ in 0..Injury::UNATTENDED_TICKS {
+            respond(&mut world);
+        }
+        assert!(world
+            .entry_ref(skiier)
+            .unwrap()
+            .get_component::<Injury>()
+            .is_ok());
+        respond(&mut world);
+        assert!(world
+            .entry_ref(skiier)
+            .unwrap()
+            .get_component::<Injury>()
+            .is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use super::prelude::{FollowPath, GraphLayer, Transform};
+use legion::world::SubWorld;
+use legion::*;
+use nalgebra::Vector2;
+use std::collections::HashMap;
+/// Buckets points into square cells so points near a position can be found without checking
+/// every point.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+    fn cell(&self, position: Vector2<f32>) -> (i64, i64) {
+        (
+            (position.x / self.cell_size).floor() as i64,
+            (position.y / self.cell_size).floor() as i64,
+        )
+    }
+    pub fn insert(&mut self, index: usize, position: Vector2<f32>) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(index);
+    }
+    /// Indices of every point in the cells touching the square of `radius` around `position`.
+    /// Points further than `radius` may be included.
+    pub fn near(&self, position: Vector2<f32>, radius: f32) -> Vec<usize> {
+        let min = self.cell(position - Vector2::new(radius, radius));
+        let max = self.cell(position + Vector2::new(radius, radius));
+        let mut found = vec![];
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    found.extend(cell.iter().copied());
+                }
+            }
+        }
+        found
+    }
+}
+/// How far a skiier is pushed off the path it is following to keep clear of other skiiers and
+/// lift stations. Progress along the path is not changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Steering {
+    pub offset: Vector2<f32>,
+}
+impl Default for Steering {
+    fn default() -> Self {
+        Self {
+            offset: Vector2::new(0.0, 0.0),
+        }
+    }
+}
+impl Steering {
+    /// Distance skiiers try to keep between each other, in tiles
+    const SEPARATION: f32 = 0.4;
+    /// Distance skiiers try to keep from the center of a lift station
+    const STATION_RADIUS: f32 = 0.6;
+    /// How strongly crowding pushes a skiier each tick
+    const STRENGTH: f32 = 0.1;
+    /// Fraction of the offset removed each tick so skiiers drift back to their path
+    const RETURN: f32 = 0.1;
+    /// Furthest a skiier can be from their path
+    const MAX_OFFSET: f32 = 0.45;
+}
+/// Push away from a point closer than `radius`, strongest when the points are on top of each
+/// other
+fn repel(from: Vector2<f32>, position: Vector2<f32>, radius: f32) -> Option<Vector2<f32>> {
+    let delta = position - from;
+    let distance = delta.norm();
+    if distance >= radius || distance <= f32::EPSILON {
+        None
+    } else {
+        Some(delta / distance * (radius - distance) / radius)
+    }
+}
+/// Computes the next offset of every skiier given where their paths have them this tick.
+/// Skiiers `riding` a lift are not pushed away from stations since their lift starts and ends
+/// at one. Skiiers on exactly the same spot are split apart by their order so the result does
+/// not depend on anything but the inputs.
+pub fn steer(
+    on_path: &[Vector2<f32>],
+    offsets: &[Vector2<f32>],
+    riding: &[bool],
+    stations: &[Vector2<f32>],
+) -> Vec<Vector2<f32>> {
+    let positions: Vec<Vector2<f32>> = on_path
+        .iter()
+        .zip(offsets.iter())
+        .map(|(p, o)| p + o)
+        .collect();
+    let mut hash = SpatialHash::new(Steering::SEPARATION);
+    for (i, position) in positions.iter().enumerate() {
+        hash.insert(i, *position);
+    }
+    positions
+        .iter()
+        .zip(offsets.iter())
+        .enumerate()
+        .map(|(i, (position, offset))| {
+            let mut push = Vector2::new(0.0, 0.0);
+            for j in hash.near(*position, Steering::SEPARATION) {
+                if i == j {
+                    continue;
+                }
+                if (positions[j] - position).norm() <= f32::EPSILON {
+                    push.x += if i < j { 1.0 } else { -1.0 };
+                } else if let Some(p) = repel(positions[j], *position, Steering::SEPARATION) {
+                    push += p;
+                }
+            }
+            for station in stations.iter().filter(|_| !riding[i]) {
+                if let Some(p) = repel(*station, *position, Steering::STATION_RADIUS) {
+                    push += p;
+                }
+            }
+            let next = offset * (1.0 - Steering::RETURN) + push * Steering::STRENGTH;
+            if next.norm() > Steering::MAX_OFFSET {
+                next.normalize() * Steering::MAX_OFFSET
+            } else {
+                next
+            }
+        })
+        .collect()
+}
+/// Offsets skiiers from their paths so they do not overlap each other or lift stations. Runs
+/// after skiiers have moved along their paths.
+#[system]
+#[read_component(FollowPath)]
+#[read_component(GraphLayer)]
+#[write_component(Steering)]
+#[write_component(Transform)]
+pub fn avoid_collisions(world: &mut SubWorld) {
+    let stations: Vec<Vector2<f32>> = <&GraphLayer>::query()
+        .iter(world)
+        .filter_map(|layer| match layer {
+            GraphLayer::Lift(lift) => Some(vec![lift.start.node, lift.end.node]),
+            _ => None,
+        })
+        .flatten()
+        .map(|n| Vector2::new(n.x as f32, n.y as f32))
+        .collect();
+    let mut query = <(&FollowPath, &mut Steering, &mut Transform)>::query();
+    let mut on_path = vec![];
+    let mut offsets = vec![];
+    let mut riding = vec![];
+    for (t, path, steering) in query
+        .iter_mut(world)
+        .filter_map(|(path, steering, _)| path.get().map(|t| (t, path, steering)))
+    {
+        on_path.push(Vector2::new(t.x, t.z));
+        offsets.push(steering.offset);
+        //lifts are the only way uphill
+        riding.push(
+            path.segment()
+                .map(|(start, end)| end.y - start.y > 0.0)
+                .unwrap_or(false),
+        );
+    }
+    let offsets = steer(&on_path, &offsets, &riding, &stations);
+    for ((mut t, steering, transform), offset) in query
+        .iter_mut(world)
+        .filter_map(|(path, steering, transform)| path.get().map(|t| (t, steering, transform)))
+        .zip(offsets)
+    {
+        steering.offset = offset;
+        t.x += offset.x;
+        t.z += offset.y;
+        transform.set_translation(t);
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn spatial_hash() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(0, Vector2::new(0.5, 0.5));
+        hash.insert(1, Vector2::new(1.5, 0.5));
+        hash.insert(2, Vector2::new(5.0, 5.0));
+        hash.insert(3, Vector2::new(-0.5, -0.5));
+        let mut near = hash.near(Vector2::new(0.9, 0.5), 0.2);
+        near.sort_unstable();
+        assert_eq!(near, vec![0, 1]);
+        let mut near = hash.near(Vector2::new(0.1, 0.1), 0.2);
+        near.sort_unstable();
+        assert_eq!(near, vec![0, 3]);
+        assert!(hash.near(Vector2::new(3.0, 3.0), 0.5).is_empty());
+    }
+    #[test]
+    fn separates() {
+        let zero = Vector2::new(0.0, 0.0);
+        //two skiiers on the same spot are pushed in opposite directions
+        let offsets = steer(
+            &[Vector2::new(2.0, 2.0), Vector2::new(2.0, 2.0)],
+            &[zero, zero],
+            &[false, false],
+            &[],
+        );
+        assert!(offsets[0].x > 0.0);
+        assert!(offsets[1].x < 0.0);
+        //skiiers far apart and away from stations stay on their paths
+        let offsets = steer(
+            &[Vector2::new(0.0, 0.0), Vector2::new(5.0, 5.0)],
+            &[zero, zero],
+            &[false, false],
+            &[Vector2::new(10.0, 10.0)],
+        );
+        assert_eq!(offsets, vec![zero, zero]);
+        //skiiers near a station are pushed away from it but never too far from their path
+        let mut offset = zero;
+        for _ in 0..100 {
+            offset = steer(
+                &[Vector2::new(3.1, 3.0)],
+                &[offset],
+                &[false],
+                &[Vector2::new(3.0, 3.0)],
+            )[0];
+            assert!(offset.norm() <= Steering::MAX_OFFSET + f32::EPSILON);
+        }
+        assert!(offset.x > 0.0);
+        //skiiers riding a lift stay on the line into its station
+        assert_eq!(
+            steer(
+                &[Vector2::new(3.1, 3.0)],
+                &[zero],
+                &[true],
+                &[Vector2::new(3.0, 3.0)],
+            ),
+            vec![zero]
+        );
+        //once the crowd is gone skiiers return to their path
+        for _ in 0..200 {
+            offset = steer(&[Vector2::new(0.0, 0.0)], &[offset], &[false], &[])[0];
+        }
+        assert!(offset.norm() < 0.01);
+    }
+}
@@ -1,110 +1,131 @@
+use super::graphics_system::TerrainModelOutdated;
 use super::prelude::{
-    build_skiier, insert_lift, insert_terrain, AssetManager, GraphLayer, GraphWeight, Grid,
-    GridNode, Model, RenderingContext, RuntimeModel, ShaderBind, Transform,
+    push_terrain, update_flow_fields, update_navigation_graph, update_path_hierarchies, GraphLayer,
+    GraphWeight, Grid, GridNode, Model, NavigationGraph, Transform,
+};
+use crate::{
+    clock::Clock,
+    commands::PlayerCommand,
+    finances::Finances,
+    lift::push_lift,
+    objectives::{Objective, ObjectiveTracker},
+    rng::SeededRng,
+    skiier::{spawn_skiier, GuestStatistics, Personality, Skill},
+    staff::{push_staff, Role, Shift, Staff},
+    weather::Weather,
 };
 use egui::CtxRef;
-use legion::World;
-use log::info;
+use legion::*;
+use log::{error, info};
 use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
 mod pgm_parser;
+mod scenario_file;
+pub use scenario_file::{parse_scenario, ScenarioError, TerrainSource};
+/// Directory next to the executable that scenario files are loaded from on native platforms
+#[cfg(not(target_arch = "wasm32"))]
+pub const SCENARIO_DIRECTORY: &str = "scenarios";
+/// Environment variable that overrides the scenario directory
+#[cfg(not(target_arch = "wasm32"))]
+pub const SCENARIO_DIRECTORY_VARIABLE: &str = "SKI_TYCOON_SCENARIOS";
+/// Scenarios compiled into the game, used when the scenario directory is not available
+const BUILTIN_SCENARIOS: &[(&str, &str)] = &[
+    ("cone_world.ron", include_str!("scenarios/cone_world.ron")),
+    ("pgm_file.ron", include_str!("scenarios/pgm_file.ron")),
+    (
+        "pgm_file_no_skiiers.ron",
+        include_str!("scenarios/pgm_file_no_skiiers.ron"),
+    ),
+    (
+        "small_cone_world.ron",
+        include_str!("scenarios/small_cone_world.ron"),
+    ),
+    ("toture_test.ron", include_str!("scenarios/toture_test.ron")),
+    ("volcano.ron", include_str!("scenarios/volcano.ron")),
+];
+/// Gets heightmaps compiled into the game. Paths are relative to the scenario directory
+fn builtin_heightmap(path: &str) -> Option<Vec<u8>> {
+    match path {
+        "../heightmaps/output.pgm" => Some(include_bytes!("heightmaps/output.pgm").to_vec()),
+        "../heightmaps/cone.pgm" => Some(include_bytes!("heightmaps/cone.pgm").to_vec()),
+        _ => None,
+    }
+}
 pub struct TerrainLibrary {
     entries: Vec<Scenario>,
 }
 impl Default for TerrainLibrary {
+    /// Loads the builtin scenarios, logging and skipping any that are invalid
     fn default() -> Self {
-        Self {
-            entries: vec![
-                Scenario {
-                    name: "Cone World".to_string(),
-                    terrain_ctor: Box::new(|| {
-                        Terrain::new_cone(
-                            Vector2::new(20, 20),
-                            Vector2::new(10.0, 10.0),
-                            10.0,
-                            -1.0,
-                        )
-                    }),
-                    skiier_spawn: (0..10)
-                        .map(|x| (0..10).map(move |y| Vector2::new(x, y)))
-                        .flatten()
-                        .collect(),
-                    lift_positions: vec![LiftPosition {
-                        start: Vector2::new(0, 0),
-                        end: Vector2::new(3, 3),
-                    }],
-                },
-                Scenario {
-                    name: "Small Cone World".to_string(),
-                    terrain_ctor: Box::new(|| {
-                        Terrain::new_cone(Vector2::new(5, 5), Vector2::new(10.0, 10.0), 10.0, 1.0)
-                    }),
-                    skiier_spawn: vec![],
-                    lift_positions: vec![],
-                },
-                Scenario {
-                    name: "Toture Test".to_string(),
-                    terrain_ctor: Box::new(|| {
-                        Terrain::new_cone(
-                            Vector2::new(100, 100),
-                            Vector2::new(50.0, 50.0),
-                            50.0,
-                            -1.0,
-                        )
-                    }),
-                    skiier_spawn: (0..100)
-                        .map(|x| (0..100).map(move |y| Vector2::new(x, y)))
-                        .flatten()
-                        .collect(),
-                    lift_positions: vec![LiftPosition {
-                        start: Vector2::new(0, 0),
-                        end: Vector2::new(50, 50),
-                    }],
-                },
-                Scenario {
-                    name: "PGM File".to_string(),
-                    terrain_ctor: Box::new(|| {
-                        Terrain::from_pgm(include_bytes!("heightmaps/output.pgm").to_vec(), 0.01)
-                            .unwrap()
-                    }),
-                    skiier_spawn: (0..5)
-                        .map(|x| (0..1).map(move |y| Vector2::new(x, y)))
-                        .flatten()
-                        .collect(),
-                    lift_positions: vec![LiftPosition {
-                        start: Vector2::new(0, 0),
-                        end: Vector2::new(7, 3),
-                    }],
-                },
-                Scenario {
-                    name: "PGM File No Skiiers".to_string(),
-                    terrain_ctor: Box::new(|| {
-                        Terrain::from_pgm(include_bytes!("heightmaps/output.pgm").to_vec(), 0.01)
-                            .unwrap()
-                    }),
-                    skiier_spawn: vec![],
-                    lift_positions: vec![LiftPosition {
-                        start: Vector2::new(0, 0),
-                        end: Vector2::new(7, 3),
-                    }],
-                },
-                Scenario {
-                    name: "Volcano".to_string(),
-                    terrain_ctor: Box::new(|| {
-                        Terrain::from_pgm(include_bytes!("heightmaps/cone.pgm").to_vec(), 0.001)
-                            .unwrap()
-                    }),
-                    skiier_spawn: (0..20)
-                        .map(|x| (0..20).map(move |y| Vector2::new(x, y)))
-                        .flatten()
-                        .collect(),
-                    lift_positions: vec![LiftPosition {
-                        start: Vector2::new(0, 0),
-                        end: Vector2::new(50, 50),
-                    }],
+        Self::from_sources(BUILTIN_SCENARIOS)
+    }
+}
+impl TerrainLibrary {
+    /// Parses scenarios compiled into the game given as file name and contents
+    fn from_sources(sources: &[(&str, &str)]) -> Self {
+        let entries = sources
+            .iter()
+            .filter_map(
+                |(file, source)| match parse_scenario(file, source, &builtin_heightmap) {
+                    Ok(scenario) => Some(scenario),
+                    Err(e) => {
+                        error!("invalid builtin scenario: {}", e);
+                        None
+                    }
                 },
-            ],
+            )
+            .collect();
+        Self { entries }
+    }
+    #[cfg(test)]
+    pub fn from_scenarios(entries: Vec<Scenario>) -> Self {
+        Self { entries }
+    }
+    /// Loads every valid `.ron` file in `directory` in alphabetical order, along with the error
+    /// for each file that could not be loaded
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_directory(directory: &std::path::Path) -> (Self, Vec<ScenarioError>) {
+        match Self::read_directory(directory) {
+            Ok(loaded) => loaded,
+            Err(error) => (Self { entries: vec![] }, vec![error]),
         }
     }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_directory(
+        directory: &std::path::Path,
+    ) -> Result<(Self, Vec<ScenarioError>), ScenarioError> {
+        let read_error = |error: std::io::Error| ScenarioError::Io {
+            file: directory.display().to_string(),
+            error: error.to_string(),
+        };
+        let mut paths = std::fs::read_dir(directory)
+            .map_err(read_error)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(read_error)?;
+        paths.retain(|p| p.extension().map(|e| e == "ron").unwrap_or(false));
+        paths.sort();
+        let load_heightmap = |path: &str| std::fs::read(directory.join(path)).ok();
+        let mut entries = vec![];
+        let mut errors = vec![];
+        for path in paths.iter() {
+            let file = path.display().to_string();
+            let scenario = std::fs::read_to_string(path)
+                .map_err(|e| ScenarioError::Io {
+                    file: file.clone(),
+                    error: e.to_string(),
+                })
+                .and_then(|source| parse_scenario(&file, &source, &load_heightmap));
+            match scenario {
+                Ok(s) => entries.push(s),
+                Err(e) => errors.push(e),
+            }
+        }
+        Ok((Self { entries }, errors))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 }
 pub struct LiftPosition {
     start: Vector2<i64>,
@@ -112,73 +133,195 @@ pub struct LiftPosition {
 }
 pub struct Scenario {
     pub name: String,
-    pub terrain_ctor: Box<dyn Fn() -> Terrain>,
+    pub terrain: TerrainSource,
     pub skiier_spawn: Vec<Vector2<i64>>,
     pub lift_positions: Vec<LiftPosition>,
+    pub starting_cash: f32,
+    pub objectives: Vec<Objective>,
 }
 impl Scenario {
-    pub fn build_scenario(
-        &self,
-        world: &mut World,
-        graphics: &mut RenderingContext,
-        asset_manager: &mut AssetManager<RuntimeModel>,
-        bound_shader: &ShaderBind,
-    ) {
+    /// Clears the world and builds the scenario. `rng` should be freshly seeded so that the
+    /// scenario is reproducible
+    pub fn build_scenario(&self, world: &mut World, rng: &mut SeededRng) {
         world.clear();
         info!("building scene: {}", self.name);
 
-        insert_terrain(
-            (self.terrain_ctor)(),
+        push_terrain(
+            self.terrain
+                .build(rng)
+                .expect("scenario terrain is validated when loaded"),
             world,
-            graphics,
-            asset_manager,
-            bound_shader.get_bind(),
-        )
-        .expect("failed to insert terrain");
+        );
+        world.push((
+            Clock::default(),
+            Finances::new(self.starting_cash),
+            GuestStatistics::default(),
+            ObjectiveTracker::new(self.objectives.clone()),
+            Weather::new(rng),
+        ));
+        //scenario lifts come with an operator so they run from the start
         for l in self.lift_positions.iter() {
-            insert_lift(world, graphics, asset_manager, bound_shader, l.start, l.end)
-                .expect("failed to build skiier");
+            let [lift, _] = push_lift(world, l.start, l.end);
+            push_staff(
+                world,
+                Staff {
+                    role: Role::LiftOperator,
+                    shift: Shift::AllDay,
+                    post: Some(lift),
+                },
+            );
         }
+        update_navigation_graph(world);
+        update_path_hierarchies(world);
+        update_flow_fields(world);
         for s in self.skiier_spawn.iter() {
-            build_skiier(world, graphics, asset_manager, bound_shader, *s)
-                .expect("failed to build skiier");
+            let skill = Skill::random(rng);
+            let personality = Personality::random(rng);
+            spawn_skiier(world, *s, skill, personality);
         }
     }
 }
 impl TerrainLibrary {
-    pub fn draw_gui(
-        &self,
-        world: &mut World,
-        context: &mut CtxRef,
-        graphics: &mut RenderingContext,
-        asset_manager: &mut AssetManager<RuntimeModel>,
-        bound_shader: &ShaderBind,
-    ) {
+    pub fn get(&self, name: &str) -> Option<&Scenario> {
+        self.entries.iter().find(|s| s.name == name)
+    }
+    /// Draws the scenario picker. Returns a command to load a scenario if one was picked. `rng`
+    /// picks the seed of the new scenario.
+    pub fn draw_gui(&self, context: &mut CtxRef, rng: &mut SeededRng) -> Option<PlayerCommand> {
+        let mut command = None;
         egui::Window::new("Scenarios").show(context, |ui| {
             for t in self.entries.iter() {
                 ui.label(t.name.to_string());
+                for objective in t.objectives.iter() {
+                    ui.label(format!("    {}", objective));
+                }
                 if ui.button("").clicked {
-                    t.build_scenario(world, graphics, asset_manager, bound_shader);
+                    command = Some(PlayerCommand::LoadScenario {
+                        name: t.name.clone(),
+                        seed: rng.next_u64(),
+                    });
                 }
             }
         });
+        command
     }
 }
-#[derive(Clone, Debug, PartialEq)]
+/// Difficulty rating of a run
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Difficulty {
+    Green,
+    Blue,
+    Black,
+    DoubleBlack,
+}
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Green,
+        Difficulty::Blue,
+        Difficulty::Black,
+        Difficulty::DoubleBlack,
+    ];
+    /// Gets the difficulty of a slope with grade (rise over run) `grade`
+    pub fn from_grade(grade: f32) -> Self {
+        let grade = grade.abs();
+        if grade < 0.25 {
+            Self::Green
+        } else if grade < 0.4 {
+            Self::Blue
+        } else if grade < 0.6 {
+            Self::Black
+        } else {
+            Self::DoubleBlack
+        }
+    }
+}
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Green => write!(f, "Green"),
+            Self::Blue => write!(f, "Blue"),
+            Self::Black => write!(f, "Black"),
+            Self::DoubleBlack => write!(f, "Double Black"),
+        }
+    }
+}
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TileType {
+    /// Packed snow that has been skied on
     Snow,
+    /// Snow packed smooth by a groomer, faster to ski on
+    Groomed,
+    /// Fresh snow that has not been skied yet, deep and slow
+    Powder,
+    /// Bumps carved into steep slopes by skiier traffic
+    Moguls,
+    /// Snow scraped down to ice by skiier traffic, fast and hard to control
+    Ice,
+    /// Covered by a building, skiiers can not move onto it
+    Building,
+    /// Snow too thin to cover the ground, slow and unpleasant to ski over
+    Rocks,
+}
+impl TileType {
+    /// Coefficient of friction between skis and the tile
+    pub fn friction(&self) -> f32 {
+        match self {
+            Self::Snow => 0.05,
+            Self::Groomed => 0.03,
+            Self::Powder => 0.1,
+            Self::Moguls => 0.08,
+            Self::Ice => 0.01,
+            Self::Building => 1.0,
+            Self::Rocks => 0.3,
+        }
+    }
+    /// Whether the tile is snow that skiiers ski on and that changes with traffic and weather.
+    /// Buildings and rocks showing through thin cover are not.
+    pub fn is_snow(&self) -> bool {
+        !matches!(self, Self::Building | Self::Rocks)
+    }
+}
+impl std::fmt::Display for TileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Snow => write!(f, "packed snow"),
+            Self::Groomed => write!(f, "groomed"),
+            Self::Powder => write!(f, "powder"),
+            Self::Moguls => write!(f, "moguls"),
+            Self::Ice => write!(f, "ice"),
+            Self::Building => write!(f, "building"),
+            Self::Rocks => write!(f, "rocks"),
+        }
+    }
 }
 #[derive(Clone, Debug, PartialEq)]
 pub struct Tile {
     pub height: f32,
     pub tile_type: TileType,
+    /// Centimeters of snow on the tile
+    pub snow_depth: f32,
+}
+impl Tile {
+    /// Depth of the snow when a scenario starts
+    pub const STARTING_SNOW_DEPTH: f32 = 80.0;
 }
 #[derive(Clone, Debug, PartialEq)]
 pub struct Terrain {
     tiles: Vec<Tile>,
     dimensions: Vector2<usize>,
+    /// Tiles whose type or height changed since the edits were last taken
+    edited: Vec<Vector2<i64>>,
+    /// Whether the height of an edited tile changed
+    reshaped: bool,
 }
 impl Terrain {
+    /// Distance part of the weight of moving to a tile along the x or y axis
+    pub const STRAIGHT_WEIGHT: i32 = 10;
+    /// Distance part of the weight of moving to a diagonal tile, 10 * sqrt(2) rounded down
+    pub const DIAGONAL_WEIGHT: i32 = 14;
+    /// Extra weight of moving onto a rocky tile, high enough that skiiers go around bare
+    /// patches when there is any snow to ski on
+    pub const ROCKS_WEIGHT: i32 = 1000;
     /// Builds cone terrain with centar at center and slope of `slope`
     pub fn new_cone(
         dimensions: Vector2<usize>,
@@ -186,8 +329,7 @@ impl Terrain {
         center_height: f32,
         slope: f32,
     ) -> Self {
-        let mut tiles = vec![];
-        tiles.reserve(dimensions.x * dimensions.y);
+        let mut tiles = Vec::with_capacity(dimensions.x * dimensions.y);
         for x in 0..dimensions.x {
             for y in 0..dimensions.y {
                 let radius = ((x as f32 - center.x).powi(2) + (y as f32 - center.y).powi(2)).sqrt();
@@ -195,27 +337,73 @@ impl Terrain {
                 tiles.push(Tile {
                     height,
                     tile_type: TileType::Snow,
+                    snow_depth: Tile::STARTING_SNOW_DEPTH,
+                });
+            }
+        }
+        Self::from_tiles(tiles, dimensions)
+    }
+
+    /// Builds rolling hills on a slope that descends along the x axis. Hills are made from
+    /// smoothly interpolated random heights, `roughness` is the height of hills relative to
+    /// `height`.
+    pub fn new_random(
+        dimensions: Vector2<usize>,
+        height: f32,
+        roughness: f32,
+        rng: &mut SeededRng,
+    ) -> Self {
+        const FEATURE_SIZE: usize = 8;
+        let control_dimensions = Vector2::new(
+            dimensions.x / FEATURE_SIZE + 2,
+            dimensions.y / FEATURE_SIZE + 2,
+        );
+        let control: Vec<f32> = (0..control_dimensions.x * control_dimensions.y)
+            .map(|_| rng.next_f32())
+            .collect();
+        let control_height = |x: usize, y: usize| control[x * control_dimensions.y + y];
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let mut tiles = Vec::with_capacity(dimensions.x * dimensions.y);
+        for x in 0..dimensions.x {
+            for y in 0..dimensions.y {
+                let (x0, y0) = (x / FEATURE_SIZE, y / FEATURE_SIZE);
+                let tx = smooth((x % FEATURE_SIZE) as f32 / FEATURE_SIZE as f32);
+                let ty = smooth((y % FEATURE_SIZE) as f32 / FEATURE_SIZE as f32);
+                let noise = (control_height(x0, y0) * (1.0 - tx) + control_height(x0 + 1, y0) * tx)
+                    * (1.0 - ty)
+                    + (control_height(x0, y0 + 1) * (1.0 - tx)
+                        + control_height(x0 + 1, y0 + 1) * tx)
+                        * ty;
+                let slope = height * (1.0 - x as f32 / dimensions.x as f32);
+                tiles.push(Tile {
+                    height: slope + noise * roughness * height,
+                    tile_type: TileType::Snow,
+                    snow_depth: Tile::STARTING_SNOW_DEPTH,
                 });
             }
         }
-        Self { tiles, dimensions }
+        Self::from_tiles(tiles, dimensions)
     }
 
     pub fn from_pgm(data: Vec<u8>, scaling: f32) -> Option<Self> {
         if let Ok(s) = String::from_utf8(data) {
-            if let Ok(t) = pgm_parser::terrain_from_pgm(s, TileType::Snow, scaling) {
-                Some(t)
-            } else {
-                None
-            }
+            pgm_parser::terrain_from_pgm(s, TileType::Snow, scaling).ok()
         } else {
             None
         }
     }
     pub fn from_tiles(tiles: Vec<Tile>, dimensions: Vector2<usize>) -> Self {
-        Self { tiles, dimensions }
+        Self {
+            tiles,
+            dimensions,
+            edited: vec![],
+            reshaped: false,
+        }
     }
 
+    pub fn dimensions(&self) -> Vector2<usize> {
+        self.dimensions
+    }
     pub fn model(&self) -> Model {
         let heights = self.tiles.iter().map(|t| t.height).collect();
         Model::from_heights(heights, self.dimensions, Transform::default())
@@ -238,19 +426,92 @@ impl Terrain {
         ))
         .unwrap()
     }
+    /// Gets the tile at a coordinate, none if the coordinate is outside of the terrain
+    pub fn get_tile(&self, coordinate: &Vector2<i64>) -> Option<&Tile> {
+        if coordinate.x < 0
+            || coordinate.y < 0
+            || coordinate.x >= self.dimensions.x as i64
+            || coordinate.y >= self.dimensions.y as i64
+        {
+            None
+        } else {
+            Some(&self.tiles[coordinate.x as usize * self.dimensions.y + coordinate.y as usize])
+        }
+    }
+    /// Replaces the tile at a coordinate, returning the old tile. None if the coordinate is
+    /// outside of the terrain
+    pub fn set_tile(&mut self, coordinate: &Vector2<i64>, tile: Tile) -> Option<Tile> {
+        self.get_tile(coordinate)?;
+        let index = coordinate.x as usize * self.dimensions.y + coordinate.y as usize;
+        let old = std::mem::replace(&mut self.tiles[index], tile);
+        let new = &self.tiles[index];
+        if old.tile_type != new.tile_type || old.height != new.height {
+            self.edited.push(*coordinate);
+            self.reshaped |= old.height != new.height;
+        }
+        Some(old)
+    }
+    /// Takes the tiles edited since the last call along with whether any of their heights changed
+    pub fn take_edits(&mut self) -> (Vec<Vector2<i64>>, bool) {
+        let mut tiles = std::mem::take(&mut self.edited);
+        tiles.sort_by_key(|t| (t.x, t.y));
+        tiles.dedup();
+        (tiles, std::mem::replace(&mut self.reshaped, false))
+    }
+    /// Changes the snow on a tile, keeping its height. Returns what the tile used to be.
+    pub fn set_tile_type(
+        &mut self,
+        coordinate: &Vector2<i64>,
+        tile_type: TileType,
+    ) -> Option<TileType> {
+        let tile = self.get_tile(coordinate)?;
+        let (height, snow_depth) = (tile.height, tile.snow_depth);
+        self.set_tile(
+            coordinate,
+            Tile {
+                height,
+                tile_type,
+                snow_depth,
+            },
+        )
+        .map(|t| t.tile_type)
+    }
+    /// Centimeters of snow on a tile, none off the terrain
+    pub fn snow_depth(&self, coordinate: &Vector2<i64>) -> f32 {
+        self.get_tile(coordinate)
+            .map(|t| t.snow_depth)
+            .unwrap_or(0.0)
+    }
+    /// Changes the depth of the snow on a tile. Returns the old depth, none if the coordinate is
+    /// outside of the terrain
+    pub fn set_snow_depth(&mut self, coordinate: &Vector2<i64>, snow_depth: f32) -> Option<f32> {
+        self.get_tile(coordinate)?;
+        let index = coordinate.x as usize * self.dimensions.y + coordinate.y as usize;
+        Some(std::mem::replace(
+            &mut self.tiles[index].snow_depth,
+            snow_depth,
+        ))
+    }
     pub fn get_transform(&self, coordinate: &Vector2<i64>) -> Option<Vector3<f32>> {
-        let pos = coordinate.x as usize * self.dimensions.y + coordinate.y as usize;
-        if pos < self.tiles.len() {
-            Some(Vector3::new(
-                coordinate.x as f32,
-                self.tiles[pos].height,
-                coordinate.y as f32,
-            ))
+        self.get_tile(coordinate)
+            .map(|tile| Vector3::new(coordinate.x as f32, tile.height, coordinate.y as f32))
+    }
+    /// Average grade (rise over run) between two points. Returns none if either point is outside
+    /// of the terrain or if the points are the same
+    pub fn grade(&self, start: Vector2<i64>, end: Vector2<i64>) -> Option<f32> {
+        let start_position = self.get_transform(&start)?;
+        let end_position = self.get_transform(&end)?;
+        let run = Vector2::new((end.x - start.x) as f32, (end.y - start.y) as f32).norm();
+        if run > 0.0 {
+            Some((end_position.y - start_position.y).abs() / run)
         } else {
             None
         }
     }
-    fn get_weight(&self, start: Vector2<i64>, end: Vector2<i64>) -> GraphWeight {
+    /// Weight of moving between neighboring tiles. Made of the distance travelled plus a cost
+    /// for the change in height. Tiles covered by buildings can be left but not entered and
+    /// rocky tiles are only entered when there is no way around them.
+    pub fn get_weight(&self, start: Vector2<i64>, end: Vector2<i64>) -> GraphWeight {
         if end.x >= self.dimensions.x as i64
             || end.x < 0
             || end.y >= self.dimensions.y as i64
@@ -264,44 +525,238 @@ impl Terrain {
         } else {
             let start_tile = &self.tiles[start.x as usize * self.dimensions.y + start.y as usize];
             let end_tile = &self.tiles[end.x as usize * self.dimensions.y + end.y as usize];
+            if end_tile.tile_type == TileType::Building {
+                return GraphWeight::Infinity;
+            }
+            let distance = if start.x != end.x && start.y != end.y {
+                Self::DIAGONAL_WEIGHT
+            } else {
+                Self::STRAIGHT_WEIGHT
+            };
+            let distance = if end_tile.tile_type == TileType::Rocks {
+                distance + Self::ROCKS_WEIGHT
+            } else {
+                distance
+            };
             let delta_height = start_tile.height - end_tile.height;
             if delta_height as i32 >= 0 {
-                GraphWeight::Some((delta_height * 100.0).abs() as i32)
+                GraphWeight::Some(distance + (delta_height * 100.0).abs() as i32)
             } else {
-                GraphWeight::Some((delta_height * 10.0).abs() as i32)
+                GraphWeight::Some(distance + (delta_height * 10.0).abs() as i32)
             }
         }
     }
     pub fn build_graph(&self) -> GraphLayer {
-        let mut data = vec![];
-        data.reserve(self.dimensions.x * self.dimensions.y);
+        let mut data = Vec::with_capacity(self.dimensions.x * self.dimensions.y);
         for x in 0..self.dimensions.x {
             for y in 0..self.dimensions.y {
-                let x_plus = self.get_weight(
-                    Vector2::new(x as i64, y as i64),
-                    Vector2::new(x as i64 + 1, y as i64),
-                );
-                let x_minus = self.get_weight(
-                    Vector2::new(x as i64, y as i64),
-                    Vector2::new(x as i64 - 1, y as i64),
-                );
-                let z_plus = self.get_weight(
-                    Vector2::new(x as i64, y as i64),
-                    Vector2::new(x as i64, y as i64 + 1),
-                );
-                let z_minus = self.get_weight(
-                    Vector2::new(x as i64, y as i64),
-                    Vector2::new(x as i64, y as i64 + 1),
-                );
-                data.push(GridNode {
-                    x_plus,
-                    x_minus,
-                    z_plus,
-                    z_minus,
-                });
+                let position = Vector2::new(x as i64, y as i64);
+                data.push(GridNode::from_weights(|direction| {
+                    self.get_weight(position, position + direction)
+                }));
             }
         }
         let grid = Grid::from_vec(data, self.dimensions);
         GraphLayer::Grid { grid }
     }
+    /// Rebuilds the nodes of a grid layer with an edge to or from one of `tiles`. Returns the
+    /// nodes that changed.
+    pub fn update_graph(
+        &self,
+        layer: &mut GraphLayer,
+        tiles: &[Vector2<i64>],
+    ) -> Vec<(Vector2<i64>, GridNode)> {
+        let grid = match layer {
+            GraphLayer::Grid { grid } => grid,
+            _ => return vec![],
+        };
+        let mut affected: Vec<Vector2<i64>> = tiles
+            .iter()
+            .flat_map(|tile| {
+                GridNode::DIRECTIONS
+                    .iter()
+                    .map(move |(x, y)| tile + Vector2::new(*x, *y))
+                    .chain(std::iter::once(*tile))
+            })
+            .filter(|tile| self.get_tile(tile).is_some())
+            .collect();
+        affected.sort_by_key(|t| (t.x, t.y));
+        affected.dedup();
+        affected
+            .into_iter()
+            .filter_map(|position| {
+                let node = GridNode::from_weights(|direction| {
+                    self.get_weight(position, position + direction)
+                });
+                let index = Vector2::new(position.x as usize, position.y as usize);
+                if grid[index].weights() == node.weights() {
+                    None
+                } else {
+                    grid[index] = node.clone();
+                    Some((position, node))
+                }
+            })
+            .collect()
+    }
+}
+/// Tiles of the terrain along with their coordinates, used to edit the terrain and undo edits
+pub type TileEdits = Vec<(Vector2<i64>, Tile)>;
+/// Replaces tiles of the terrain in the world, returning the tiles that were replaced. Only the
+/// graph around the replaced tiles is rebuilt.
+pub fn replace_tiles(world: &mut World, tiles: &[(Vector2<i64>, Tile)]) -> TileEdits {
+    let terrain = match <&mut Terrain>::query().iter_mut(world).next() {
+        Some(terrain) => terrain,
+        None => return vec![],
+    };
+    let replaced = tiles
+        .iter()
+        .filter_map(|(coordinate, tile)| {
+            terrain
+                .set_tile(coordinate, tile.clone())
+                .map(|old| (*coordinate, old))
+        })
+        .collect();
+    apply_terrain_edits(world);
+    replaced
+}
+/// Brings the grid layer, navigation graph and model of the terrain up to date with tiles that
+/// were edited in place. Only the nodes around edited tiles are rebuilt and they are handed to
+/// the navigation graph as a dirty region.
+pub fn apply_terrain_edits(world: &mut World) {
+    let mut edits = vec![];
+    for (entity, terrain, layer) in
+        <(Entity, &mut Terrain, &mut GraphLayer)>::query().iter_mut(world)
+    {
+        let (tiles, reshaped) = terrain.take_edits();
+        if !tiles.is_empty() {
+            edits.push((*entity, terrain.update_graph(layer, &tiles), reshaped));
+        }
+    }
+    for (entity, nodes, reshaped) in edits.into_iter() {
+        if reshaped {
+            if let Some(mut entry) = world.entry(entity) {
+                entry.add_component(TerrainModelOutdated {});
+            }
+        }
+        if let Some(graph) = <&mut NavigationGraph>::query().iter_mut(world).next() {
+            graph.edit_grid(entity, nodes);
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn grade_off_terrain() {
+        let terrain = Terrain::new_cone(Vector2::new(4, 4), Vector2::new(2.0, 2.0), 4.0, -1.0);
+        assert!(terrain
+            .grade(Vector2::new(1, 1), Vector2::new(2, 2))
+            .is_some());
+        assert_eq!(
+            terrain.grade(Vector2::new(0, 0), Vector2::new(-1, -1)),
+            None
+        );
+        assert_eq!(terrain.grade(Vector2::new(0, 3), Vector2::new(0, 4)), None);
+        assert_eq!(terrain.get_transform(&Vector2::new(-1, 0)), None);
+    }
+    #[test]
+    fn edits_update_graph_in_place() {
+        let mut world = World::default();
+        push_terrain(
+            Terrain::new_cone(Vector2::new(8, 8), Vector2::new(4.0, 4.0), 4.0, -0.5),
+            &mut world,
+        );
+        update_navigation_graph(&mut world);
+        let entity = *<(Entity, &Terrain)>::query().iter(&world).next().unwrap().0;
+        world
+            .entry(entity)
+            .unwrap()
+            .remove_component::<TerrainModelOutdated>();
+        let tile = Vector2::new(3, 3);
+        let weight = |world: &World| {
+            let graph = <&NavigationGraph>::query().iter(world).next().unwrap();
+            match graph.layers()[0] {
+                GraphLayer::Grid { grid } => grid[Vector2::new(3, 2)].get(Vector2::new(0, 1)),
+                _ => panic!("expected grid"),
+            }
+        };
+        assert!(weight(&world).is_finite());
+        let old = {
+            let terrain = <&Terrain>::query().iter(&world).next().unwrap();
+            terrain.get_tile(&tile).unwrap().clone()
+        };
+        let building = Tile {
+            tile_type: TileType::Building,
+            ..old.clone()
+        };
+        replace_tiles(&mut world, &[(tile, building)]);
+        update_navigation_graph(&mut world);
+        //the terrain is edited where it is, not pushed again
+        let entry = world.entry(entity).unwrap();
+        assert!(entry.get_component::<TerrainModelOutdated>().is_err());
+        {
+            let graph = <&NavigationGraph>::query().iter(&world).next().unwrap();
+            assert_eq!(graph.version(), 1);
+            assert!(!graph.last_change().full);
+            assert!(graph.last_change().regions[0]
+                .tiles
+                .contains(&Vector2::new(3, 2)));
+        }
+        assert_eq!(weight(&world), GraphWeight::Infinity);
+        //changing the height rebuilds the model
+        replace_tiles(
+            &mut world,
+            &[(
+                tile,
+                Tile {
+                    height: old.height + 1.0,
+                    ..old
+                },
+            )],
+        );
+        update_navigation_graph(&mut world);
+        assert!(weight(&world).is_finite());
+        let entry = world.entry(entity).unwrap();
+        assert!(entry.get_component::<TerrainModelOutdated>().is_ok());
+    }
+    #[test]
+    fn invalid_builtin_scenarios_are_skipped() {
+        let library = TerrainLibrary::from_sources(&[
+            ("bad.ron", "Scenario(name: "),
+            (
+                "cone.ron",
+                r#"Scenario(
+                    name: "Cone",
+                    terrain: Cone(dimensions: (4, 4), center: (2.0, 2.0), center_height: 4.0, slope: -1.0),
+                    starting_cash: 1000.0,
+                )"#,
+            ),
+        ]);
+        assert!(library.get("Cone").is_some());
+        assert!(!TerrainLibrary::default().is_empty());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn invalid_scenarios_are_skipped() {
+        let directory = std::env::temp_dir().join("ski_tycoon_invalid_scenarios_are_skipped");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("a_cone.ron"),
+            r#"Scenario(
+                name: "Cone",
+                terrain: Cone(dimensions: (4, 4), center: (2.0, 2.0), center_height: 4.0, slope: -1.0),
+                starting_cash: 1000.0,
+            )"#,
+        )
+        .unwrap();
+        std::fs::write(directory.join("b_bad.ron"), "Scenario(name: ").unwrap();
+        let (library, errors) = TerrainLibrary::from_directory(&directory);
+        std::fs::remove_dir_all(&directory).ok();
+        assert!(library.get("Cone").is_some());
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            ScenarioError::Parse { file, .. } => assert!(file.ends_with("b_bad.ron")),
+            _ => panic!("expected parse error"),
+        }
+    }
 }
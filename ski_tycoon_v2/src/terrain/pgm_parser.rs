@@ -69,8 +69,7 @@ pub fn terrain_from_pgm(
             error: max_height_string,
         });
     };
-    let mut tiles = vec![];
-    tiles.reserve(x_dimensions * y_dimensions);
+    let mut tiles = Vec::with_capacity(x_dimensions * y_dimensions);
     for _x in 0..x_dimensions {
         for _y in 0..y_dimensions {
             let height_string = if let Some(s) = iter.next() {
@@ -89,6 +88,7 @@ pub fn terrain_from_pgm(
             tiles.push(Tile {
                 height: (height as f32) * y_scaling,
                 tile_type: default_tile_type.clone(),
+                snow_depth: Tile::STARTING_SNOW_DEPTH,
             });
         }
     }
@@ -221,13 +221,15 @@ mod test {
             "
             .to_string(),
             TileType::Snow,
+            1.0,
         );
         assert_eq!(
             terrain,
             Ok(Terrain::from_tiles(
                 vec![Tile {
                     height: 10_000.0,
-                    tile_type: TileType::Snow
+                    tile_type: TileType::Snow,
+                    snow_depth: Tile::STARTING_SNOW_DEPTH,
                 }],
                 Vector2::new(1, 1)
             ))
@@ -244,13 +246,15 @@ mod test {
             "
             .to_string(),
             TileType::Snow,
+            1.0,
         );
         assert_eq!(
             terrain,
             Ok(Terrain::from_tiles(
                 vec![Tile {
                     height: 10_000.0,
-                    tile_type: TileType::Snow
+                    tile_type: TileType::Snow,
+                    snow_depth: Tile::STARTING_SNOW_DEPTH,
                 }],
                 Vector2::new(1, 1)
             ))
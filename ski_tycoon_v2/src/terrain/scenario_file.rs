@@ -0,0 +1,388 @@
+use super::{pgm_parser, LiftPosition, Scenario, Terrain, TileType};
+use crate::{objectives::Objective, rng::SeededRng};
+use nalgebra::Vector2;
+use serde::Deserialize;
+/// Where the terrain of a scenario comes from
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum TerrainSource {
+    /// Cone shaped terrain, see `Terrain::new_cone`
+    Cone {
+        dimensions: (usize, usize),
+        center: (f32, f32),
+        center_height: f32,
+        slope: f32,
+    },
+    /// Random hills on a slope, see `Terrain::new_random`. Generated from the scenario's seed
+    Random {
+        dimensions: (usize, usize),
+        height: f32,
+        roughness: f32,
+    },
+    /// PGM heightmap. `path` is relative to the scenario directory
+    Heightmap {
+        path: String,
+        scaling: f32,
+        /// contents of the heightmap, filled in when the scenario is validated
+        #[serde(skip)]
+        data: Vec<u8>,
+    },
+}
+impl TerrainSource {
+    pub fn build(&self, rng: &mut SeededRng) -> Result<Terrain, pgm_parser::ParseError> {
+        match self {
+            Self::Cone {
+                dimensions,
+                center,
+                center_height,
+                slope,
+            } => Ok(Terrain::new_cone(
+                Vector2::new(dimensions.0, dimensions.1),
+                Vector2::new(center.0, center.1),
+                *center_height,
+                *slope,
+            )),
+            Self::Random {
+                dimensions,
+                height,
+                roughness,
+            } => Ok(Terrain::new_random(
+                Vector2::new(dimensions.0, dimensions.1),
+                *height,
+                *roughness,
+                rng,
+            )),
+            Self::Heightmap { scaling, data, .. } => pgm_parser::terrain_from_pgm(
+                String::from_utf8_lossy(data).to_string(),
+                TileType::Snow,
+                *scaling,
+            ),
+        }
+    }
+}
+/// Tiles skiiers are placed on when the scenario starts
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum SpawnArea {
+    Point((i64, i64)),
+    /// Every tile in the rectangle from `start` (inclusive) to `end` (exclusive)
+    Area {
+        start: (i64, i64),
+        end: (i64, i64),
+    },
+}
+impl SpawnArea {
+    pub fn positions(&self) -> Vec<Vector2<i64>> {
+        match self {
+            Self::Point((x, y)) => vec![Vector2::new(*x, *y)],
+            Self::Area { start, end } => (start.0..end.0)
+                .flat_map(|x| (start.1..end.1).map(move |y| Vector2::new(x, y)))
+                .collect(),
+        }
+    }
+}
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LiftDescription {
+    start: (i64, i64),
+    end: (i64, i64),
+}
+/// On disk layout of a scenario
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename = "Scenario")]
+pub struct ScenarioFile {
+    name: String,
+    terrain: TerrainSource,
+    #[serde(default)]
+    skiier_spawns: Vec<SpawnArea>,
+    #[serde(default)]
+    lifts: Vec<LiftDescription>,
+    starting_cash: f32,
+    #[serde(default)]
+    objectives: Vec<Objective>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScenarioError {
+    Io {
+        file: String,
+        error: String,
+    },
+    /// File is not valid RON or does not match the scenario layout
+    Parse {
+        file: String,
+        error: String,
+    },
+    /// File parsed but a field holds an invalid value
+    InvalidField {
+        file: String,
+        field: String,
+        reason: String,
+    },
+}
+impl std::fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { file, error } => write!(f, "{}: failed to read: {}", file, error),
+            Self::Parse { file, error } => write!(f, "{}: {}", file, error),
+            Self::InvalidField {
+                file,
+                field,
+                reason,
+            } => write!(f, "{}: field `{}`: {}", file, field, reason),
+        }
+    }
+}
+/// Parses and validates a scenario. `load_heightmap` gets the contents of a heightmap given its
+/// path relative to the scenario directory.
+pub fn parse_scenario(
+    file: &str,
+    source: &str,
+    load_heightmap: &dyn Fn(&str) -> Option<Vec<u8>>,
+) -> Result<Scenario, ScenarioError> {
+    let mut scenario: ScenarioFile =
+        ron::de::from_str(source).map_err(|e| ScenarioError::Parse {
+            file: file.to_string(),
+            error: e.to_string(),
+        })?;
+    let invalid = |field: String, reason: String| ScenarioError::InvalidField {
+        file: file.to_string(),
+        field,
+        reason,
+    };
+    if scenario.name.trim().is_empty() {
+        return Err(invalid("name".to_string(), "must not be empty".to_string()));
+    }
+    match &mut scenario.terrain {
+        TerrainSource::Cone { dimensions, .. } | TerrainSource::Random { dimensions, .. } => {
+            if dimensions.0 < 2 || dimensions.1 < 2 {
+                return Err(invalid(
+                    "terrain.dimensions".to_string(),
+                    format!("{:?} must be at least (2, 2)", dimensions),
+                ));
+            }
+        }
+        TerrainSource::Heightmap {
+            path,
+            scaling,
+            data,
+        } => {
+            if !scaling.is_finite() || *scaling <= 0.0 {
+                return Err(invalid(
+                    "terrain.scaling".to_string(),
+                    format!("{} is not a positive number", scaling),
+                ));
+            }
+            *data = load_heightmap(path).ok_or_else(|| {
+                invalid(
+                    "terrain.path".to_string(),
+                    format!("heightmap \"{}\" not found", path),
+                )
+            })?;
+        }
+    }
+    //terrain is only built to check its dimensions so the seed does not matter
+    let terrain = scenario
+        .terrain
+        .build(&mut SeededRng::new(0))
+        .map_err(|e| {
+            invalid(
+                "terrain.path".to_string(),
+                format!("invalid heightmap: {:?}", e),
+            )
+        })?;
+    let dimensions = terrain.dimensions();
+    let check_bounds = |field: String, point: (i64, i64)| {
+        if point.0 < 0
+            || point.1 < 0
+            || point.0 >= dimensions.x as i64
+            || point.1 >= dimensions.y as i64
+        {
+            Err(invalid(
+                field,
+                format!(
+                    "{:?} is outside of the terrain of size ({}, {})",
+                    point, dimensions.x, dimensions.y
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    };
+    for (i, spawn) in scenario.skiier_spawns.iter().enumerate() {
+        match spawn {
+            SpawnArea::Point(p) => check_bounds(format!("skiier_spawns[{}]", i), *p)?,
+            SpawnArea::Area { start, end } => {
+                //the end is exclusive so it is only checked once the area is known not to be
+                //empty
+                if end.0 <= start.0 || end.1 <= start.1 {
+                    return Err(invalid(
+                        format!("skiier_spawns[{}].end", i),
+                        format!("{:?} must be greater than start {:?}", end, start),
+                    ));
+                }
+                check_bounds(format!("skiier_spawns[{}].start", i), *start)?;
+                check_bounds(format!("skiier_spawns[{}].end", i), (end.0 - 1, end.1 - 1))?;
+            }
+        }
+    }
+    for (i, lift) in scenario.lifts.iter().enumerate() {
+        check_bounds(format!("lifts[{}].start", i), lift.start)?;
+        check_bounds(format!("lifts[{}].end", i), lift.end)?;
+        if lift.start == lift.end {
+            return Err(invalid(
+                format!("lifts[{}].end", i),
+                "lift must not start and end at the same point".to_string(),
+            ));
+        }
+    }
+    if !scenario.starting_cash.is_finite() || scenario.starting_cash < 0.0 {
+        return Err(invalid(
+            "starting_cash".to_string(),
+            format!("{} is not a positive amount", scenario.starting_cash),
+        ));
+    }
+    for (i, objective) in scenario.objectives.iter().enumerate() {
+        objective
+            .validate()
+            .map_err(|(field, reason)| invalid(format!("objectives[{}].{}", i, field), reason))?;
+    }
+    Ok(Scenario {
+        name: scenario.name,
+        terrain: scenario.terrain,
+        skiier_spawn: scenario
+            .skiier_spawns
+            .iter()
+            .flat_map(|s| s.positions())
+            .collect(),
+        lift_positions: scenario
+            .lifts
+            .iter()
+            .map(|l| LiftPosition {
+                start: Vector2::new(l.start.0, l.start.1),
+                end: Vector2::new(l.end.0, l.end.1),
+            })
+            .collect(),
+        starting_cash: scenario.starting_cash,
+        objectives: scenario.objectives,
+    })
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn no_heightmaps(_path: &str) -> Option<Vec<u8>> {
+        None
+    }
+    #[test]
+    fn cone() {
+        let scenario = parse_scenario(
+            "cone.ron",
+            r#"Scenario(
+                name: "Cone",
+                terrain: Cone(dimensions: (4, 4), center: (2.0, 2.0), center_height: 4.0, slope: -1.0),
+                skiier_spawns: [Point((0, 0)), Area(start: (1, 1), end: (3, 2))],
+                lifts: [(start: (0, 0), end: (2, 2))],
+                starting_cash: 1000.0,
+                objectives: [Cash(amount: 2000.0)],
+            )"#,
+            &no_heightmaps,
+        )
+        .unwrap();
+        assert_eq!(scenario.name, "Cone");
+        assert_eq!(
+            scenario.skiier_spawn,
+            vec![Vector2::new(0, 0), Vector2::new(1, 1), Vector2::new(2, 1)]
+        );
+        assert_eq!(scenario.lift_positions.len(), 1);
+        assert_eq!(
+            scenario.objectives,
+            vec![Objective::Cash { amount: 2000.0 }]
+        );
+    }
+    #[test]
+    fn lift_out_of_bounds() {
+        let error = parse_scenario(
+            "cone.ron",
+            r#"Scenario(
+                name: "Cone",
+                terrain: Cone(dimensions: (4, 4), center: (2.0, 2.0), center_height: 4.0, slope: -1.0),
+                lifts: [(start: (0, 0), end: (2, 2)), (start: (0, 0), end: (4, 2))],
+                starting_cash: 1000.0,
+            )"#,
+            &no_heightmaps,
+        )
+        .err()
+        .unwrap();
+        match error {
+            ScenarioError::InvalidField { field, .. } => assert_eq!(field, "lifts[1].end"),
+            _ => panic!("expected invalid field"),
+        }
+    }
+    #[test]
+    fn reversed_spawn_area() {
+        let error = parse_scenario(
+            "cone.ron",
+            r#"Scenario(
+                name: "Cone",
+                terrain: Cone(dimensions: (4, 4), center: (2.0, 2.0), center_height: 4.0, slope: -1.0),
+                skiier_spawns: [Area(start: (2, 2), end: (0, 0))],
+                starting_cash: 1000.0,
+            )"#,
+            &no_heightmaps,
+        )
+        .err()
+        .unwrap();
+        match error {
+            ScenarioError::InvalidField { field, reason, .. } => {
+                assert_eq!(field, "skiier_spawns[0].end");
+                assert!(reason.contains("must be greater than start"));
+            }
+            _ => panic!("expected invalid field"),
+        }
+    }
+    #[test]
+    fn missing_heightmap() {
+        let error = parse_scenario(
+            "map.ron",
+            r#"Scenario(
+                name: "Map",
+                terrain: Heightmap(path: "missing.pgm", scaling: 1.0),
+                starting_cash: 1000.0,
+            )"#,
+            &no_heightmaps,
+        )
+        .err()
+        .unwrap();
+        match error {
+            ScenarioError::InvalidField { field, .. } => assert_eq!(field, "terrain.path"),
+            _ => panic!("expected invalid field"),
+        }
+    }
+    #[test]
+    fn invalid_objective() {
+        let error = parse_scenario(
+            "cone.ron",
+            r#"Scenario(
+                name: "Cone",
+                terrain: Cone(dimensions: (4, 4), center: (2.0, 2.0), center_height: 4.0, slope: -1.0),
+                starting_cash: 1000.0,
+                objectives: [AverageSatisfaction(percent: 170.0)],
+            )"#,
+            &no_heightmaps,
+        )
+        .err()
+        .unwrap();
+        match error {
+            ScenarioError::InvalidField { field, .. } => {
+                assert_eq!(field, "objectives[0].percent")
+            }
+            _ => panic!("expected invalid field"),
+        }
+    }
+    #[test]
+    fn syntax_error() {
+        let error = parse_scenario("bad.ron", "Scenario(name: ", &no_heightmaps)
+            .err()
+            .unwrap();
+        match error {
+            ScenarioError::Parse { file, .. } => assert_eq!(file, "bad.ron"),
+            _ => panic!("expected parse error"),
+        }
+    }
+}
@@ -6,8 +6,7 @@ pub struct RGBATexture {
 }
 impl RGBATexture {
     pub fn get_raw_vector(&self) -> Vec<u8> {
-        let mut v = vec![];
-        v.reserve((self.dimensions.x * self.dimensions.y * 4) as usize);
+        let mut v = Vec::with_capacity((self.dimensions.x * self.dimensions.y * 4) as usize);
         for pixel in self.pixels.iter() {
             v.push(pixel.x);
             v.push(pixel.y);
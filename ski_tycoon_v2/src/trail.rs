@@ -0,0 +1,259 @@
+use super::commands::PlayerCommand;
+use super::prelude::{GraphLayer, Model, RuntimeModelId, Terrain, Texture, TrailLayer, Transform};
+use super::terrain::Difficulty;
+use egui::CtxRef;
+use legion::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+/// Usage of a run
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct TrailStatistics {
+    /// Number of times a skiier skied the run
+    pub runs: u32,
+}
+/// Id of the sign model placed at the top of runs of a difficulty
+pub fn sign_model_id(difficulty: Difficulty) -> String {
+    match difficulty {
+        Difficulty::Green => "sign_green",
+        Difficulty::Blue => "sign_blue",
+        Difficulty::Black => "sign_black",
+        Difficulty::DoubleBlack => "sign_double_black",
+    }
+    .to_string()
+}
+/// Sign colored by difficulty. Double black signs are black with a yellow band so they stand
+/// out from black signs.
+pub fn sign_model(difficulty: Difficulty) -> Model {
+    let color = match difficulty {
+        Difficulty::Green => Vector4::new(0, 160, 0, 255),
+        Difficulty::Blue => Vector4::new(0, 0, 200, 255),
+        Difficulty::Black | Difficulty::DoubleBlack => Vector4::new(20, 20, 20, 255),
+    };
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(color, Vector2::new(8, 8));
+    if difficulty == Difficulty::DoubleBlack {
+        //middle two rows
+        for pixel in model.texture.pixels[24..40].iter_mut() {
+            *pixel = Vector4::new(230, 200, 0, 255);
+        }
+    }
+    model
+}
+/// Model marking tiles of a trail that is being painted
+pub fn marker_model() -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(Vector4::new(255, 120, 0, 255), Vector2::new(8, 8));
+    model
+}
+/// Adds a run to the world with its sign placed on the highest tile. Tiles must be on the
+/// terrain and connected.
+pub fn push_trail(
+    world: &mut World,
+    name: String,
+    difficulty: Difficulty,
+    tiles: &[Vector2<i64>],
+) -> Entity {
+    let (layer, sign_position) = {
+        let terrain = <&Terrain>::query().iter(world).next().unwrap();
+        let sign_position = tiles
+            .iter()
+            .filter_map(|t| terrain.get_transform(t))
+            .fold(None, |acc: Option<Vector3<f32>>, p| match acc {
+                Some(highest) if highest.y >= p.y => Some(highest),
+                _ => Some(p),
+            })
+            .unwrap();
+        (
+            TrailLayer::new(name, difficulty, tiles, terrain),
+            sign_position,
+        )
+    };
+    let mut transform = Transform::default();
+    transform.set_scale(Vector3::new(0.05, 0.3, 0.05));
+    transform.set_translation(sign_position + Vector3::new(0.0, 0.3, 0.0));
+    world.push((
+        GraphLayer::Trail(layer),
+        TrailStatistics::default(),
+        transform,
+        RuntimeModelId::new(sign_model_id(difficulty)),
+    ))
+}
+/// Gets the first name of the form "Run n" that is not used by a run in the world
+fn next_trail_name(world: &World) -> String {
+    let names: Vec<String> = <&GraphLayer>::query()
+        .iter(world)
+        .filter_map(|layer| match layer {
+            GraphLayer::Trail(t) => Some(t.name.clone()),
+            _ => None,
+        })
+        .collect();
+    (1..)
+        .map(|i| format!("Run {}", i))
+        .find(|name| !names.contains(name))
+        .unwrap()
+}
+/// Trail that is being painted
+struct TrailDraft {
+    difficulty: Difficulty,
+    tiles: Vec<Vector2<i64>>,
+    cursor: Vector2<i64>,
+    /// Entities marking painted tiles
+    markers: Vec<Entity>,
+}
+impl TrailDraft {
+    fn remove_markers(&self, world: &mut World) {
+        for marker in self.markers.iter() {
+            world.remove(*marker);
+        }
+    }
+}
+/// Tool for painting runs onto the terrain
+#[derive(Default)]
+pub struct PaintTrail {
+    draft: Option<TrailDraft>,
+}
+impl PaintTrail {
+    /// Draws the trail window listing runs and their usage. Returns a command to build the run
+    /// once painting is done
+    pub fn draw_gui(&mut self, world: &mut World, context: &mut CtxRef) -> Option<PlayerCommand> {
+        let terrain = <&Terrain>::query().iter(world).next();
+        let has_terrain = terrain.is_some();
+        //difficulty suggested by the slope between the ends of the trail being painted
+        let suggested = match (terrain, self.draft.as_ref()) {
+            (Some(terrain), Some(draft)) if draft.tiles.len() >= 2 => terrain
+                .grade(draft.tiles[0], draft.tiles[draft.tiles.len() - 1])
+                .map(Difficulty::from_grade),
+            _ => None,
+        };
+        let runs: Vec<String> = <(&GraphLayer, &TrailStatistics)>::query()
+            .iter(world)
+            .filter_map(|(layer, statistics)| match layer {
+                GraphLayer::Trail(t) => Some(format!(
+                    "{} ({}): {} tiles, skied {} times",
+                    t.name,
+                    t.difficulty,
+                    t.tiles().count(),
+                    statistics.runs
+                )),
+                _ => None,
+            })
+            .collect();
+        let mut start = false;
+        let mut finish = false;
+        let mut cancel = false;
+        let mut difficulty = None;
+        let mut direction: Vector2<i64> = Vector2::new(0, 0);
+        let draft = &self.draft;
+        egui::Window::new("trails").show(context, |ui| {
+            for run in runs.iter() {
+                ui.label(run);
+            }
+            if !has_terrain {
+                ui.label("Load a scenario to paint trails");
+                return;
+            }
+            match draft {
+                None => {
+                    if ui.button("paint trail").clicked {
+                        start = true;
+                    }
+                }
+                Some(draft) => {
+                    ui.label(format!(
+                        "Painting {} trail, {} tiles",
+                        draft.difficulty,
+                        draft.tiles.len()
+                    ));
+                    if let Some(suggested) = suggested {
+                        ui.label(format!("Slope suggests {}", suggested));
+                    }
+                    for d in Difficulty::ALL.iter() {
+                        if ui.button(format!("{}", d)).clicked {
+                            difficulty = Some(*d);
+                        }
+                    }
+                    if ui.button("x+").clicked {
+                        direction.x += 1;
+                    }
+                    if ui.button("x-").clicked {
+                        direction.x -= 1;
+                    }
+                    if ui.button("y+").clicked {
+                        direction.y += 1;
+                    }
+                    if ui.button("y-").clicked {
+                        direction.y -= 1;
+                    }
+                    if draft.tiles.len() >= 2 && ui.button("finish trail").clicked {
+                        finish = true;
+                    }
+                    if ui.button("cancel").clicked {
+                        cancel = true;
+                    }
+                }
+            }
+        });
+        if start {
+            let mut draft = TrailDraft {
+                difficulty: Difficulty::Green,
+                tiles: vec![],
+                cursor: Vector2::new(0, 0),
+                markers: vec![],
+            };
+            Self::paint(&mut draft, world, Vector2::new(0, 0));
+            self.draft = Some(draft);
+        }
+        if let Some(draft) = self.draft.as_mut() {
+            if let Some(d) = difficulty {
+                draft.difficulty = d;
+            }
+            if direction != Vector2::new(0, 0) {
+                let cursor = draft.cursor + direction;
+                Self::paint(draft, world, cursor);
+            }
+        }
+        if cancel {
+            if let Some(draft) = self.draft.take() {
+                draft.remove_markers(world);
+            }
+        }
+        if finish {
+            if let Some(draft) = self.draft.take() {
+                draft.remove_markers(world);
+                return Some(PlayerCommand::BuildTrail {
+                    name: next_trail_name(world),
+                    difficulty: draft.difficulty,
+                    tiles: draft.tiles.iter().map(|t| (t.x, t.y)).collect(),
+                });
+            }
+        }
+        None
+    }
+    /// Moves the cursor to `tile` and adds it to the trail if it is on the terrain
+    fn paint(draft: &mut TrailDraft, world: &mut World, tile: Vector2<i64>) {
+        let translation = {
+            let terrain = match <&Terrain>::query().iter(world).next() {
+                Some(terrain) => terrain,
+                None => return,
+            };
+            let dimensions = terrain.dimensions();
+            if tile.x < 0
+                || tile.y < 0
+                || tile.x >= dimensions.x as i64
+                || tile.y >= dimensions.y as i64
+            {
+                return;
+            }
+            terrain.get_transform(&tile).unwrap()
+        };
+        draft.cursor = tile;
+        if !draft.tiles.contains(&tile) {
+            draft.tiles.push(tile);
+            let mut transform = Transform::default();
+            transform.set_scale(Vector3::new(0.1, 0.1, 0.1));
+            transform.set_translation(translation);
+            draft
+                .markers
+                .push(world.push((transform, RuntimeModelId::new("trail_marker".to_string()))));
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use super::clock::Clock;
+use super::prelude::{Model, RuntimeModelId, Terrain, Texture, Transform};
+use super::rng::SeededRng;
+use super::skiier::{Satisfaction, Skill};
+use super::snow;
+use egui::CtxRef;
+use legion::world::EntityStore;
+use legion::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+/// Weather expected over a day
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forecast {
+    /// Centimeters of snow expected over the day
+    pub snowfall: f32,
+    /// Average temperature in degrees celsius
+    pub temperature: f32,
+    /// Average wind speed in km/h
+    pub wind: f32,
+    /// Whether low cloud sits on the mountain in the morning
+    pub fog: bool,
+}
+impl Forecast {
+    /// Chance of snow on a day
+    const SNOW_CHANCE: f32 = 0.3;
+    /// Chance of snow the day after it snowed, storms often last a few days
+    const STORM_CHANCE: f32 = 0.5;
+    /// Chance of a foggy morning
+    const FOG_CHANCE: f32 = 0.2;
+    /// Warmest day it can snow on
+    const SNOW_TEMPERATURE: f32 = 1.0;
+    /// Temperature days drift back towards
+    const AVERAGE_TEMPERATURE: f32 = -5.0;
+    /// Snowfall that brings out the most extra guests
+    const POWDER_DAY: f32 = 20.0;
+    /// Makes tomorrow's forecast, following on from today's weather
+    pub fn next(&self, rng: &mut SeededRng) -> Self {
+        let chance = if self.snowfall > 0.0 {
+            Self::STORM_CHANCE
+        } else {
+            Self::SNOW_CHANCE
+        };
+        let snowing = rng.next_f32() < chance;
+        let temperature = Self::AVERAGE_TEMPERATURE
+            + (self.temperature - Self::AVERAGE_TEMPERATURE) * 0.5
+            + rng.range_f32(-6.0, 6.0);
+        let snowfall = if snowing && temperature < Self::SNOW_TEMPERATURE {
+            rng.range_f32(5.0, 40.0)
+        } else {
+            0.0
+        };
+        //storms bring wind
+        let wind = rng.range_f32(0.0, 30.0) + (snowfall / 40.0) * rng.range_f32(0.0, 40.0);
+        let fog = rng.next_f32() < Self::FOG_CHANCE;
+        Self {
+            snowfall,
+            temperature,
+            wind,
+            fog,
+        }
+    }
+    /// Guests that come compared to an average day. Fresh snow brings guests out, bitter cold
+    /// and wind keep them at home.
+    pub fn turnout(&self) -> f32 {
+        let mut turnout = 1.0 + 0.3 * (self.snowfall / Self::POWDER_DAY).min(1.0);
+        if self.temperature < Conditions::BITTER_COLD {
+            turnout *= 0.7;
+        }
+        if self.wind > Weather::LIFT_WIND_LIMIT {
+            turnout *= 0.6;
+        }
+        turnout
+    }
+}
+impl Default for Forecast {
+    fn default() -> Self {
+        Self {
+            snowfall: 0.0,
+            temperature: Self::AVERAGE_TEMPERATURE,
+            wind: 10.0,
+            fog: false,
+        }
+    }
+}
+impl std::fmt::Display for Forecast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.0} cm of snow, {:.0}°C, wind {:.0} km/h",
+            self.snowfall, self.temperature, self.wind
+        )?;
+        if self.fog {
+            write!(f, ", morning fog")?;
+        }
+        Ok(())
+    }
+}
+/// Weather over the current hour
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conditions {
+    /// Centimeters of snow falling this hour
+    pub snowfall: f32,
+    /// Degrees celsius
+    pub temperature: f32,
+    /// Wind speed in km/h
+    pub wind: f32,
+    /// How far skiiers can see, from 0 in a whiteout to 1 on a clear day
+    pub visibility: f32,
+}
+impl Conditions {
+    /// Snowfall in an hour that cuts visibility the most
+    const HEAVY_SNOW: f32 = 3.0;
+    /// Difference between the warmest and average temperature of a day
+    const DAILY_SWING: f32 = 4.0;
+    /// Last hour of morning fog
+    const FOG_LIFTS: u32 = 11;
+    /// Temperature below which skiiers are uncomfortable
+    const BITTER_COLD: f32 = -15.0;
+    /// Wind speed above which skiiers are uncomfortable
+    const GUSTY: f32 = 30.0;
+    /// Visibility below which skiiers are uncomfortable
+    const POOR_VISIBILITY: f32 = 0.4;
+    /// Change in satisfaction each tick for every uncomfortable part of the weather
+    const DISCOMFORT: f32 = -0.0001;
+    /// Change in satisfaction each tick on a calm, clear day
+    const BLUEBIRD: f32 = 0.0001;
+    /// Conditions for an hour of a day. It is coldest before dawn and warmest mid afternoon,
+    /// snow and wind come and go around the forecast.
+    pub fn for_hour(forecast: &Forecast, hour: u32, rng: &mut SeededRng) -> Self {
+        let angle = (hour as f32 - 9.0) / 24.0 * 2.0 * std::f32::consts::PI;
+        let temperature = forecast.temperature + Self::DAILY_SWING * angle.sin();
+        let snowfall = forecast.snowfall / 24.0 * rng.range_f32(0.0, 2.0);
+        let wind = forecast.wind * rng.range_f32(0.6, 1.4);
+        let fog = if forecast.fog && hour <= Self::FOG_LIFTS {
+            0.6
+        } else {
+            0.0
+        };
+        let visibility = (1.0 - 0.7 * (snowfall / Self::HEAVY_SNOW).min(1.0) - fog).max(0.1);
+        Self {
+            snowfall,
+            temperature,
+            wind,
+            visibility,
+        }
+    }
+    /// Change in satisfaction for each tick skiiers spend out in this weather
+    pub fn comfort(&self) -> f32 {
+        let discomforts = [
+            self.temperature < Self::BITTER_COLD,
+            self.wind > Self::GUSTY,
+            self.visibility < Self::POOR_VISIBILITY,
+        ]
+        .iter()
+        .filter(|d| **d)
+        .count();
+        if discomforts > 0 {
+            discomforts as f32 * Self::DISCOMFORT
+        } else if self.snowfall == 0.0 && self.visibility > 0.8 && self.wind < 15.0 {
+            Self::BLUEBIRD
+        } else {
+            0.0
+        }
+    }
+}
+/// Weather of the current scenario, changes every hour
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weather {
+    pub today: Forecast,
+    pub tomorrow: Forecast,
+    pub conditions: Conditions,
+}
+impl Weather {
+    /// Wind speed above which lifts close
+    pub const LIFT_WIND_LIMIT: f32 = 50.0;
+    /// Centimeters of snow that melt each hour for every degree above freezing
+    const MELT_RATE: f32 = 0.2;
+    pub fn new(rng: &mut SeededRng) -> Self {
+        let today = Forecast::default();
+        let tomorrow = today.next(rng);
+        let conditions = Conditions::for_hour(&today, 0, rng);
+        Self {
+            today,
+            tomorrow,
+            conditions,
+        }
+    }
+    /// Whether the wind is too strong for lifts to run
+    pub fn wind_hold(&self) -> bool {
+        self.conditions.wind > Self::LIFT_WIND_LIMIT
+    }
+}
+/// Whether lifts are closed by wind, false if no scenario is loaded
+pub fn wind_hold<W: EntityStore>(world: &W) -> bool {
+    <&Weather>::query()
+        .iter(world)
+        .next()
+        .map(|w| w.wind_hold())
+        == Some(true)
+}
+/// Guests that come today compared to an average day
+pub fn turnout(world: &World) -> f32 {
+    <&Weather>::query()
+        .iter(world)
+        .next()
+        .map(|w| w.today.turnout())
+        .unwrap_or(1.0)
+}
+/// Moves the weather on, making a forecast for tomorrow at midnight and new conditions every
+/// hour. Snow falls and melts by the hour and skiiers feel the weather every tick.
+pub fn update_weather(world: &mut World, rng: &mut SeededRng) {
+    let tick = match <&Clock>::query().iter(world).next() {
+        Some(clock) => clock.tick(),
+        None => return,
+    };
+    let comfort = match <&mut Weather>::query().iter_mut(world).next() {
+        Some(weather) => {
+            if tick % Clock::TICKS_PER_DAY == 0 {
+                weather.today = weather.tomorrow.clone();
+                weather.tomorrow = weather.today.next(rng);
+            }
+            if tick % Clock::TICKS_PER_HOUR == 0 {
+                let hour = ((tick % Clock::TICKS_PER_DAY) / Clock::TICKS_PER_HOUR) as u32;
+                weather.conditions = Conditions::for_hour(&weather.today, hour, rng);
+            }
+            weather.conditions.comfort()
+        }
+        None => return,
+    };
+    for (_, satisfaction) in <(&Skill, &mut Satisfaction)>::query().iter_mut(world) {
+        satisfaction.change(comfort);
+    }
+    if tick % Clock::TICKS_PER_HOUR == 0 {
+        let conditions = <&Weather>::query()
+            .iter(world)
+            .next()
+            .map(|w| w.conditions.clone());
+        if let Some(conditions) = conditions {
+            snow::snowfall(world, conditions.snowfall);
+            if conditions.temperature > 0.0 {
+                snow::melt(world, conditions.temperature * Weather::MELT_RATE);
+            }
+        }
+    }
+}
+/// Draws the weather window with the current conditions and forecasts
+pub fn draw_gui(world: &World, context: &mut CtxRef) {
+    let weather = match <&Weather>::query().iter(world).next() {
+        Some(weather) => weather,
+        None => return,
+    };
+    let conditions = &weather.conditions;
+    egui::Window::new("weather").show(context, |ui| {
+        ui.label(format!(
+            "now: {:.1} cm of snow an hour, {:.0}°C, wind {:.0} km/h, visibility {:.0}%",
+            conditions.snowfall,
+            conditions.temperature,
+            conditions.wind,
+            conditions.visibility * 100.0
+        ));
+        if weather.wind_hold() {
+            ui.label("lifts are closed by high wind");
+        }
+        ui.label(format!("today: {}", weather.today));
+        ui.label(format!("tomorrow: {}", weather.tomorrow));
+    });
+}
+/// Falling snowflake, only used for drawing snowfall
+pub struct Snowflake {
+    index: usize,
+    /// Times the snowflake has gone back to the top
+    fallen: usize,
+}
+/// Layer of fog drawn when visibility drops
+pub struct FogLayer;
+/// Most snowflakes drawn, in the heaviest snow
+const MAX_SNOWFLAKES: usize = 300;
+/// Most fog layers drawn, in a whiteout
+const MAX_FOG_LAYERS: usize = 4;
+/// Distance snowflakes fall each frame
+const FALL_SPEED: f32 = 0.05;
+/// Distance snowflakes drift each frame for every km/h of wind
+const WIND_DRIFT: f32 = 0.001;
+/// Height above the top of the mountain snowflakes start at
+const SNOW_CEILING: f32 = 3.0;
+pub fn snowflake_model() -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(Vector4::new(255, 255, 255, 255), Vector2::new(8, 8));
+    model
+}
+/// Translucent so the mountain shows through
+pub fn fog_model() -> Model {
+    let mut model = Model::cube(Transform::default());
+    model.texture = Texture::constant_color(Vector4::new(230, 230, 235, 70), Vector2::new(8, 8));
+    model
+}
+/// Color the sky is cleared to, greying out as visibility drops
+pub fn sky_color(world: &World) -> Vector4<f32> {
+    let visibility = <&Weather>::query()
+        .iter(world)
+        .next()
+        .map(|w| w.conditions.visibility)
+        .unwrap_or(1.0);
+    let clear = Vector4::new(0.2, 0.2, 0.2, 1.0);
+    let fog = Vector4::new(0.7, 0.7, 0.72, 1.0);
+    clear.lerp(&fog, 1.0 - visibility)
+}
+/// Color of the sunlight, dimmed under snow and fog
+pub fn sun_color(world: &World) -> Vector4<f32> {
+    let visibility = <&Weather>::query()
+        .iter(world)
+        .next()
+        .map(|w| w.conditions.visibility)
+        .unwrap_or(1.0);
+    let brightness = 0.6 + 0.4 * visibility;
+    Vector4::new(brightness, brightness, brightness, 1.0)
+}
+/// Spreads snowflakes over the map without using the simulation's rng, returns a number in
+/// [0, 1)
+fn scatter(index: usize, fallen: usize, salt: u64) -> f32 {
+    let mut x = (index as u64)
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        .wrapping_add((fallen as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9))
+        .wrapping_add(salt);
+    x ^= x >> 31;
+    x = x.wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 29;
+    (x % 10_000) as f32 / 10_000.0
+}
+/// Area snow falls over: the size of the map and the heights snowflakes fall between
+struct SnowBox {
+    dimensions: Vector2<f32>,
+    ground: f32,
+    ceiling: f32,
+}
+impl SnowBox {
+    /// Where a snowflake starts falling. Snowflakes added during a snowfall start anywhere in
+    /// the air, ones that reached the ground start again from the top.
+    fn start(&self, index: usize, fallen: usize) -> Transform {
+        let height = if fallen == 0 {
+            self.ground + scatter(index, fallen, 1) * (self.ceiling - self.ground)
+        } else {
+            self.ceiling
+        };
+        let mut transform = Transform::default();
+        transform.set_scale(Vector3::new(0.03, 0.03, 0.03));
+        transform.set_translation(Vector3::new(
+            scatter(index, fallen, 0) * self.dimensions.x,
+            height,
+            scatter(index, fallen, 2) * self.dimensions.y,
+        ));
+        transform
+    }
+}
+/// Keeps the snowflakes and fog drawn in step with the weather. Snowflakes fall and drift with
+/// the wind, starting again from the top once they reach the ground.
+pub fn update_particles(world: &mut World) {
+    let (snowflakes, fog_layers, wind) = match <&Weather>::query().iter(world).next() {
+        Some(weather) => {
+            let c = &weather.conditions;
+            let intensity = (c.snowfall / Conditions::HEAVY_SNOW).min(1.0);
+            (
+                (intensity * MAX_SNOWFLAKES as f32) as usize,
+                ((1.0 - c.visibility) * MAX_FOG_LAYERS as f32).round() as usize,
+                c.wind,
+            )
+        }
+        None => (0, 0, 0.0),
+    };
+    //heights of the ground under each snowflake along with the area snow falls over
+    let (landed, area) = {
+        let terrain = <&Terrain>::query().iter(world).next();
+        let dimensions = terrain
+            .map(|t| t.dimensions())
+            .unwrap_or_else(|| Vector2::new(0, 0));
+        let heights: Vec<f32> = terrain
+            .map(|t| {
+                (0..dimensions.x as i64)
+                    .flat_map(|x| (0..dimensions.y as i64).map(move |y| Vector2::new(x, y)))
+                    .filter_map(|tile| t.get_tile(&tile).map(|tile| tile.height))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let ground = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+        let ground = if ground.is_finite() { ground } else { 0.0 };
+        let top = heights.iter().cloned().fold(ground, f32::max);
+        let area = SnowBox {
+            dimensions: Vector2::new(dimensions.x as f32, dimensions.y as f32),
+            ground,
+            ceiling: top + SNOW_CEILING,
+        };
+        let landed: Vec<Entity> = <(Entity, &Transform)>::query()
+            .filter(component::<Snowflake>())
+            .iter(world)
+            .filter(|(_, transform)| {
+                let t = transform.get_translation();
+                let tile = Vector2::new(t.x.round() as i64, t.z.round() as i64);
+                let height = terrain
+                    .and_then(|terrain| terrain.get_tile(&tile))
+                    .map(|tile| tile.height)
+                    .unwrap_or(area.ground);
+                t.y - FALL_SPEED < height
+            })
+            .map(|(entity, _)| *entity)
+            .collect();
+        (landed, area)
+    };
+    let mut query = <(Entity, &mut Snowflake, &mut Transform)>::query();
+    for (entity, flake, transform) in query.iter_mut(world) {
+        if landed.contains(entity) {
+            flake.fallen += 1;
+            *transform = area.start(flake.index, flake.fallen);
+        } else {
+            let mut t = transform.get_translation();
+            t += Vector3::new(wind * WIND_DRIFT, -FALL_SPEED, 0.0);
+            if t.x >= area.dimensions.x {
+                t.x -= area.dimensions.x;
+            }
+            transform.set_translation(t);
+        }
+    }
+    //matching the number of snowflakes and fog layers to the weather
+    let mut existing: Vec<(Entity, usize)> = <(Entity, &Snowflake)>::query()
+        .iter(world)
+        .map(|(entity, flake)| (*entity, flake.index))
+        .collect();
+    existing.sort_by_key(|(_, index)| *index);
+    for (entity, _) in existing.iter().skip(snowflakes) {
+        world.remove(*entity);
+    }
+    for index in existing.len()..snowflakes {
+        world.push((
+            Snowflake { index, fallen: 0 },
+            area.start(index, 0),
+            RuntimeModelId::new("snowflake".to_string()),
+        ));
+    }
+    let layers: Vec<Entity> = <(Entity, &FogLayer)>::query()
+        .iter(world)
+        .map(|(entity, _)| *entity)
+        .collect();
+    for entity in layers.iter().skip(fog_layers) {
+        world.remove(*entity);
+    }
+    for _ in layers.len()..fog_layers {
+        world.push((
+            FogLayer,
+            Transform::default(),
+            RuntimeModelId::new("fog".to_string()),
+        ));
+    }
+    //fog layers are spread from the bottom of the mountain up
+    let spacing = (area.ceiling - SNOW_CEILING - area.ground) / (MAX_FOG_LAYERS as f32 + 1.0);
+    for (i, transform) in <&mut Transform>::query()
+        .filter(component::<FogLayer>())
+        .iter_mut(world)
+        .enumerate()
+    {
+        transform.set_scale(Vector3::new(
+            area.dimensions.x / 2.0,
+            0.01,
+            area.dimensions.y / 2.0,
+        ));
+        transform.set_translation(Vector3::new(
+            area.dimensions.x / 2.0,
+            area.ground + spacing * (i as f32 + 1.0),
+            area.dimensions.y / 2.0,
+        ));
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn forecasts_follow_on() {
+        let mut rng = SeededRng::new(0);
+        let mut forecast = Forecast::default();
+        let mut snowy_days = 0;
+        for _ in 0..200 {
+            forecast = forecast.next(&mut rng);
+            if forecast.snowfall > 0.0 {
+                snowy_days += 1;
+                assert!(forecast.temperature < Forecast::SNOW_TEMPERATURE);
+            }
+            let conditions = Conditions::for_hour(&forecast, 3, &mut rng);
+            assert!(conditions.visibility >= 0.1 && conditions.visibility <= 1.0);
+            assert!(conditions.temperature < forecast.temperature);
+        }
+        assert!(snowy_days > 20 && snowy_days < 150);
+    }
+    #[test]
+    fn weather_affects_guests() {
+        let calm = Forecast::default();
+        let powder_day = Forecast {
+            snowfall: 30.0,
+            ..calm.clone()
+        };
+        let storm = Forecast {
+            wind: 70.0,
+            temperature: -20.0,
+            ..calm.clone()
+        };
+        assert!(powder_day.turnout() > calm.turnout());
+        assert!(storm.turnout() < calm.turnout());
+        let mut conditions = Conditions {
+            snowfall: 0.0,
+            temperature: -5.0,
+            wind: 5.0,
+            visibility: 1.0,
+        };
+        assert!(conditions.comfort() > 0.0);
+        conditions.visibility = 0.2;
+        conditions.wind = 60.0;
+        assert_eq!(conditions.comfort(), 2.0 * Conditions::DISCOMFORT);
+        let weather = Weather {
+            today: calm.clone(),
+            tomorrow: calm,
+            conditions,
+        };
+        assert!(weather.wind_hold());
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+/// How independent work items are run. Parallel work uses rayon's thread pool on native
+/// platforms. The browser has no threads so parallel work runs on the calling thread there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WorkerPool {
+    Serial,
+    #[default]
+    Parallel,
+}
+impl WorkerPool {
+    /// Applies `f` to every item. Results are in the same order as `items` no matter how the
+    /// work was split up.
+    pub fn map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync + Send,
+    {
+        match self {
+            Self::Serial => items.iter().map(f).collect(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Parallel => items.par_iter().map(f).collect(),
+            #[cfg(target_arch = "wasm32")]
+            Self::Parallel => items.iter().map(f).collect(),
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn keeps_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let serial = WorkerPool::Serial.map(&items, |i| i * 2);
+        let parallel = WorkerPool::Parallel.map(&items, |i| i * 2);
+        assert_eq!(serial, parallel);
+        assert_eq!(parallel[999], 1998);
+    }
+}
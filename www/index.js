@@ -50,8 +50,16 @@ function press_putton(event) {
     let button_event = new Map();
     button_event.set("name", "keypress");
     button_event.set("key", event.key)
+    button_event.set("ctrl", event.ctrlKey)
     events.push(button_event)
 }
+//keypress is not sent for control shortcuts so they are caught on keydown
+function on_key_down(event) {
+    if (event.ctrlKey && (event.key === "z" || event.key === "Z" || event.key === "y")) {
+        event.preventDefault();
+        press_putton(event);
+    }
+}
 function on_mouse_down(event) {
     let mouse_event = new Map();
     mouse_event.set("name", "mousedown");
@@ -80,6 +88,7 @@ document.getElementById("canvas").onmousemove = mouse_move
 document.getElementById("canvas").onwheel = onwheel;
 document.getElementById("canvas").onresize = resize;
 document.onkeypress = press_putton;
+document.onkeydown = on_key_down;
 document.getElementById("canvas").onmousedown = on_mouse_down;
 document.getElementById("canvas").onmouseup = on_mouse_up;
 SCREEN_X_SIZE = window.innerWidth;